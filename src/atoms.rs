@@ -0,0 +1,185 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use x11::xlib;
+
+macro_rules! atoms {
+    ($($field:ident = $name:expr,)*) => {
+        /**
+         * Every EWMH and ICCCM atom, interned once at startup
+         */
+        #[allow(dead_code)]
+        pub struct Atoms {
+            $(pub $field: xlib::Atom,)*
+        }
+
+        impl Atoms {
+            /**
+             * Interns all the atoms in a single round trip
+             */
+            pub fn intern(display: *mut xlib::Display) -> Atoms {
+                let names: Vec<CString> = vec![$(CString::new($name).unwrap(),)*];
+                let mut pointers: Vec<*mut c_char> =
+                    names.iter().map(|name| name.as_ptr() as *mut c_char).collect();
+                let mut atoms: Vec<xlib::Atom> = vec![0; names.len()];
+
+                unsafe {
+                    xlib::XInternAtoms(
+                        display,
+                        pointers.as_mut_ptr(),
+                        names.len() as i32,
+                        xlib::False,
+                        atoms.as_mut_ptr(),
+                    );
+                }
+
+                let mut atoms = atoms.into_iter();
+                Atoms {
+                    $($field: atoms.next().unwrap(),)*
+                }
+            }
+        }
+    };
+}
+
+atoms! {
+    utf8_string = "UTF8_STRING",
+    clipboard = "CLIPBOARD",
+    targets = "TARGETS",
+    incr = "INCR",
+
+    wm_protocols = "WM_PROTOCOLS",
+    wm_delete_window = "WM_DELETE_WINDOW",
+    wm_take_focus = "WM_TAKE_FOCUS",
+    wm_state = "WM_STATE",
+    wm_change_state = "WM_CHANGE_STATE",
+    wm_client_leader = "WM_CLIENT_LEADER",
+    wm_window_role = "WM_WINDOW_ROLE",
+    motif_wm_hints = "_MOTIF_WM_HINTS",
+    gtk_frame_extents = "_GTK_FRAME_EXTENTS",
+
+    net_supported = "_NET_SUPPORTED",
+    net_supporting_wm_check = "_NET_SUPPORTING_WM_CHECK",
+    net_client_list = "_NET_CLIENT_LIST",
+    net_client_list_stacking = "_NET_CLIENT_LIST_STACKING",
+    net_number_of_desktops = "_NET_NUMBER_OF_DESKTOPS",
+    net_desktop_geometry = "_NET_DESKTOP_GEOMETRY",
+    net_desktop_viewport = "_NET_DESKTOP_VIEWPORT",
+    net_current_desktop = "_NET_CURRENT_DESKTOP",
+    net_desktop_names = "_NET_DESKTOP_NAMES",
+    net_active_window = "_NET_ACTIVE_WINDOW",
+    net_workarea = "_NET_WORKAREA",
+    net_showing_desktop = "_NET_SHOWING_DESKTOP",
+    net_close_window = "_NET_CLOSE_WINDOW",
+    net_moveresize_window = "_NET_MOVERESIZE_WINDOW",
+    net_wm_moveresize = "_NET_WM_MOVERESIZE",
+    net_restack_window = "_NET_RESTACK_WINDOW",
+    net_request_frame_extents = "_NET_REQUEST_FRAME_EXTENTS",
+
+    net_wm_name = "_NET_WM_NAME",
+    net_wm_visible_name = "_NET_WM_VISIBLE_NAME",
+    net_wm_icon_name = "_NET_WM_ICON_NAME",
+    net_wm_window_opacity = "_NET_WM_WINDOW_OPACITY",
+    net_wm_cm_s0 = "_NET_WM_CM_S0",
+    net_wm_desktop = "_NET_WM_DESKTOP",
+    net_wm_window_type = "_NET_WM_WINDOW_TYPE",
+    net_wm_state = "_NET_WM_STATE",
+    net_wm_allowed_actions = "_NET_WM_ALLOWED_ACTIONS",
+    net_wm_strut = "_NET_WM_STRUT",
+    net_wm_strut_partial = "_NET_WM_STRUT_PARTIAL",
+    net_wm_icon_geometry = "_NET_WM_ICON_GEOMETRY",
+    net_wm_icon = "_NET_WM_ICON",
+    net_wm_pid = "_NET_WM_PID",
+    net_wm_user_time = "_NET_WM_USER_TIME",
+    net_wm_user_time_window = "_NET_WM_USER_TIME_WINDOW",
+    net_frame_extents = "_NET_FRAME_EXTENTS",
+    net_wm_opaque_region = "_NET_WM_OPAQUE_REGION",
+    net_wm_ping = "_NET_WM_PING",
+    net_wm_sync_request = "_NET_WM_SYNC_REQUEST",
+    net_wm_fullscreen_monitors = "_NET_WM_FULLSCREEN_MONITORS",
+    net_startup_id = "_NET_STARTUP_ID",
+    net_startup_info = "_NET_STARTUP_INFO",
+    net_startup_info_begin = "_NET_STARTUP_INFO_BEGIN",
+
+    net_wm_window_type_desktop = "_NET_WM_WINDOW_TYPE_DESKTOP",
+    net_wm_window_type_dock = "_NET_WM_WINDOW_TYPE_DOCK",
+    net_wm_window_type_toolbar = "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    net_wm_window_type_menu = "_NET_WM_WINDOW_TYPE_MENU",
+    net_wm_window_type_utility = "_NET_WM_WINDOW_TYPE_UTILITY",
+    net_wm_window_type_splash = "_NET_WM_WINDOW_TYPE_SPLASH",
+    net_wm_window_type_dialog = "_NET_WM_WINDOW_TYPE_DIALOG",
+    net_wm_window_type_dropdown_menu = "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    net_wm_window_type_popup_menu = "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    net_wm_window_type_tooltip = "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    net_wm_window_type_notification = "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    net_wm_window_type_combo = "_NET_WM_WINDOW_TYPE_COMBO",
+    net_wm_window_type_dnd = "_NET_WM_WINDOW_TYPE_DND",
+    net_wm_window_type_normal = "_NET_WM_WINDOW_TYPE_NORMAL",
+
+    net_wm_state_modal = "_NET_WM_STATE_MODAL",
+    net_wm_state_sticky = "_NET_WM_STATE_STICKY",
+    net_wm_state_maximized_vert = "_NET_WM_STATE_MAXIMIZED_VERT",
+    net_wm_state_maximized_horz = "_NET_WM_STATE_MAXIMIZED_HORZ",
+    net_wm_state_shaded = "_NET_WM_STATE_SHADED",
+    net_wm_state_skip_taskbar = "_NET_WM_STATE_SKIP_TASKBAR",
+    net_wm_state_skip_pager = "_NET_WM_STATE_SKIP_PAGER",
+    net_wm_state_hidden = "_NET_WM_STATE_HIDDEN",
+    net_wm_state_fullscreen = "_NET_WM_STATE_FULLSCREEN",
+    net_wm_state_above = "_NET_WM_STATE_ABOVE",
+    net_wm_state_below = "_NET_WM_STATE_BELOW",
+    net_wm_state_demands_attention = "_NET_WM_STATE_DEMANDS_ATTENTION",
+    net_wm_state_focused = "_NET_WM_STATE_FOCUSED",
+}
+
+impl Atoms {
+    /**
+     * Lists the hints advertised in _NET_SUPPORTED
+     */
+    pub fn supported(&self) -> Vec<xlib::Atom> {
+        vec![
+            self.net_supported,
+            self.net_supporting_wm_check,
+            self.net_client_list,
+            self.net_client_list_stacking,
+            self.net_active_window,
+            self.net_number_of_desktops,
+            self.net_current_desktop,
+            self.net_desktop_names,
+            self.net_desktop_geometry,
+            self.net_desktop_viewport,
+            self.net_workarea,
+            self.net_showing_desktop,
+            self.net_close_window,
+            self.net_wm_moveresize,
+            self.net_request_frame_extents,
+            self.net_frame_extents,
+            self.net_wm_name,
+            self.net_wm_desktop,
+            self.net_wm_user_time,
+            self.net_wm_user_time_window,
+            self.net_wm_ping,
+            self.net_wm_strut,
+            self.net_wm_strut_partial,
+            self.net_wm_window_type,
+            self.net_wm_window_type_desktop,
+            self.net_wm_window_type_dock,
+            self.net_wm_window_type_dropdown_menu,
+            self.net_wm_window_type_popup_menu,
+            self.net_wm_window_type_tooltip,
+            self.net_wm_window_type_notification,
+            self.net_wm_window_type_combo,
+            self.net_wm_window_type_dnd,
+            self.net_wm_window_type_splash,
+            self.net_wm_state,
+            self.net_wm_state_skip_taskbar,
+            self.net_wm_state_skip_pager,
+            self.net_wm_state_maximized_vert,
+            self.net_wm_state_maximized_horz,
+            self.net_wm_state_fullscreen,
+            self.net_wm_fullscreen_monitors,
+            self.net_wm_state_above,
+            self.net_wm_state_below,
+            self.net_wm_state_demands_attention,
+            self.net_startup_id,
+        ]
+    }
+}
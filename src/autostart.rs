@@ -0,0 +1,219 @@
+use decoration;
+use monitor;
+use rules::{self, Actions};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use toml::value::Table;
+use vector2d::Vector2D;
+use x11::xlib;
+use {
+    frame_extents, get_cardinal_list, get_gtk_extents, pointer_position, work_area, Candidate,
+    WindowManager,
+};
+
+/**
+ * Part of the work area a window started at login is put in
+ */
+#[derive(Clone, Copy)]
+pub enum Place {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Full,
+}
+
+/**
+ * A command from the configuration started along with the window manager,
+ * with where its window goes and what is done to it like for a rule
+ */
+pub struct Entry {
+    pub command: String,
+    pub place: Option<Place>,
+    pub actions: Actions,
+}
+
+/**
+ * A started command whose window has not shown up yet, and when it ended if
+ * it already did
+ */
+pub struct Pending {
+    id: String,
+    child: Child,
+    place: Option<Place>,
+    actions: Actions,
+    exited: Option<Instant>,
+}
+
+/**
+ * Parses an autostart table, giving what went wrong with it otherwise. Any
+ * key but the command and the place is read as a rule action.
+ */
+pub fn parse(table: &Table) -> Result<Entry, String> {
+    let mut actions = table.clone();
+
+    let command = actions
+        .remove("command")
+        .ok_or("'command' is missing")?
+        .as_str()
+        .ok_or("'command' must be a string")?
+        .to_string();
+
+    let place = match actions.remove("place") {
+        None => None,
+        Some(value) => Some(match value.as_str() {
+            Some("left") => Place::Left,
+            Some("right") => Place::Right,
+            Some("top") => Place::Top,
+            Some("bottom") => Place::Bottom,
+            Some("full") => Place::Full,
+            _ => return Err("'place' must be left, right, top, bottom or full".to_string()),
+        }),
+    };
+
+    for key in ["class", "instance", "title"].iter() {
+        if actions.contains_key(*key) {
+            return Err(format!("Unknown option '{}'", key));
+        }
+    }
+
+    Ok(Entry {
+        command,
+        place,
+        actions: rules::parse(&actions)?.actions,
+    })
+}
+
+/**
+ * Starts the configured commands, each with its own startup notification
+ * ID to recognize its window by. They run through sh -c, so their process
+ * is only the one of the window when the shell execs the command, as it
+ * does for a single simple command or one starting with exec.
+ */
+pub fn run(_wm: &mut WindowManager) {
+    for (i, entry) in _wm.config.autostart.iter().enumerate() {
+        let id = format!("gothite-autostart-{}-{}_TIME0", std::process::id(), i);
+
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&entry.command)
+            .env("DESKTOP_STARTUP_ID", &id)
+            .stdin(Stdio::null())
+            .spawn();
+
+        match child {
+            Ok(child) => {
+                info!("Started '{}'", entry.command);
+                _wm.autostart.push(Pending {
+                    id,
+                    child,
+                    place: entry.place,
+                    actions: entry.actions.clone(),
+                    exited: None,
+                });
+            }
+            Err(err) => warn!("Could not start '{}': {}", entry.command, err),
+        }
+    }
+}
+
+/**
+ * Finds the started command a new window belongs to, by its startup
+ * notification ID or else by its process while it runs, and slots the
+ * window where the configuration puts it
+ */
+pub fn claim(_wm: &mut WindowManager, candidate: &mut Candidate) {
+    if _wm.autostart.is_empty() {
+        return;
+    }
+
+    let pid = get_cardinal_list(_wm, candidate.window, _wm.atoms.net_wm_pid)
+        .first()
+        .map(|pid| *pid as u32);
+    let index = _wm.autostart.iter().position(|pending| {
+        candidate.startup_id.as_ref() == Some(&pending.id)
+            || (pending.exited.is_none() && pid == Some(pending.child.id()))
+    });
+    let pending = match index {
+        Some(index) => _wm.autostart.remove(index),
+        None => return,
+    };
+
+    debug!("Window {} was started at login", candidate.window);
+    rules::apply_actions(_wm, candidate, &pending.actions);
+
+    if let Some(place) = pending.place {
+        slot(_wm, candidate, place);
+    }
+
+    // The command stays tracked until it ends, so it does not linger as a
+    // zombie
+    _wm.autostart_done.push(pending.child);
+}
+
+/**
+ * Gives a new window the part of the work area of the monitor under the
+ * pointer its place asks for, frame included
+ */
+fn slot(_wm: &WindowManager, candidate: &mut Candidate, place: Place) {
+    let monitor = monitor::index_at(&_wm.monitors, pointer_position(_wm));
+    let (origin, size) = work_area(_wm, monitor);
+    let half = Vector2D::new(size.x / 2, size.y / 2);
+
+    let (position, size) = match place {
+        Place::Left => (origin, Vector2D::new(half.x, size.y)),
+        Place::Right => (
+            Vector2D::new(origin.x + half.x as i32, origin.y),
+            Vector2D::new(size.x - half.x, size.y),
+        ),
+        Place::Top => (origin, Vector2D::new(size.x, half.y)),
+        Place::Bottom => (
+            Vector2D::new(origin.x, origin.y + half.y as i32),
+            Vector2D::new(size.x, size.y - half.y),
+        ),
+        Place::Full => (origin, size),
+    };
+
+    let ext = frame_extents(
+        decoration::metrics(&_wm.config),
+        candidate.decorated,
+        get_gtk_extents(_wm, candidate.window),
+    );
+    let width = (size.x as i32 - ext.left - ext.right).max(1);
+    let height = (size.y as i32 - ext.top - ext.bottom).max(1);
+
+    candidate.attrs.x = position.x;
+    candidate.attrs.y = position.y;
+    candidate.attrs.width = width;
+    candidate.attrs.height = height;
+
+    unsafe {
+        xlib::XResizeWindow(_wm.display, candidate.window, width as u32, height as u32);
+    }
+}
+
+/**
+ * Collects the commands started at login that have ended since. Those
+ * still without a window are given up on once the startup timeout has
+ * passed, so a reused process ID or a stray startup ID cannot match them.
+ */
+pub fn reap(_wm: &mut WindowManager) {
+    // Commands that fork their program off may end before its window shows
+    for pending in _wm.autostart.iter_mut() {
+        if pending.exited.is_none() && matches!(pending.child.try_wait(), Ok(Some(_))) {
+            pending.exited = Some(Instant::now());
+        }
+    }
+
+    let timeout = Duration::from_secs(_wm.config.startup_timeout);
+    _wm.autostart.retain(|pending| {
+        let expired = pending.exited.is_some_and(|at| at.elapsed() >= timeout);
+        if expired {
+            debug!("No window showed up for {}", pending.id);
+        }
+        !expired
+    });
+
+    _wm.autostart_done
+        .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+}
@@ -0,0 +1,570 @@
+use cairo_sys;
+use libc;
+use std::ffi::CString;
+use std::fs;
+use std::io::Read;
+use std::mem::zeroed;
+use std::os::raw::c_char;
+use std::process::{Child, Command, Stdio};
+use std::ptr;
+use std::time::Duration;
+use strut::Strut;
+use vector2d::Vector2D;
+use x11::xlib;
+use {get_geometry, is_urgent, name_window, Layer, WindowManager};
+
+pub const HEIGHT: u32 = 24;
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+const MARGIN: f64 = 10.0;
+const SPACING: f64 = 16.0;
+
+/**
+ * Built-in status modules, shown on the right of the bar in order
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Module {
+    Datetime,
+    Battery,
+    Load,
+    Volume,
+}
+
+impl Module {
+    /**
+     * Looks up a module by its configuration name
+     */
+    pub fn from_name(name: &str) -> Option<Module> {
+        match name {
+            "datetime" => Some(Module::Datetime),
+            "battery" => Some(Module::Battery),
+            "loadavg" => Some(Module::Load),
+            "volume" => Some(Module::Volume),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * When a bar is shown. Fullscreen windows hide the bar of their monitor
+ * in every mode.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Always,
+    Dodge,
+    Hidden,
+}
+
+impl Mode {
+    /**
+     * Looks up a mode by its configuration name
+     */
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "always" => Some(Mode::Always),
+            "dodge" => Some(Mode::Dodge),
+            "hidden" => Some(Mode::Hidden),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * The bar window along the top of one monitor, with the strip along the
+ * edge that brings it back while it is out of the way
+ */
+struct Panel {
+    window: xlib::Window,
+    trigger: xlib::Window,
+    surface: *mut cairo_sys::cairo_surface_t,
+    context: *mut cairo_sys::cairo_t,
+    position: Vector2D<i32>,
+    size: Vector2D<u32>,
+    mode: Mode,
+    revealed: bool,
+}
+
+/**
+ * The bars on every monitor, with the latest text of each module
+ */
+pub struct Bar {
+    panels: Vec<Panel>,
+    texts: Vec<String>,
+    volume: String,
+    volume_command: Option<Child>,
+}
+
+impl Bar {
+    pub fn new() -> Bar {
+        Bar {
+            panels: Vec::new(),
+            texts: Vec::new(),
+            volume: String::new(),
+            volume_command: None,
+        }
+    }
+}
+
+/**
+ * Formats the local time with a strftime format
+ */
+fn datetime(format: &str) -> String {
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+
+    unsafe {
+        let now = libc::time(ptr::null_mut());
+        let mut local: libc::tm = zeroed();
+        libc::localtime_r(&now, &mut local);
+
+        let mut buffer = [0 as c_char; 256];
+        let length = libc::strftime(buffer.as_mut_ptr(), buffer.len(), format.as_ptr(), &local);
+
+        let bytes: Vec<u8> = buffer[..length].iter().map(|c| *c as u8).collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/**
+ * Reads the charge of the first battery from sysfs, marking it while it
+ * charges. Machines without a battery show nothing.
+ */
+fn battery() -> String {
+    let entries = match fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return String::new(),
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let read = |name: &str| {
+            fs::read_to_string(path.join(name))
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        if read("type") != "Battery" {
+            continue;
+        }
+
+        let capacity = read("capacity");
+        if capacity.is_empty() {
+            continue;
+        }
+
+        return match read("status").as_str() {
+            "Charging" => format!("BAT {}% +", capacity),
+            _ => format!("BAT {}%", capacity),
+        };
+    }
+
+    String::new()
+}
+
+/**
+ * Reads the one minute load average
+ */
+fn load() -> String {
+    fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|text| {
+            text.split_whitespace()
+                .next()
+                .map(|l| format!("LOAD {}", l))
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * Picks up the output of the volume command once it finished and starts it
+ * again, so a slow command never holds up the window manager
+ */
+fn volume(bar: &mut Bar, command: &str) -> String {
+    if command.is_empty() {
+        return String::new();
+    }
+
+    if let Some(mut child) = bar.volume_command.take() {
+        match child.try_wait() {
+            Ok(Some(_)) => {
+                let mut output = String::new();
+                if let Some(mut stdout) = child.stdout.take() {
+                    let _ = stdout.read_to_string(&mut output);
+                }
+                bar.volume = output.lines().next().unwrap_or("").trim().to_string();
+            }
+            Ok(None) => {
+                bar.volume_command = Some(child);
+                return bar.volume.clone();
+            }
+            Err(_) => {}
+        }
+    }
+
+    bar.volume_command = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok();
+
+    bar.volume.clone()
+}
+
+/**
+ * Creates a bar on every monitor, replacing any there were, and reserves the
+ * space they take
+ */
+pub fn create(_wm: &mut WindowManager) {
+    destroy(_wm);
+
+    let areas: Vec<(Vector2D<i32>, Vector2D<u32>, Mode)> = _wm
+        .monitors
+        .iter()
+        .map(|monitor| {
+            (
+                monitor.position,
+                monitor.size,
+                monitor_mode(_wm, &monitor.name),
+            )
+        })
+        .collect();
+
+    for (position, area, mode) in areas {
+        let size = Vector2D::new(area.x, HEIGHT);
+
+        unsafe {
+            let screen = xlib::XDefaultScreen(_wm.display);
+            let visual = xlib::XDefaultVisual(_wm.display, screen);
+            let depth = xlib::XDefaultDepth(_wm.display, screen);
+
+            let mut attributes: xlib::XSetWindowAttributes = zeroed();
+            attributes.override_redirect = xlib::True;
+            attributes.event_mask =
+                xlib::ExposureMask | xlib::ButtonPressMask | xlib::LeaveWindowMask;
+
+            let window = xlib::XCreateWindow(
+                _wm.display,
+                _wm.root,
+                position.x,
+                position.y,
+                size.x,
+                size.y,
+                0,
+                depth,
+                xlib::InputOutput as u32,
+                visual,
+                xlib::CWOverrideRedirect | xlib::CWEventMask,
+                &mut attributes,
+            );
+            name_window(_wm.display, window, "gothite-bar");
+
+            let surface = cairo_sys::cairo_xlib_surface_create(
+                _wm.display,
+                window,
+                visual,
+                size.x as i32,
+                size.y as i32,
+            );
+            let context = cairo_sys::cairo_create(surface);
+
+            // Only a bar that is always there keeps windows out of its way,
+            // reserved like a panel would with _NET_WM_STRUT_PARTIAL
+            if mode == Mode::Always {
+                let left = position.x as i64;
+                let right = left + area.x as i64 - 1;
+                let top = position.y as i64 + HEIGHT as i64;
+                let strut = Strut::parse(&[0, 0, top, 0, 0, 0, 0, 0, left, right, 0, 0]);
+
+                if let Some(strut) = strut {
+                    _wm.struts.insert(window, strut);
+                }
+            }
+
+            let mut attributes: xlib::XSetWindowAttributes = zeroed();
+            attributes.override_redirect = xlib::True;
+            attributes.event_mask = xlib::EnterWindowMask;
+
+            let trigger = xlib::XCreateWindow(
+                _wm.display,
+                _wm.root,
+                position.x,
+                position.y,
+                size.x,
+                1,
+                0,
+                0,
+                xlib::InputOnly as u32,
+                ptr::null_mut(),
+                xlib::CWOverrideRedirect | xlib::CWEventMask,
+                &mut attributes,
+            );
+            name_window(_wm.display, trigger, "gothite-bar-trigger");
+
+            _wm.unmanaged.push((window, Layer::Dock));
+            _wm.unmanaged.push((trigger, Layer::Popup));
+
+            _wm.bar.panels.push(Panel {
+                window,
+                trigger,
+                surface,
+                context,
+                position,
+                size,
+                mode,
+                revealed: false,
+            });
+        }
+    }
+
+    update(_wm);
+    refresh(_wm);
+}
+
+/**
+ * Finds the mode for the bar of a monitor. Parts of a split output go by
+ * the name of the output.
+ */
+fn monitor_mode(_wm: &WindowManager, name: &str) -> Mode {
+    let output = name.split(" (").next().unwrap_or(name);
+    let modes = &_wm.config.bar_monitors;
+
+    modes
+        .get(name)
+        .or_else(|| modes.get(output))
+        .cloned()
+        .unwrap_or(_wm.config.bar_mode)
+}
+
+/**
+ * Checks if a visible window on the current workspace overlaps a bar, only
+ * counting fullscreen ones if asked to
+ */
+fn overlapped(_wm: &WindowManager, panel: &Panel, fullscreen_only: bool) -> bool {
+    let (left, top) = (panel.position.x, panel.position.y);
+    let (right, bottom) = (left + panel.size.x as i32, top + panel.size.y as i32);
+
+    let tree = &_wm.workspaces[_wm.workspace].tree;
+
+    _wm.windows.iter().any(|(w, win)| {
+        if !tree.contains(*w) || win.minimized || win.hidden {
+            return false;
+        }
+        if fullscreen_only && !win.fullscreen {
+            return false;
+        }
+
+        let (position, size) = get_geometry(_wm, win.frame);
+        position.x < right
+            && position.y < bottom
+            && position.x + size.x as i32 > left
+            && position.y + size.y as i32 > top
+    })
+}
+
+/**
+ * Shows or hides each bar as its mode asks, after windows moved, changed
+ * state or the workspace changed
+ */
+pub fn update(_wm: &WindowManager) {
+    for panel in _wm.bar.panels.iter() {
+        let visible = !overlapped(_wm, panel, true)
+            && match panel.mode {
+                Mode::Always => true,
+                Mode::Dodge => panel.revealed || !overlapped(_wm, panel, false),
+                Mode::Hidden => panel.revealed,
+            };
+
+        unsafe {
+            if visible {
+                xlib::XMapWindow(_wm.display, panel.window);
+                xlib::XUnmapWindow(_wm.display, panel.trigger);
+            } else {
+                xlib::XUnmapWindow(_wm.display, panel.window);
+                if panel.mode != Mode::Always {
+                    xlib::XMapWindow(_wm.display, panel.trigger);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Brings back a bar when the pointer touches the edge of its monitor
+ */
+pub fn on_enter(_wm: &mut WindowManager, window: xlib::Window) -> bool {
+    match _wm
+        .bar
+        .panels
+        .iter_mut()
+        .find(|panel| panel.trigger == window)
+    {
+        Some(panel) => panel.revealed = true,
+        None => return false,
+    }
+
+    update(_wm);
+    true
+}
+
+/**
+ * Lets a bar that was brought back get out of the way again once the
+ * pointer leaves it
+ */
+pub fn on_leave(_wm: &mut WindowManager, window: xlib::Window) -> bool {
+    match _wm
+        .bar
+        .panels
+        .iter_mut()
+        .find(|panel| panel.window == window)
+    {
+        Some(panel) if panel.revealed => panel.revealed = false,
+        Some(_) => return true,
+        None => return false,
+    }
+
+    update(_wm);
+    true
+}
+
+/**
+ * Removes the bars and gives back their space
+ */
+pub fn destroy(_wm: &mut WindowManager) {
+    for panel in _wm.bar.panels.drain(..) {
+        _wm.struts.remove(&panel.window);
+        _wm.unmanaged
+            .retain(|(w, _)| *w != panel.window && *w != panel.trigger);
+
+        unsafe {
+            cairo_sys::cairo_destroy(panel.context);
+            cairo_sys::cairo_surface_destroy(panel.surface);
+            xlib::XDestroyWindow(_wm.display, panel.window);
+            xlib::XDestroyWindow(_wm.display, panel.trigger);
+        }
+    }
+}
+
+/**
+ * Updates the text of every module and redraws the bars
+ */
+pub fn refresh(_wm: &mut WindowManager) {
+    let modules = _wm.config.bar_modules.clone();
+    let texts = modules
+        .iter()
+        .map(|module| match module {
+            Module::Datetime => datetime(&_wm.config.bar_datetime_format),
+            Module::Battery => battery(),
+            Module::Load => load(),
+            Module::Volume => {
+                let command = _wm.config.bar_volume_command.clone();
+                volume(&mut _wm.bar, &command)
+            }
+        })
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    _wm.bar.texts = texts;
+    draw(_wm);
+}
+
+/**
+ * Renders one bar: the workspaces on the left, the modules on the right
+ */
+fn draw_panel(_wm: &WindowManager, panel: &Panel) {
+    let _ctx = panel.context;
+    let width = panel.size.x as f64;
+    let height = panel.size.y as f64;
+
+    unsafe {
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.18, 0.204, 0.251);
+        cairo_sys::cairo_paint(_ctx);
+
+        let top = (height - _wm.font.height(_ctx)) / 2.0;
+
+        let mut x = 0.0;
+        for (i, name) in _wm.workspace_names.iter().enumerate() {
+            let cell = _wm.font.text_width(_ctx, name) + MARGIN * 2.0;
+
+            let urgent = _wm.workspaces[i]
+                .tree
+                .clients()
+                .iter()
+                .any(|w| is_urgent(&_wm.windows[w]));
+
+            if i == _wm.workspace {
+                cairo_sys::cairo_set_source_rgb(_ctx, 0.298, 0.337, 0.416);
+                cairo_sys::cairo_rectangle(_ctx, x, 0.0, cell, height);
+                cairo_sys::cairo_fill(_ctx);
+            } else if urgent {
+                cairo_sys::cairo_set_source_rgb(_ctx, 0.816, 0.529, 0.439);
+                cairo_sys::cairo_rectangle(_ctx, x, 0.0, cell, height);
+                cairo_sys::cairo_fill(_ctx);
+            }
+
+            cairo_sys::cairo_set_source_rgb(_ctx, 0.925, 0.937, 0.957);
+            _wm.font.draw_text(_ctx, name, x + MARGIN, top);
+            x += cell;
+        }
+
+        let mut x = width - MARGIN;
+        for text in _wm.bar.texts.iter().rev() {
+            x -= _wm.font.text_width(_ctx, text);
+            _wm.font.draw_text(_ctx, text, x, top);
+            x -= SPACING;
+        }
+
+        cairo_sys::cairo_surface_flush(panel.surface);
+    }
+}
+
+/**
+ * Redraws every bar, after the workspace changed or a module updated
+ */
+pub fn draw(_wm: &WindowManager) {
+    for panel in _wm.bar.panels.iter() {
+        draw_panel(_wm, panel);
+    }
+}
+
+/**
+ * Redraws a bar after it was exposed
+ */
+pub fn on_expose(_wm: &WindowManager, window: xlib::Window) -> bool {
+    match _wm.bar.panels.iter().find(|panel| panel.window == window) {
+        Some(panel) => {
+            draw_panel(_wm, panel);
+            true
+        }
+        None => false,
+    }
+}
+
+/**
+ * Checks if a window is one of the bars
+ */
+pub fn is_bar(_wm: &WindowManager, window: xlib::Window) -> bool {
+    _wm.bar.panels.iter().any(|panel| panel.window == window)
+}
+
+/**
+ * Finds the workspace drawn at a horizontal position of a bar
+ */
+pub fn workspace_at(_wm: &WindowManager, window: xlib::Window, x: i32) -> Option<usize> {
+    let panel = _wm.bar.panels.iter().find(|panel| panel.window == window)?;
+
+    let mut right = 0.0;
+    for (i, name) in _wm.workspace_names.iter().enumerate() {
+        right += _wm.font.text_width(panel.context, name) + MARGIN * 2.0;
+        if (x as f64) < right {
+            return Some(i);
+        }
+    }
+
+    None
+}
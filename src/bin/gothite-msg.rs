@@ -0,0 +1,108 @@
+use std::env;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::process;
+
+#[path = "../ipc.rs"]
+#[allow(dead_code)]
+mod ipc;
+
+const USAGE: &str = "Usage: gothite-msg <command> [--class NAME] [--title TEXT] [--id ID]
+
+Commands:
+  focus       Bring the window into view and focus it
+  focus left|right|up|down
+              Move the focus to the nearest window that way, on to the
+              next monitor past the last one
+  raise       Raise the windows
+  close       Close the windows
+  move X Y    Move the windows to a position
+  float       Take the windows out of the tiling layout, back where they
+              were last floating, or put them back in
+  send NAME   Move the windows to a workspace, created if there is none
+              by that name
+  reload      Reread the configuration file and print any problems in it
+  profile [NAME]
+              Switch to a profile from the configuration file until the next
+              one on its schedule, or print the profile in use
+  layout [NAME]
+              Switch the current workspace to a layout, which it keeps when
+              switching away and back, or print the layout it uses
+  nightlight [TEMPERATURE|off|auto]
+              Set the color temperature of the screens, like 3500K, until
+              the schedule turns to day or night, go back to the schedule
+              with auto, or print the temperature in use
+  workspace NAME
+              Switch to a workspace, creating it if there is none by that
+              name, to be removed again once left empty
+
+  rule add KEY=VALUE... [--save]
+              Add a rule for new windows, with the keys of a rule in the
+              configuration file, and with --save append it to that file
+  rule remove N
+              Remove the rule numbered N by rule list, until the next reload
+  rule list   Print the rules in effect
+
+  clients [NAME] [--all]
+              Print the windows on a workspace, the current one by default,
+              top first with their id, workspace, class and title. Windows
+              that ask to be left out of taskbars or pagers are only listed
+              with --all
+  visible [N] [--all]
+              Print the windows shown on monitor N, counting from 1, or on
+              every monitor
+
+Without any criteria the focused window is used, except by clients and
+visible which then print every window.";
+
+/**
+ * Sends a request to the window manager and waits for the reply
+ */
+fn send(args: &[String]) -> Result<String, String> {
+    let path = ipc::socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|err| format!("cannot connect to {}: {}", path.display(), err))?;
+
+    let mut reply = String::new();
+    stream
+        .write_all(args.join("\0").as_bytes())
+        .and_then(|_| stream.shutdown(Shutdown::Write))
+        .and_then(|_| stream.read_to_string(&mut reply))
+        .map_err(|err| err.to_string())?;
+
+    Ok(reply)
+}
+
+/**
+ * Program
+ */
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args[0] == "--help" || args[0] == "-h" {
+        println!("{}", USAGE);
+        return;
+    }
+
+    // Catch mistakes before bothering the window manager
+    if let Err(err) = ipc::Request::parse(&args) {
+        eprintln!("gothite-msg: {}", err);
+        process::exit(2);
+    }
+
+    match send(&args) {
+        Ok(ref reply) if reply.starts_with("error: ") => {
+            eprintln!("gothite-msg: {}", &reply[7..]);
+            process::exit(1);
+        }
+        Ok(reply) => {
+            if !reply.is_empty() {
+                println!("{}", reply);
+            }
+        }
+        Err(err) => {
+            eprintln!("gothite-msg: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use monitor;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_void;
+use std::ptr;
+use x11::{xlib, xrandr};
+
+/**
+ * Extensions worth reporting, with their protocol names
+ */
+const EXTENSIONS: [(&str, &str); 5] = [
+    ("RandR", "RANDR"),
+    ("Shape", "SHAPE"),
+    ("XFixes", "XFIXES"),
+    ("Composite", "Composite"),
+    ("XInput", "XInputExtension"),
+];
+
+/**
+ * Interns an atom by name
+ */
+fn atom(display: *mut xlib::Display, name: &str) -> xlib::Atom {
+    let name = CString::new(name).unwrap();
+    unsafe { xlib::XInternAtom(display, name.as_ptr(), xlib::False) }
+}
+
+/**
+ * Reads the raw contents of a property, as 32 bit values or as bytes
+ */
+fn read_property(
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    property: xlib::Atom,
+) -> Option<(Vec<u64>, Vec<u8>)> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            display,
+            window,
+            property,
+            0,
+            1024,
+            xlib::False,
+            xlib::AnyPropertyType as u64,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return None;
+        }
+
+        // Xlib hands out 32 bit values as longs
+        let result = match actual_format {
+            32 => (
+                std::slice::from_raw_parts(data as *const u64, count as usize).to_vec(),
+                Vec::new(),
+            ),
+            8 => (
+                Vec::new(),
+                std::slice::from_raw_parts(data, count as usize).to_vec(),
+            ),
+            _ => (Vec::new(), Vec::new()),
+        };
+
+        xlib::XFree(data as *mut c_void);
+        Some(result)
+    }
+}
+
+/**
+ * Finds out which window manager, if any, runs on a screen
+ */
+fn window_manager(display: *mut xlib::Display, screen: i32) -> String {
+    let owner =
+        unsafe { xlib::XGetSelectionOwner(display, atom(display, &format!("WM_S{}", screen))) };
+    let root = unsafe { xlib::XRootWindow(display, screen) };
+
+    let check = read_property(display, root, atom(display, "_NET_SUPPORTING_WM_CHECK"))
+        .and_then(|(values, _)| values.first().cloned());
+    let name = check
+        .and_then(|window| read_property(display, window, atom(display, "_NET_WM_NAME")))
+        .map(|(_, bytes)| String::from_utf8_lossy(&bytes).into_owned());
+
+    match (name, owner) {
+        (Some(name), _) => format!("{} (EWMH check window 0x{:x})", name, check.unwrap()),
+        (None, 0) => "none detected".to_string(),
+        (None, owner) => format!("unnamed, WM_S{} owned by 0x{:x}", screen, owner),
+    }
+}
+
+/**
+ * Prints what the X server offers, for bug reports
+ */
+pub fn run(display: *mut xlib::Display) {
+    unsafe {
+        let name = CStr::from_ptr(xlib::XDisplayString(display)).to_string_lossy();
+        let vendor = CStr::from_ptr(xlib::XServerVendor(display)).to_string_lossy();
+
+        println!("Display:    {}", name);
+        println!(
+            "Server:     {} (release {})",
+            vendor,
+            xlib::XVendorRelease(display)
+        );
+        println!(
+            "Protocol:   X{}.{}",
+            xlib::XProtocolVersion(display),
+            xlib::XProtocolRevision(display)
+        );
+
+        println!();
+        println!("Extensions:");
+        for (label, extension) in EXTENSIONS.iter() {
+            let extension = CString::new(*extension).unwrap();
+            let (mut opcode, mut event, mut error) = (0, 0, 0);
+            let present = xlib::XQueryExtension(
+                display,
+                extension.as_ptr(),
+                &mut opcode,
+                &mut event,
+                &mut error,
+            ) != 0;
+
+            let status = if !present {
+                "missing".to_string()
+            } else if *label == "RandR" {
+                let (mut major, mut minor) = (0, 0);
+                xrandr::XRRQueryVersion(display, &mut major, &mut minor);
+                format!("{}.{}", major, minor)
+            } else {
+                "present".to_string()
+            };
+
+            println!("  {:<10} {}", label, status);
+        }
+
+        for screen in 0..xlib::XScreenCount(display) {
+            let root = xlib::XRootWindow(display, screen);
+
+            println!();
+            println!(
+                "Screen {}:   {}x{}, depth {}, root 0x{:x}",
+                screen,
+                xlib::XDisplayWidth(display, screen),
+                xlib::XDisplayHeight(display, screen),
+                xlib::XDefaultDepth(display, screen),
+                root
+            );
+            println!("  WM:       {}", window_manager(display, screen));
+
+            for monitor in monitor::query(display, root, &HashMap::new()) {
+                println!(
+                    "  Monitor:  {} {}x{}+{}+{}",
+                    monitor.name,
+                    monitor.size.x,
+                    monitor.size.y,
+                    monitor.position.x,
+                    monitor.position.y
+                );
+            }
+        }
+    }
+}
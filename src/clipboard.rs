@@ -0,0 +1,281 @@
+use std::mem::zeroed;
+use std::os::raw::c_void;
+use std::ptr;
+use std::time::Duration;
+use x11::xlib;
+use {name_window, WindowManager};
+
+/**
+ * How long a client gets to hand over the clipboard before it is killed
+ */
+pub const TIMEOUT: Duration = Duration::from_millis(500);
+
+/**
+ * Xorg gives each client 21 bits of resource ids, so windows of the same
+ * client share the bits above them
+ */
+const CLIENT_MASK: xlib::Window = !0x1F_FFFF;
+
+/**
+ * Largest contents taken over, in 32 bit units as XGetWindowProperty counts
+ */
+const MAX_LENGTH: i64 = 1 << 22;
+
+/**
+ * Contents of the clipboard kept after its owner was killed
+ */
+pub struct Clipboard {
+    window: xlib::Window,
+    pending: Option<xlib::Window>,
+    contents: Option<Vec<u8>>,
+}
+
+impl Clipboard {
+    /**
+     * Creates the window that receives and owns the clipboard contents
+     */
+    pub fn new(display: *mut xlib::Display, root: xlib::Window) -> Clipboard {
+        unsafe {
+            let mut attributes: xlib::XSetWindowAttributes = zeroed();
+            let window = xlib::XCreateWindow(
+                display,
+                root,
+                -1,
+                -1,
+                1,
+                1,
+                0,
+                0,
+                xlib::InputOnly as u32,
+                ptr::null_mut(),
+                0,
+                &mut attributes,
+            );
+            name_window(display, window, "gothite-clipboard");
+
+            Clipboard {
+                window,
+                pending: None,
+                contents: None,
+            }
+        }
+    }
+}
+
+/**
+ * Checks if the clipboard belongs to the client of a window
+ */
+pub fn owned_by(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    let owner = unsafe { xlib::XGetSelectionOwner(_wm.display, _wm.atoms.clipboard) };
+
+    owner != 0 && owner != _wm.clipboard.window && owner & CLIENT_MASK == _w & CLIENT_MASK
+}
+
+/**
+ * Checks if a client is already asked for the clipboard
+ */
+pub fn is_saving(_wm: &WindowManager) -> bool {
+    _wm.clipboard.pending.is_some()
+}
+
+/**
+ * Asks the owner of the clipboard for its contents as text, to take them over
+ * before the owner gets killed
+ */
+pub fn save(_wm: &mut WindowManager, _w: xlib::Window) {
+    debug!("Saving the clipboard of {}", _w);
+    _wm.clipboard.pending = Some(_w);
+
+    unsafe {
+        xlib::XConvertSelection(
+            _wm.display,
+            _wm.atoms.clipboard,
+            _wm.atoms.utf8_string,
+            _wm.atoms.clipboard,
+            _wm.clipboard.window,
+            _wm.event_time,
+        );
+    }
+}
+
+/**
+ * Gives up on saving the clipboard of a window, giving the window to kill
+ */
+pub fn give_up(_wm: &mut WindowManager, _w: xlib::Window) -> Option<xlib::Window> {
+    if _wm.clipboard.pending != Some(_w) {
+        return None;
+    }
+
+    warn!("Client {} did not hand over the clipboard in time", _w);
+    _wm.clipboard.pending.take()
+}
+
+/**
+ * Reads the converted contents and takes over the clipboard with them,
+ * giving the window that waits to be killed
+ */
+pub fn on_selection_notify(
+    _wm: &mut WindowManager,
+    _e: xlib::XSelectionEvent,
+) -> Option<xlib::Window> {
+    if _e.requestor != _wm.clipboard.window || _wm.clipboard.pending.is_none() {
+        return None;
+    }
+
+    let contents = if _e.property == 0 {
+        None
+    } else {
+        read_contents(_wm)
+    };
+
+    match contents {
+        Some(contents) => unsafe {
+            debug!("Took over {} bytes of clipboard", contents.len());
+            _wm.clipboard.contents = Some(contents);
+            xlib::XSetSelectionOwner(
+                _wm.display,
+                _wm.atoms.clipboard,
+                _wm.clipboard.window,
+                _e.time,
+            );
+        },
+        None => warn!("Could not save the clipboard"),
+    }
+
+    _wm.clipboard.pending.take()
+}
+
+/**
+ * Reads and deletes the property the contents were converted into
+ */
+fn read_contents(_wm: &WindowManager) -> Option<Vec<u8>> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            _wm.display,
+            _wm.clipboard.window,
+            _wm.atoms.clipboard,
+            0,
+            MAX_LENGTH,
+            xlib::True,
+            xlib::AnyPropertyType as u64,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return None;
+        }
+
+        // Contents sent in chunks are too large to be worth keeping
+        let result = if actual_format == 8 && actual_type != _wm.atoms.incr && remaining == 0 {
+            Some(std::slice::from_raw_parts(data, count as usize).to_vec())
+        } else {
+            None
+        };
+
+        xlib::XFree(data as *mut c_void);
+        result
+    }
+}
+
+/**
+ * Converts UTF-8 text to the Latin-1 that STRING holds, with a question mark
+ * for each character it has no room for
+ */
+fn latin1(text: &[u8]) -> Vec<u8> {
+    String::from_utf8_lossy(text)
+        .chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect()
+}
+
+/**
+ * Hands the kept contents to a client pasting them
+ */
+pub fn on_selection_request(_wm: &WindowManager, _e: xlib::XSelectionRequestEvent) {
+    if _e.owner != _wm.clipboard.window {
+        return;
+    }
+
+    // Obsolete clients leave it to the owner to pick a property
+    let property = if _e.property == 0 {
+        _e.target
+    } else {
+        _e.property
+    };
+
+    let answered = match _wm.clipboard.contents {
+        Some(ref contents) if _e.selection == _wm.atoms.clipboard => unsafe {
+            if _e.target == _wm.atoms.targets {
+                let targets = [_wm.atoms.targets, _wm.atoms.utf8_string, xlib::XA_STRING];
+                xlib::XChangeProperty(
+                    _wm.display,
+                    _e.requestor,
+                    property,
+                    xlib::XA_ATOM,
+                    32,
+                    xlib::PropModeReplace,
+                    targets.as_ptr() as *const u8,
+                    targets.len() as i32,
+                );
+                true
+            } else if _e.target == _wm.atoms.utf8_string || _e.target == xlib::XA_STRING {
+                let text = if _e.target == xlib::XA_STRING {
+                    latin1(contents)
+                } else {
+                    contents.clone()
+                };
+                xlib::XChangeProperty(
+                    _wm.display,
+                    _e.requestor,
+                    property,
+                    _e.target,
+                    8,
+                    xlib::PropModeReplace,
+                    text.as_ptr(),
+                    text.len() as i32,
+                );
+                true
+            } else {
+                false
+            }
+        },
+        _ => false,
+    };
+
+    unsafe {
+        let mut reply: xlib::XEvent = zeroed();
+        reply.selection = xlib::XSelectionEvent {
+            type_: xlib::SelectionNotify,
+            serial: 0,
+            send_event: xlib::True,
+            display: _wm.display,
+            requestor: _e.requestor,
+            selection: _e.selection,
+            target: _e.target,
+            property: if answered { property } else { 0 },
+            time: _e.time,
+        };
+
+        xlib::XSendEvent(_wm.display, _e.requestor, xlib::False, 0, &mut reply);
+    }
+}
+
+/**
+ * Forgets the kept contents once something else was copied
+ */
+pub fn on_selection_clear(_wm: &mut WindowManager, _e: xlib::XSelectionClearEvent) {
+    if _e.window == _wm.clipboard.window {
+        debug!("Clipboard taken over by another client");
+        _wm.clipboard.contents = None;
+    }
+}
@@ -0,0 +1,1006 @@
+use autostart::{self, Entry};
+use bar::{Mode, Module};
+use decoration::{self, Button};
+use focus;
+use layout;
+use nightlight::{self, Schedule};
+use profiles::{self, Profile};
+use rules::{self, Rule};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use toml::value::Table;
+use toml::Value;
+
+/**
+ * Version of the configuration format, bumped whenever options are renamed
+ * or change meaning
+ */
+pub const VERSION: i64 = 1;
+
+/**
+ * Options that were renamed, with the version that renamed them. Values
+ * under the old name are still read, with a warning.
+ */
+const RENAMED: &[(&str, &str, i64)] = &[];
+
+/**
+ * Every option the current version understands
+ */
+const OPTIONS: &[&str] = &[
+    "version",
+    "min_width",
+    "min_height",
+    "font",
+    "font_fallback",
+    "startup_timeout",
+    "buttons_left",
+    "buttons_right",
+    "workspaces",
+    "workspace_autoname",
+    "workspace_scroll",
+    "workspace_scroll_reverse",
+    "passthrough_keys",
+    "monitor_split",
+    "focus_policy",
+    "focus_follows_mouse",
+    "raise_on_focus",
+    "snap_grid",
+    "key_move_step",
+    "key_move_rate",
+    "edge_maximize",
+    "edge_flip",
+    "large_targets",
+    "theme",
+    "zoom",
+    "background",
+    "layout",
+    "master_width",
+    "gaps_inner",
+    "gaps_outer",
+    "bar",
+    "bar_modules",
+    "bar_datetime_format",
+    "bar_volume_command",
+    "notifications",
+    "preserve_clipboard",
+    "nightlight",
+    "rules",
+    "autostart",
+    "profiles",
+];
+
+/**
+ * User configuration
+ */
+pub struct Config {
+    pub min_width: u32,
+    pub min_height: u32,
+    pub font: String,
+    pub font_fallback: Vec<String>,
+    pub startup_timeout: u64,
+    pub buttons_left: Vec<Button>,
+    pub buttons_right: Vec<Button>,
+    pub workspaces: Vec<String>,
+    pub workspace_autoname: bool,
+    pub workspace_scroll: bool,
+    pub workspace_scroll_reverse: bool,
+    pub passthrough_keys: Vec<String>,
+    pub monitor_split: HashMap<String, u32>,
+    pub focus_policy: String,
+    pub focus_follows_mouse: bool,
+    pub raise_on_focus: bool,
+    pub snap_grid: u32,
+    pub key_move_step: u32,
+    pub key_move_rate: u32,
+    pub edge_maximize: bool,
+    pub edge_flip: u32,
+    pub large_targets: bool,
+    pub theme: String,
+    pub zoom: u32,
+    pub background: u64,
+    pub layout: String,
+    pub master_width: u32,
+    pub gaps_inner: u32,
+    pub gaps_outer: u32,
+    pub bar_mode: Mode,
+    pub bar_monitors: HashMap<String, Mode>,
+    pub bar_modules: Vec<Module>,
+    pub bar_datetime_format: String,
+    pub bar_volume_command: String,
+    pub notifications: bool,
+    pub preserve_clipboard: bool,
+    pub nightlight: Option<Schedule>,
+    pub rules: Vec<Rule>,
+    pub autostart: Vec<Entry>,
+    pub profiles: Vec<Profile>,
+    pub warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            min_width: 10,
+            min_height: 10,
+            font: "Sans 10".to_string(),
+            font_fallback: vec!["emoji".to_string()],
+            startup_timeout: 15,
+            buttons_left: vec![Button::Icon],
+            buttons_right: vec![Button::Minimize, Button::Maximize, Button::Close],
+            workspaces: (1..5).map(|n| n.to_string()).collect(),
+            workspace_autoname: false,
+            workspace_scroll: true,
+            workspace_scroll_reverse: false,
+            passthrough_keys: Vec::new(),
+            monitor_split: HashMap::new(),
+            focus_policy: "click".to_string(),
+            focus_follows_mouse: false,
+            raise_on_focus: true,
+            snap_grid: 0,
+            key_move_step: 10,
+            key_move_rate: 30,
+            edge_maximize: false,
+            edge_flip: 0,
+            large_targets: false,
+            theme: "dark".to_string(),
+            zoom: 200,
+            background: 0x2E3440,
+            layout: layout::FLOATING.to_string(),
+            master_width: 50,
+            gaps_inner: 0,
+            gaps_outer: 0,
+            bar_mode: Mode::Always,
+            bar_monitors: HashMap::new(),
+            bar_modules: vec![Module::Load, Module::Battery, Module::Datetime],
+            bar_datetime_format: "%a %d %b %H:%M".to_string(),
+            bar_volume_command: String::new(),
+            notifications: false,
+            preserve_clipboard: false,
+            nightlight: None,
+            rules: Vec::new(),
+            autostart: Vec::new(),
+            profiles: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /**
+     * Loads the configuration file, falling back to defaults
+     */
+    pub fn load() -> Config {
+        Config::read().unwrap_or_else(|err| {
+            error!("{}", err);
+            Config::default()
+        })
+    }
+
+    /**
+     * Reads the configuration file. A missing file gives the defaults, but
+     * one that cannot be parsed is an error.
+     */
+    pub fn read() -> Result<Config, String> {
+        let mut config = Config::default();
+
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(config),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                debug!("No configuration file at {}", path.display());
+                return Ok(config);
+            }
+        };
+
+        let value = contents
+            .parse::<Value>()
+            .map_err(|err| format!("Failed to parse {}: {}", path.display(), err))?;
+
+        info!("Loaded configuration from {}", path.display());
+        config.apply(&value);
+
+        Ok(config)
+    }
+
+    /**
+     * Applies the values found in a parsed configuration file
+     */
+    fn apply(&mut self, _v: &Value) {
+        let migrated = self.migrate(_v);
+        let _v = &migrated;
+
+        read_u32(_v, "min_width", &mut self.min_width, &mut self.warnings);
+        read_u32(_v, "min_height", &mut self.min_height, &mut self.warnings);
+        read_font(_v, "font", &mut self.font, &mut self.warnings);
+        read_font_fallback(
+            _v,
+            "font_fallback",
+            &mut self.font_fallback,
+            &mut self.warnings,
+        );
+        read_u64(
+            _v,
+            "startup_timeout",
+            &mut self.startup_timeout,
+            &mut self.warnings,
+        );
+        read_buttons(
+            _v,
+            "buttons_left",
+            &mut self.buttons_left,
+            &mut self.warnings,
+        );
+        read_buttons(
+            _v,
+            "buttons_right",
+            &mut self.buttons_right,
+            &mut self.warnings,
+        );
+        read_string_list(_v, "workspaces", &mut self.workspaces, &mut self.warnings);
+        read_bool(
+            _v,
+            "workspace_autoname",
+            &mut self.workspace_autoname,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "workspace_scroll",
+            &mut self.workspace_scroll,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "workspace_scroll_reverse",
+            &mut self.workspace_scroll_reverse,
+            &mut self.warnings,
+        );
+        read_string_list(
+            _v,
+            "passthrough_keys",
+            &mut self.passthrough_keys,
+            &mut self.warnings,
+        );
+        read_split(
+            _v,
+            "monitor_split",
+            &mut self.monitor_split,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "focus_follows_mouse",
+            &mut self.focus_follows_mouse,
+            &mut self.warnings,
+        );
+        // Only for the table that sets the old option, so profiles applied
+        // over the file keep the policy it picked
+        if self.focus_follows_mouse
+            && _v.get("focus_follows_mouse").is_some()
+            && _v.get("focus_policy").is_none()
+        {
+            self.focus_policy = "sloppy".to_string();
+        }
+        read_focus_policy(
+            _v,
+            "focus_policy",
+            &mut self.focus_policy,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "raise_on_focus",
+            &mut self.raise_on_focus,
+            &mut self.warnings,
+        );
+        read_u32(_v, "snap_grid", &mut self.snap_grid, &mut self.warnings);
+        read_u32(
+            _v,
+            "key_move_step",
+            &mut self.key_move_step,
+            &mut self.warnings,
+        );
+        read_u32(
+            _v,
+            "key_move_rate",
+            &mut self.key_move_rate,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "edge_maximize",
+            &mut self.edge_maximize,
+            &mut self.warnings,
+        );
+        read_u32(_v, "edge_flip", &mut self.edge_flip, &mut self.warnings);
+        read_bool(
+            _v,
+            "large_targets",
+            &mut self.large_targets,
+            &mut self.warnings,
+        );
+        read_theme(_v, "theme", &mut self.theme, &mut self.warnings);
+        read_u32(_v, "zoom", &mut self.zoom, &mut self.warnings);
+        read_color(_v, "background", &mut self.background, &mut self.warnings);
+        read_layout(_v, "layout", &mut self.layout, &mut self.warnings);
+        read_u32(
+            _v,
+            "master_width",
+            &mut self.master_width,
+            &mut self.warnings,
+        );
+        read_u32(_v, "gaps_inner", &mut self.gaps_inner, &mut self.warnings);
+        read_u32(_v, "gaps_outer", &mut self.gaps_outer, &mut self.warnings);
+        read_bar(
+            _v,
+            "bar",
+            &mut self.bar_mode,
+            &mut self.bar_monitors,
+            &mut self.warnings,
+        );
+        read_modules(_v, "bar_modules", &mut self.bar_modules, &mut self.warnings);
+        read_string(
+            _v,
+            "bar_datetime_format",
+            &mut self.bar_datetime_format,
+            &mut self.warnings,
+        );
+        read_string(
+            _v,
+            "bar_volume_command",
+            &mut self.bar_volume_command,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "notifications",
+            &mut self.notifications,
+            &mut self.warnings,
+        );
+        read_bool(
+            _v,
+            "preserve_clipboard",
+            &mut self.preserve_clipboard,
+            &mut self.warnings,
+        );
+        read_rules(_v, "rules", &mut self.rules, &mut self.warnings);
+        read_nightlight(_v, "nightlight", &mut self.nightlight, &mut self.warnings);
+        read_autostart(_v, "autostart", &mut self.autostart, &mut self.warnings);
+        read_profiles(_v, "profiles", &mut self.profiles, &mut self.warnings);
+
+        if self.workspaces.is_empty() {
+            report(
+                &mut self.warnings,
+                "At least one workspace is required".to_string(),
+            );
+            self.workspaces = Config::default().workspaces;
+        }
+
+        // A client can never be smaller than a single pixel
+        self.min_width = self.min_width.max(1);
+        self.min_height = self.min_height.max(1);
+    }
+
+    /**
+     * Applies the options of a profile over the ones read from the file
+     */
+    pub fn apply_profile(&mut self, name: &str) {
+        let options = match self.profiles.iter().find(|p| p.name == name) {
+            Some(profile) => profile.options.clone(),
+            None => return,
+        };
+
+        self.apply(&options);
+    }
+
+    /**
+     * Checks the version and option names of a configuration file, moving
+     * values under renamed options to their new name
+     */
+    fn migrate(&mut self, _v: &Value) -> Value {
+        let version = match _v.get("version") {
+            None => VERSION,
+            Some(value) => match value.as_integer() {
+                Some(n) if n >= 1 => n,
+                _ => {
+                    report(
+                        &mut self.warnings,
+                        "Invalid value for 'version' in configuration".to_string(),
+                    );
+                    VERSION
+                }
+            },
+        };
+
+        if version > VERSION {
+            report(
+                &mut self.warnings,
+                format!(
+                    "Configuration is version {}, but only up to {} is understood",
+                    version, VERSION
+                ),
+            );
+        }
+
+        let mut table = match _v.as_table() {
+            Some(table) => table.clone(),
+            None => return _v.clone(),
+        };
+
+        let keys: Vec<String> = table.keys().cloned().collect();
+        for key in keys {
+            if OPTIONS.contains(&key.as_str()) {
+                continue;
+            }
+
+            match RENAMED.iter().find(|(old, _, _)| *old == key) {
+                Some((old, new, since)) => {
+                    report(
+                        &mut self.warnings,
+                        format!(
+                            "'{}' was renamed to '{}' in version {}, please update the configuration",
+                            old, new, since
+                        ),
+                    );
+
+                    let value = table.remove(&key).unwrap();
+                    if !table.contains_key(*new) {
+                        table.insert(new.to_string(), value);
+                    }
+                }
+                None => report(
+                    &mut self.warnings,
+                    format!("Unknown option '{}' in configuration", key),
+                ),
+            }
+        }
+
+        Value::Table(table)
+    }
+}
+
+/**
+ * Logs a problem with the configuration and keeps it for whoever asked
+ * for the reload
+ */
+fn report(warnings: &mut Vec<String>, message: String) {
+    warn!("{}", message);
+    warnings.push(message);
+}
+
+/**
+ * Adds a rule at the end of the configuration file, creating it if needed.
+ * The file is left alone when the rule would break it, as it does when the
+ * rules are written inline rather than as [[rules]] tables.
+ */
+pub fn append_rule(rule: &Table) -> Result<(), String> {
+    let path = config_path().ok_or("no configuration directory")?;
+    let text = toml::to_string(rule).map_err(|err| err.to_string())?;
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("cannot read {}: {}", path.display(), err)),
+    };
+
+    let appended = format!("{}\n[[rules]]\n{}", contents, text);
+    if let Err(err) = appended.parse::<Value>() {
+        let inline = contents
+            .parse::<Value>()
+            .is_ok_and(|value| value.get("rules").is_some());
+        return Err(if inline {
+            format!(
+                "the rules in {} are not [[rules]] tables, add this one by hand",
+                path.display()
+            )
+        } else {
+            format!("cannot add the rule to {}: {}", path.display(), err)
+        });
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| write!(file, "\n[[rules]]\n{}", text))
+        .map_err(|err| format!("cannot write {}: {}", path.display(), err))?;
+
+    info!("Saved rule to {}", path.display());
+    Ok(())
+}
+
+/**
+ * Resolves the location of the configuration file
+ */
+fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(base.join("gothite").join("config.toml"))
+}
+
+/**
+ * Reads an unsigned integer option
+ */
+fn read_u32(_v: &Value, key: &str, out: &mut u32, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        match value.as_integer() {
+            Some(n) if n >= 0 => *out = n as u32,
+            _ => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads an unsigned integer option
+ */
+fn read_u64(_v: &Value, key: &str, out: &mut u64, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        match value.as_integer() {
+            Some(n) if n >= 0 => *out = n as u64,
+            _ => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads a boolean option
+ */
+fn read_bool(_v: &Value, key: &str, out: &mut bool, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        match value.as_bool() {
+            Some(b) => *out = b,
+            None => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads a string option
+ */
+fn read_string(_v: &Value, key: &str, out: &mut String, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        match value.as_str() {
+            Some(s) => *out = s.to_string(),
+            None => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads a list of strings option
+ */
+fn read_string_list(_v: &Value, key: &str, out: &mut Vec<String>, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        let list: Option<Vec<String>> = value.as_array().and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(|s| s.to_string()))
+                .collect()
+        });
+
+        match list {
+            Some(list) => *out = list,
+            None => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads a font description, keeping the font in use for ones that cannot
+ * be passed to fontconfig
+ */
+fn read_font(_v: &Value, key: &str, out: &mut String, warnings: &mut Vec<String>) {
+    let mut font = out.clone();
+    read_string(_v, key, &mut font, warnings);
+
+    if font.contains('\0') {
+        report(
+            warnings,
+            format!("Invalid value for '{}' in configuration", key),
+        );
+    } else {
+        *out = font;
+    }
+}
+
+/**
+ * Reads the fallback fonts, leaving out the ones that cannot be passed to
+ * fontconfig
+ */
+fn read_font_fallback(_v: &Value, key: &str, out: &mut Vec<String>, warnings: &mut Vec<String>) {
+    read_string_list(_v, key, out, warnings);
+
+    if out.iter().any(|font| font.contains('\0')) {
+        report(
+            warnings,
+            format!("Invalid font in '{}' in configuration", key),
+        );
+        out.retain(|font| !font.contains('\0'));
+    }
+}
+
+/**
+ * Reads a list of titlebar buttons option
+ */
+fn read_buttons(_v: &Value, key: &str, out: &mut Vec<Button>, warnings: &mut Vec<String>) {
+    let mut names = Vec::new();
+    if _v.get(key).is_none() {
+        return;
+    }
+
+    read_string_list(_v, key, &mut names, warnings);
+    *out = names
+        .iter()
+        .filter_map(|name| {
+            let button = Button::from_name(name);
+            if button.is_none() {
+                report(
+                    warnings,
+                    format!("Unknown titlebar button '{}' in '{}'", name, key),
+                );
+            }
+            button
+        })
+        .collect();
+}
+
+/**
+ * Reads a color option written as "#RRGGBB"
+ */
+fn read_color(_v: &Value, key: &str, out: &mut u64, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        let color = value
+            .as_str()
+            .filter(|text| text.len() == 7 && text.starts_with('#'))
+            .and_then(|text| u64::from_str_radix(&text[1..], 16).ok());
+
+        match color {
+            Some(color) => *out = color,
+            None => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
+
+/**
+ * Reads the name of a layout option
+ */
+fn read_layout(_v: &Value, key: &str, out: &mut String, warnings: &mut Vec<String>) {
+    let mut name = out.clone();
+    read_string(_v, key, &mut name, warnings);
+
+    if layout::exists(&name) {
+        *out = name;
+    } else {
+        report(warnings, format!("Unknown layout '{}' in '{}'", name, key));
+    }
+}
+
+/**
+ * Reads the name of a decoration palette option, keeping the current one
+ * when it is unknown
+ */
+fn read_theme(_v: &Value, key: &str, out: &mut String, warnings: &mut Vec<String>) {
+    let mut name = out.clone();
+    read_string(_v, key, &mut name, warnings);
+
+    if decoration::theme_exists(&name) {
+        *out = name;
+    } else {
+        report(warnings, format!("Unknown theme '{}' in '{}'", name, key));
+    }
+}
+
+/**
+ * Reads the name of a focus policy option, keeping the current one when it
+ * is unknown
+ */
+fn read_focus_policy(_v: &Value, key: &str, out: &mut String, warnings: &mut Vec<String>) {
+    let mut name = out.clone();
+    read_string(_v, key, &mut name, warnings);
+
+    if focus::exists(&name) {
+        *out = name;
+    } else {
+        report(
+            warnings,
+            format!("Unknown focus policy '{}' in '{}'", name, key),
+        );
+    }
+}
+
+/**
+ * Reads a list of bar modules option
+ */
+fn read_modules(_v: &Value, key: &str, out: &mut Vec<Module>, warnings: &mut Vec<String>) {
+    let mut names = Vec::new();
+    if _v.get(key).is_none() {
+        return;
+    }
+
+    read_string_list(_v, key, &mut names, warnings);
+    *out = names
+        .iter()
+        .filter_map(|name| {
+            let module = Module::from_name(name);
+            if module.is_none() {
+                report(
+                    warnings,
+                    format!("Unknown bar module '{}' in '{}'", name, key),
+                );
+            }
+            module
+        })
+        .collect();
+}
+
+/**
+ * Reads the bar table, with the mode of every bar and the modes of the bars
+ * on particular monitors
+ */
+fn read_bar(
+    _v: &Value,
+    key: &str,
+    mode: &mut Mode,
+    monitors: &mut HashMap<String, Mode>,
+    warnings: &mut Vec<String>,
+) {
+    let table = match _v.get(key) {
+        None => return,
+        Some(value) => match value.as_table() {
+            Some(table) => table,
+            None => {
+                report(
+                    warnings,
+                    format!("Invalid value for '{}' in configuration", key),
+                );
+                return;
+            }
+        },
+    };
+
+    let parse = |value: &Value| value.as_str().and_then(Mode::from_name);
+
+    for (name, value) in table {
+        match name.as_str() {
+            "mode" => match parse(value) {
+                Some(parsed) => *mode = parsed,
+                None => report(warnings, format!("Invalid value for '{}.mode'", key)),
+            },
+            "monitors" => {
+                let parsed: Option<HashMap<String, Mode>> = value.as_table().and_then(|table| {
+                    table
+                        .iter()
+                        .map(|(output, value)| parse(value).map(|m| (output.clone(), m)))
+                        .collect()
+                });
+
+                match parsed {
+                    Some(parsed) => *monitors = parsed,
+                    None => report(warnings, format!("Invalid value for '{}.monitors'", key)),
+                }
+            }
+            _ => report(warnings, format!("Unknown option '{}.{}'", key, name)),
+        }
+    }
+}
+
+/**
+ * Reads the night light schedule. Parts left out keep what was read before,
+ * so profiles only change the ones they set, or else the defaults.
+ */
+fn read_nightlight(_v: &Value, key: &str, out: &mut Option<Schedule>, warnings: &mut Vec<String>) {
+    let table = match _v.get(key) {
+        None => return,
+        Some(value) => match value.as_table() {
+            Some(table) => table,
+            None => {
+                report(
+                    warnings,
+                    format!("Invalid value for '{}' in configuration", key),
+                );
+                return;
+            }
+        },
+    };
+
+    let mut schedule = out.unwrap_or_default();
+    let time = |value: &Value| value.as_str().and_then(profiles::parse_time);
+
+    for (name, value) in table {
+        let parsed = match name.as_str() {
+            "temperature" => value
+                .as_integer()
+                .and_then(|t| nightlight::parse_temperature(&t.to_string()))
+                .map(|t| schedule.temperature = t),
+            "from" => time(value).map(|t| schedule.from = t),
+            "to" => time(value).map(|t| schedule.to = t),
+            "fade" => value
+                .as_integer()
+                .filter(|&m| m >= 0)
+                .map(|m| schedule.fade = m as u64 * 60),
+            _ => {
+                report(warnings, format!("Unknown option '{}.{}'", key, name));
+                continue;
+            }
+        };
+
+        if parsed.is_none() {
+            report(warnings, format!("Invalid value for '{}.{}'", key, name));
+        }
+    }
+
+    *out = Some(schedule);
+}
+
+/**
+ * Reads the window rules, skipping the ones that are broken
+ */
+fn read_rules(_v: &Value, key: &str, out: &mut Vec<Rule>, warnings: &mut Vec<String>) {
+    let array = match _v.get(key) {
+        None => return,
+        Some(value) => match value.as_array() {
+            Some(array) => array,
+            None => {
+                report(
+                    warnings,
+                    format!("Invalid value for '{}' in configuration", key),
+                );
+                return;
+            }
+        },
+    };
+
+    *out = array
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| {
+            let parsed = value
+                .as_table()
+                .ok_or_else(|| "Not a table".to_string())
+                .and_then(rules::parse);
+
+            match parsed {
+                Ok(rule) => Some(rule),
+                Err(err) => {
+                    report(warnings, format!("Skipping rule {}: {}", i + 1, err));
+                    None
+                }
+            }
+        })
+        .collect();
+}
+
+/**
+ * Reads the array of commands to start at login, skipping broken ones with
+ * a warning
+ */
+fn read_autostart(_v: &Value, key: &str, out: &mut Vec<Entry>, warnings: &mut Vec<String>) {
+    let array = match _v.get(key) {
+        None => return,
+        Some(value) => match value.as_array() {
+            Some(array) => array,
+            None => {
+                report(
+                    warnings,
+                    format!("Invalid value for '{}' in configuration", key),
+                );
+                return;
+            }
+        },
+    };
+
+    *out = array
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| {
+            let parsed = value
+                .as_table()
+                .ok_or_else(|| "Not a table".to_string())
+                .and_then(autostart::parse);
+
+            match parsed {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    report(
+                        warnings,
+                        format!("Skipping autostart entry {}: {}", i + 1, err),
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
+}
+
+/**
+ * Reads the table of profiles, skipping broken ones with a warning
+ */
+fn read_profiles(_v: &Value, key: &str, out: &mut Vec<Profile>, warnings: &mut Vec<String>) {
+    let table = match _v.get(key) {
+        None => return,
+        Some(value) => match value.as_table() {
+            Some(table) => table,
+            None => {
+                report(
+                    warnings,
+                    format!("Invalid value for '{}' in configuration", key),
+                );
+                return;
+            }
+        },
+    };
+
+    *out = table
+        .iter()
+        .filter_map(|(name, value)| {
+            let parsed = value
+                .as_table()
+                .ok_or_else(|| "Not a table".to_string())
+                .and_then(|table| profiles::parse(name, table));
+
+            match parsed {
+                Ok(profile) => Some(profile),
+                Err(err) => {
+                    report(warnings, format!("Skipping profile {}: {}", name, err));
+                    None
+                }
+            }
+        })
+        .collect();
+}
+
+/**
+ * Reads a table of output names to the number of monitors they are split into
+ */
+fn read_split(_v: &Value, key: &str, out: &mut HashMap<String, u32>, warnings: &mut Vec<String>) {
+    if let Some(value) = _v.get(key) {
+        let table: Option<HashMap<String, u32>> = value.as_table().and_then(|table| {
+            table
+                .iter()
+                .map(|(name, count)| match count.as_integer() {
+                    Some(n) if n >= 1 => Some((name.clone(), n as u32)),
+                    _ => None,
+                })
+                .collect()
+        });
+
+        match table {
+            Some(table) => *out = table,
+            None => report(
+                warnings,
+                format!("Invalid value for '{}' in configuration", key),
+            ),
+        }
+    }
+}
@@ -0,0 +1,659 @@
+use libc;
+use std::env;
+use std::io::{ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{SocketAddr, UnixStream};
+use std::time::Duration;
+
+/**
+ * How long the bus gets to answer during setup
+ */
+const SETUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/**
+ * How long a message may take to go out before the bus is given up on
+ */
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+
+const NAME_FLAG_DO_NOT_QUEUE: u32 = 4;
+const NAME_PRIMARY_OWNER: u32 = 1;
+
+/**
+ * Builds the little endian wire format of message bodies and headers
+ */
+pub struct Writer {
+    pub data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer { data: Vec::new() }
+    }
+
+    fn align(&mut self, n: usize) {
+        while !self.data.len().is_multiple_of(n) {
+            self.data.push(0);
+        }
+    }
+
+    fn byte(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+    }
+
+    fn signature(&mut self, value: &str) {
+        self.byte(value.len() as u8);
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+    }
+
+    /**
+     * Writes an array of strings
+     */
+    pub fn strings(&mut self, values: &[&str]) {
+        self.u32(0);
+        let length_at = self.data.len() - 4;
+        let start = self.data.len();
+
+        for value in values {
+            self.string(value);
+        }
+
+        let length = (self.data.len() - start) as u32;
+        self.data[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+    }
+}
+
+/**
+ * Reads values out of a message body in order, in the byte order its
+ * sender picked
+ */
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], big_endian: bool) -> Reader<'a> {
+        Reader {
+            data,
+            position: 0,
+            big_endian,
+        }
+    }
+
+    fn align(&mut self, n: usize) {
+        self.position = self.position.div_ceil(n) * n;
+    }
+
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(count)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.align(4);
+        let bytes = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    pub fn i32(&mut self) -> Option<i32> {
+        self.u32().map(|value| value as i32)
+    }
+
+    pub fn string(&mut self) -> Option<String> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length + 1)?;
+        Some(String::from_utf8_lossy(&bytes[..length]).into_owned())
+    }
+
+    fn signature(&mut self) -> Option<String> {
+        let length = self.byte()? as usize;
+        let bytes = self.take(length + 1)?;
+        Some(String::from_utf8_lossy(&bytes[..length]).into_owned())
+    }
+
+    /**
+     * Steps over an array whose elements are aligned to the given boundary
+     */
+    pub fn skip_array(&mut self, alignment: usize) -> Option<()> {
+        let length = self.u32()? as usize;
+        self.align(alignment);
+        self.take(length).map(|_| ())
+    }
+}
+
+/**
+ * A message received from the bus
+ */
+#[derive(Debug, Default)]
+pub struct Message {
+    pub kind: u8,
+    pub serial: u32,
+    pub reply_serial: Option<u32>,
+    pub sender: String,
+    pub path: String,
+    pub interface: String,
+    pub member: String,
+    pub signature: String,
+    pub body: Vec<u8>,
+    big_endian: bool,
+}
+
+impl Message {
+    /**
+     * Checks if the message calls a method
+     */
+    pub fn is_call(&self) -> bool {
+        self.kind == METHOD_CALL
+    }
+
+    /**
+     * Starts reading the body
+     */
+    pub fn reader(&self) -> Reader<'_> {
+        Reader::new(&self.body, self.big_endian)
+    }
+
+    /**
+     * Parses a complete message, giving None for malformed ones
+     */
+    fn parse(data: &[u8]) -> Option<Message> {
+        let big_endian = is_big_endian(data)?;
+        let mut reader = Reader::new(data, big_endian);
+        reader.take(1)?;
+        let kind = reader.byte()?;
+        reader.take(2)?;
+        let body_length = reader.u32()? as usize;
+        let serial = reader.u32()?;
+        let fields_length = reader.u32()? as usize;
+        let fields_end = 16 + fields_length;
+
+        let mut message = Message {
+            kind,
+            serial,
+            big_endian,
+            ..Message::default()
+        };
+
+        while reader.position < fields_end {
+            reader.align(8);
+            let code = reader.byte()?;
+            let signature = reader.signature()?;
+
+            match signature.as_str() {
+                "s" | "o" => {
+                    let value = reader.string()?;
+                    match code {
+                        FIELD_PATH => message.path = value,
+                        FIELD_INTERFACE => message.interface = value,
+                        FIELD_MEMBER => message.member = value,
+                        FIELD_SENDER => message.sender = value,
+                        _ => {}
+                    }
+                }
+                "g" => {
+                    let value = reader.signature()?;
+                    if code == FIELD_SIGNATURE {
+                        message.signature = value;
+                    }
+                }
+                "u" => {
+                    let value = reader.u32()?;
+                    if code == FIELD_REPLY_SERIAL {
+                        message.reply_serial = Some(value);
+                    }
+                }
+                // Nothing else is defined, so the rest cannot be understood
+                _ => break,
+            }
+        }
+
+        let body_start = fields_end.div_ceil(8) * 8;
+        message.body = data.get(body_start..body_start + body_length)?.to_vec();
+        Some(message)
+    }
+}
+
+/**
+ * Reads the byte order a message starts with, none for an unknown one
+ */
+fn is_big_endian(data: &[u8]) -> Option<bool> {
+    match data.first() {
+        Some(b'l') => Some(false),
+        Some(b'B') => Some(true),
+        _ => None,
+    }
+}
+
+/**
+ * Finds the socket of the session bus from DBUS_SESSION_BUS_ADDRESS
+ */
+fn session_address() -> Option<SocketAddr> {
+    let address = env::var("DBUS_SESSION_BUS_ADDRESS").ok()?;
+
+    for entry in address.split(';') {
+        let params = match entry.strip_prefix("unix:") {
+            Some(params) => params,
+            None => continue,
+        };
+
+        for param in params.split(',') {
+            let (key, value) = match param.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let value = unescape(value);
+
+            match key {
+                "path" => return SocketAddr::from_pathname(value).ok(),
+                "abstract" => return SocketAddr::from_abstract_name(value.as_bytes()).ok(),
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+/**
+ * Undoes the percent escaping of address values
+ */
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&value[i + 1..i + 3], 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/**
+ * A connection to the session bus
+ */
+pub struct Connection {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    serial: u32,
+    listening: bool,
+}
+
+impl Connection {
+    /**
+     * Connects and authenticates to the session bus
+     */
+    pub fn session() -> Option<Connection> {
+        let stream = UnixStream::connect_addr(&session_address()?).ok()?;
+        stream.set_read_timeout(Some(SETUP_TIMEOUT)).ok()?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+
+        let mut connection = Connection {
+            stream,
+            buffer: Vec::new(),
+            serial: 0,
+            listening: false,
+        };
+
+        let uid = unsafe { libc::getuid() }.to_string();
+        let hex: String = uid.bytes().map(|b| format!("{:02x}", b)).collect();
+        connection.write(format!("\0AUTH EXTERNAL {}\r\n", hex).as_bytes())?;
+
+        if !connection.read_line()?.starts_with("OK") {
+            return None;
+        }
+        connection.write(b"BEGIN\r\n")?;
+
+        let hello = connection.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+            "",
+            &[],
+        )?;
+        connection.wait_reply(hello)?;
+
+        Some(connection)
+    }
+
+    /**
+     * Reads one line of the authentication exchange
+     */
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+
+        while !line.ends_with(b"\r\n") {
+            if self.stream.read(&mut byte).ok()? == 0 {
+                return None;
+            }
+            line.push(byte[0]);
+        }
+
+        Some(String::from_utf8_lossy(&line).trim().to_string())
+    }
+
+    /**
+     * Descriptor to wait on for incoming messages
+     */
+    pub fn fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+
+    /**
+     * Asks to own a well known name, only succeeding if nobody else does
+     */
+    pub fn request_name(&mut self, name: &str) -> bool {
+        let mut body = Writer::new();
+        body.string(name);
+        body.u32(NAME_FLAG_DO_NOT_QUEUE);
+
+        let serial = match self.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            "su",
+            &body.data,
+        ) {
+            Some(serial) => serial,
+            None => return false,
+        };
+
+        let owned = self
+            .wait_reply(serial)
+            .and_then(|reply| reply.reader().u32())
+            == Some(NAME_PRIMARY_OWNER);
+
+        // From here on messages are picked up from the event loop
+        self.listening = owned && self.stream.set_nonblocking(true).is_ok();
+        self.listening
+    }
+
+    /**
+     * Writes out all of some data, blocking until it is gone even once
+     * reading no longer does. A message cut short would garble the ones
+     * after it, so the connection is shut down when that happens.
+     */
+    fn write(&mut self, data: &[u8]) -> Option<()> {
+        let written = self
+            .stream
+            .set_nonblocking(false)
+            .and_then(|_| self.stream.write_all(data))
+            .and_then(|_| self.stream.set_nonblocking(self.listening));
+
+        if written.is_err() {
+            let _ = self.stream.shutdown(Shutdown::Both);
+            return None;
+        }
+        Some(())
+    }
+
+    /**
+     * Blocks until the reply to a call arrives, dropping anything else
+     */
+    fn wait_reply(&mut self, serial: u32) -> Option<Message> {
+        loop {
+            let message = match self.next_message() {
+                Some(message) => message,
+                None => {
+                    if !self.fill() {
+                        return None;
+                    }
+                    continue;
+                }
+            };
+
+            if message.reply_serial == Some(serial) {
+                return match message.kind {
+                    METHOD_RETURN => Some(message),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    /**
+     * Reads whatever the socket has, returning false once it is closed
+     */
+    fn fill(&mut self) -> bool {
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return false,
+                Ok(count) => {
+                    self.buffer.extend_from_slice(&chunk[..count]);
+                    if count < chunk.len() {
+                        return true;
+                    }
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(ref err) if err.kind() == ErrorKind::WouldBlock => return true,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /**
+     * Takes the next complete message out of the buffer
+     */
+    fn next_message(&mut self) -> Option<Message> {
+        loop {
+            if self.buffer.len() < 16 {
+                return None;
+            }
+
+            let big_endian = match is_big_endian(&self.buffer) {
+                Some(big_endian) => big_endian,
+                // Nothing after it can be made sense of
+                None => {
+                    self.buffer.clear();
+                    return None;
+                }
+            };
+            let mut reader = Reader::new(&self.buffer, big_endian);
+            reader.take(4)?;
+            let body_length = reader.u32()? as usize;
+            reader.u32()?;
+            let fields_length = reader.u32()? as usize;
+
+            let total = (16 + fields_length).div_ceil(8) * 8 + body_length;
+            if self.buffer.len() < total {
+                return None;
+            }
+
+            let data: Vec<u8> = self.buffer.drain(..total).collect();
+            if let Some(message) = Message::parse(&data) {
+                return Some(message);
+            }
+        }
+    }
+
+    /**
+     * Takes all the messages that arrived, without blocking
+     */
+    pub fn receive(&mut self) -> Result<Vec<Message>, ()> {
+        let open = self.fill();
+
+        let mut messages = Vec::new();
+        while let Some(message) = self.next_message() {
+            messages.push(message);
+        }
+
+        if open {
+            Ok(messages)
+        } else {
+            Err(())
+        }
+    }
+
+    /**
+     * Sends a message with the given header fields and body
+     */
+    fn send(
+        &mut self,
+        kind: u8,
+        fields: &[(u8, &str, &str)],
+        serial_field: Option<u32>,
+        body: &[u8],
+    ) -> Option<u32> {
+        self.serial += 1;
+
+        let mut header = Writer::new();
+        header.byte(b'l');
+        header.byte(kind);
+        header.byte(0);
+        header.byte(1);
+        header.u32(body.len() as u32);
+        header.u32(self.serial);
+
+        header.u32(0);
+        let fields_start = header.data.len();
+
+        for (code, signature, value) in fields {
+            header.align(8);
+            header.byte(*code);
+            header.signature(signature);
+            match *signature {
+                "g" => header.signature(value),
+                _ => header.string(value),
+            }
+        }
+
+        if let Some(serial) = serial_field {
+            header.align(8);
+            header.byte(FIELD_REPLY_SERIAL);
+            header.signature("u");
+            header.u32(serial);
+        }
+
+        let fields_length = (header.data.len() - fields_start) as u32;
+        header.data[12..16].copy_from_slice(&fields_length.to_le_bytes());
+        header.align(8);
+        header.data.extend_from_slice(body);
+
+        self.write(&header.data)?;
+        Some(self.serial)
+    }
+
+    /**
+     * Calls a method, giving the serial its reply will refer to
+     */
+    fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: &str,
+        body: &[u8],
+    ) -> Option<u32> {
+        let mut fields = vec![
+            (FIELD_PATH, "o", path),
+            (FIELD_INTERFACE, "s", interface),
+            (FIELD_MEMBER, "s", member),
+            (FIELD_DESTINATION, "s", destination),
+        ];
+        if !signature.is_empty() {
+            fields.push((FIELD_SIGNATURE, "g", signature));
+        }
+
+        self.send(METHOD_CALL, &fields, None, body)
+    }
+
+    /**
+     * Answers a method call
+     */
+    pub fn reply(&mut self, call: &Message, signature: &str, body: &[u8]) {
+        let mut fields = vec![(FIELD_DESTINATION, "s", call.sender.as_str())];
+        if !signature.is_empty() {
+            fields.push((FIELD_SIGNATURE, "g", signature));
+        }
+
+        self.send(METHOD_RETURN, &fields, Some(call.serial), body);
+    }
+
+    /**
+     * Answers a method call with an error
+     */
+    pub fn error(&mut self, call: &Message, name: &str, text: &str) {
+        let mut body = Writer::new();
+        body.string(text);
+
+        let fields = [
+            (FIELD_DESTINATION, "s", call.sender.as_str()),
+            (FIELD_ERROR_NAME, "s", name),
+            (FIELD_SIGNATURE, "g", "s"),
+        ];
+        self.send(ERROR, &fields, Some(call.serial), &body.data);
+    }
+
+    /**
+     * Broadcasts a signal
+     */
+    pub fn signal(
+        &mut self,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: &str,
+        body: &[u8],
+    ) {
+        let fields = [
+            (FIELD_PATH, "o", path),
+            (FIELD_INTERFACE, "s", interface),
+            (FIELD_MEMBER, "s", member),
+            (FIELD_SIGNATURE, "g", signature),
+        ];
+        self.send(SIGNAL, &fields, None, body);
+    }
+}
@@ -0,0 +1,551 @@
+use cairo_sys;
+use config::Config;
+use icon;
+use std::f64::consts::PI;
+use std::mem::zeroed;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tree;
+use x11::xlib;
+use {is_urgent, Extents, Window, WindowManager};
+use {XC_ARROW, XC_HAND2};
+use {XC_BOTTOM_LEFT_CORNER, XC_BOTTOM_RIGHT_CORNER, XC_BOTTOM_SIDE, XC_LEFT_SIDE};
+use {XC_RIGHT_SIDE, XC_TOP_LEFT_CORNER, XC_TOP_RIGHT_CORNER, XC_TOP_SIDE};
+
+/**
+ * Sizes of the parts of a frame and of the places the pointer acts on, in
+ * pixels
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Metrics {
+    pub padding: i32,
+    pub titlebar: i32,
+    pub button: i32,
+    pub spacing: i32,
+    pub corner: i32,
+    pub top_edge: i32,
+    pub edge_corner: i32,
+}
+
+/**
+ * The usual sizes
+ */
+const NORMAL: Metrics = Metrics {
+    padding: 10,
+    titlebar: 24,
+    button: 14,
+    spacing: 6,
+    corner: 16,
+    top_edge: 4,
+    edge_corner: 64,
+};
+
+/**
+ * Larger sizes for people who find the usual ones hard to hit
+ */
+const LARGE: Metrics = Metrics {
+    padding: 16,
+    titlebar: 36,
+    button: 24,
+    spacing: 10,
+    corner: 32,
+    top_edge: 8,
+    edge_corner: 128,
+};
+
+impl Metrics {
+    /**
+     * Gets the space taken up by the decoration on each side of a client
+     */
+    pub fn extents(self) -> Extents {
+        Extents {
+            left: self.padding,
+            right: self.padding,
+            top: self.titlebar,
+            bottom: self.padding,
+        }
+    }
+}
+
+/**
+ * Picks the sizes the configuration asks for
+ */
+pub fn metrics(config: &Config) -> Metrics {
+    if config.large_targets {
+        LARGE
+    } else {
+        NORMAL
+    }
+}
+
+/**
+ * Colors of the parts of a frame
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub frame: (f64, f64, f64),
+    pub titlebar: (f64, f64, f64),
+    pub urgent: (f64, f64, f64),
+    pub border: (f64, f64, f64),
+    pub tab: (f64, f64, f64),
+    pub text: (f64, f64, f64),
+}
+
+/**
+ * Light text on dark frames
+ */
+const DARK: Palette = Palette {
+    frame: (0.231, 0.25, 0.322),
+    titlebar: (0.263, 0.298, 0.369),
+    urgent: (0.816, 0.529, 0.439),
+    border: (0.298, 0.337, 0.416),
+    tab: (0.369, 0.506, 0.675),
+    text: (0.925, 0.937, 0.957),
+};
+
+/**
+ * Dark text on light frames
+ */
+const LIGHT: Palette = Palette {
+    frame: (0.898, 0.914, 0.941),
+    titlebar: (0.847, 0.871, 0.914),
+    urgent: (0.816, 0.529, 0.439),
+    border: (0.749, 0.776, 0.827),
+    tab: (0.533, 0.753, 0.816),
+    text: (0.18, 0.204, 0.251),
+};
+
+/**
+ * Checks if there is a palette by that name
+ */
+pub fn theme_exists(name: &str) -> bool {
+    matches!(name, "dark" | "light")
+}
+
+/**
+ * Picks the colors the configuration asks for
+ */
+pub fn palette(config: &Config) -> Palette {
+    match config.theme.as_str() {
+        "light" => LIGHT,
+        _ => DARK,
+    }
+}
+
+/**
+ * Sets the color used for the next drawing
+ */
+unsafe fn set_color(_ctx: *mut cairo_sys::cairo_t, (r, g, b): (f64, f64, f64)) {
+    cairo_sys::cairo_set_source_rgb(_ctx, r, g, b);
+}
+
+/**
+ * Titlebar buttons
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Button {
+    Icon,
+    Minimize,
+    Maximize,
+    Close,
+}
+
+impl Button {
+    /**
+     * Looks up a button by its configuration name
+     */
+    pub fn from_name(name: &str) -> Option<Button> {
+        match name {
+            "icon" => Some(Button::Icon),
+            "minimize" => Some(Button::Minimize),
+            "maximize" => Some(Button::Maximize),
+            "close" => Some(Button::Close),
+            _ => None,
+        }
+    }
+
+    /**
+     * Gets the color used when the button is hovered
+     */
+    fn color(self) -> (f64, f64, f64) {
+        match self {
+            Button::Icon => (0.533, 0.753, 0.816),
+            Button::Minimize => (0.922, 0.796, 0.545),
+            Button::Maximize => (0.639, 0.745, 0.549),
+            Button::Close => (0.749, 0.38, 0.416),
+        }
+    }
+}
+
+/**
+ * Parts of a frame the pointer can interact with
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Region {
+    Client,
+    Titlebar,
+    Tab(usize),
+    Button(Button),
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Region {
+    /**
+     * Gets which edges (left, right, top, bottom) are moved when dragging
+     */
+    pub fn edges(self) -> (bool, bool, bool, bool) {
+        match self {
+            Region::Left => (true, false, false, false),
+            Region::Right => (false, true, false, false),
+            Region::Top => (false, false, true, false),
+            Region::Bottom => (false, false, false, true),
+            Region::TopLeft => (true, false, true, false),
+            Region::TopRight => (false, true, true, false),
+            Region::BottomLeft => (true, false, false, true),
+            Region::BottomRight => (false, true, false, true),
+            _ => (false, false, false, false),
+        }
+    }
+
+    /**
+     * Gets the cursor shown while hovering the region
+     */
+    pub fn cursor(self) -> u32 {
+        match self {
+            Region::Titlebar | Region::Tab(_) => XC_HAND2,
+            Region::Left => XC_LEFT_SIDE,
+            Region::Right => XC_RIGHT_SIDE,
+            Region::Top => XC_TOP_SIDE,
+            Region::Bottom => XC_BOTTOM_SIDE,
+            Region::TopLeft => XC_TOP_LEFT_CORNER,
+            Region::TopRight => XC_TOP_RIGHT_CORNER,
+            Region::BottomLeft => XC_BOTTOM_LEFT_CORNER,
+            Region::BottomRight => XC_BOTTOM_RIGHT_CORNER,
+            _ => XC_ARROW,
+        }
+    }
+}
+
+/**
+ * Gets the buttons of a frame along with their position
+ */
+fn layout(_wm: &WindowManager, _win: &Window) -> Vec<(Button, i32, i32)> {
+    let width = unsafe { cairo_sys::cairo_xlib_surface_get_width(_win.decoration_surface) };
+    let m = _win.metrics;
+    let y = (m.titlebar - m.button) / 2;
+    let step = m.button + m.spacing;
+    let mut buttons = Vec::new();
+
+    for (i, button) in _wm.config.buttons_left.iter().enumerate() {
+        buttons.push((*button, m.padding + i as i32 * step, y));
+    }
+
+    for (i, button) in _wm.config.buttons_right.iter().rev().enumerate() {
+        let x = width - m.padding - m.button - i as i32 * step;
+        buttons.push((*button, x, y));
+    }
+
+    buttons
+}
+
+/**
+ * Finds the button under a point inside a frame
+ */
+pub fn button_at(_wm: &WindowManager, _win: &Window, x: i32, y: i32) -> Option<Button> {
+    let size = _win.metrics.button;
+    layout(_wm, _win)
+        .into_iter()
+        .find(|&(_, bx, by)| x >= bx && x < bx + size && y >= by && y < by + size)
+        .map(|(button, _, _)| button)
+}
+
+/**
+ * Finds the part of a frame under a point
+ */
+pub fn region_at(_wm: &WindowManager, _win: &Window, x: i32, y: i32) -> Region {
+    if let Some(button) = button_at(_wm, _win, x, y) {
+        return Region::Button(button);
+    }
+
+    let (width, height) = unsafe {
+        (
+            cairo_sys::cairo_xlib_surface_get_width(_win.decoration_surface),
+            cairo_sys::cairo_xlib_surface_get_height(_win.decoration_surface),
+        )
+    };
+
+    let m = _win.metrics;
+    let left = x < m.padding;
+    let right = x >= width - m.padding;
+    let top = y < m.top_edge;
+    let bottom = y >= height - m.padding;
+    let near_left = x < m.corner;
+    let near_right = x >= width - m.corner;
+    let near_top = y < m.corner;
+    let near_bottom = y >= height - m.corner;
+
+    if (top && near_left) || (left && near_top) {
+        Region::TopLeft
+    } else if (top && near_right) || (right && near_top) {
+        Region::TopRight
+    } else if (bottom && near_left) || (left && near_bottom) {
+        Region::BottomLeft
+    } else if (bottom && near_right) || (right && near_bottom) {
+        Region::BottomRight
+    } else if top {
+        Region::Top
+    } else if left {
+        Region::Left
+    } else if right {
+        Region::Right
+    } else if bottom {
+        Region::Bottom
+    } else if y < m.titlebar {
+        match tab_at(_wm, _win, x, width) {
+            Some(index) => Region::Tab(index),
+            None => Region::Titlebar,
+        }
+    } else {
+        Region::Client
+    }
+}
+
+/**
+ * Renders the glyph shown on a hovered button
+ */
+fn draw_glyph(_ctx: *mut cairo_sys::cairo_t, button: Button, cx: f64, cy: f64, size: f64) {
+    unsafe {
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.925, 0.937, 0.957);
+        cairo_sys::cairo_set_line_width(_ctx, 1.5);
+
+        match button {
+            Button::Close => {
+                cairo_sys::cairo_move_to(_ctx, cx - size, cy - size);
+                cairo_sys::cairo_line_to(_ctx, cx + size, cy + size);
+                cairo_sys::cairo_move_to(_ctx, cx + size, cy - size);
+                cairo_sys::cairo_line_to(_ctx, cx - size, cy + size);
+            }
+            Button::Minimize => {
+                cairo_sys::cairo_move_to(_ctx, cx - size, cy);
+                cairo_sys::cairo_line_to(_ctx, cx + size, cy);
+            }
+            Button::Maximize => {
+                cairo_sys::cairo_rectangle(_ctx, cx - size, cy - size, size * 2.0, size * 2.0);
+            }
+            Button::Icon => {}
+        }
+
+        cairo_sys::cairo_stroke(_ctx);
+    }
+}
+
+/**
+ * Renders a titlebar button
+ */
+fn draw_button(
+    _ctx: *mut cairo_sys::cairo_t,
+    _win: &Window,
+    palette: Palette,
+    button: Button,
+    x: i32,
+    y: i32,
+) {
+    let size = _win.metrics.button as f64;
+    let radius = size / 2.0;
+    let cx = x as f64 + radius;
+    let cy = y as f64 + radius;
+    let hovered = _win.hovered == Some(button);
+    let (r, g, b) = button.color();
+
+    // The application icon stands in for the plain square when there is one
+    if button == Button::Icon && !_win.icon.is_null() {
+        icon::draw(_ctx, _win.icon, x as f64, y as f64, size);
+        return;
+    }
+
+    unsafe {
+        if button == Button::Close && _win.hung {
+            // Pulse while the client is not answering pings
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let phase = now.subsec_millis() as f64 / 1000.0 * PI * 2.0;
+            cairo_sys::cairo_set_source_rgba(_ctx, r, g, b, 0.6 + phase.sin() * 0.4);
+        } else if hovered || button == Button::Icon {
+            cairo_sys::cairo_set_source_rgb(_ctx, r, g, b);
+        } else {
+            set_color(_ctx, palette.border);
+        }
+
+        cairo_sys::cairo_new_path(_ctx);
+        if button == Button::Icon {
+            cairo_sys::cairo_rectangle(_ctx, x as f64, y as f64, size, size);
+        } else {
+            cairo_sys::cairo_arc(_ctx, cx, cy, radius, 0.0, PI * 2.0);
+        }
+        cairo_sys::cairo_fill(_ctx);
+    }
+
+    if hovered || (button == Button::Close && _win.hung) {
+        draw_glyph(_ctx, button, cx, cy, radius / 2.0);
+    }
+}
+
+/**
+ * Gets where the space between the buttons of a titlebar starts and ends
+ */
+fn title_span(_wm: &WindowManager, _win: &Window, width: f64) -> (f64, f64) {
+    let m = _win.metrics;
+    let step = (m.button + m.spacing) as f64;
+    (
+        m.padding as f64 + _wm.config.buttons_left.len() as f64 * step,
+        width - m.padding as f64 - _wm.config.buttons_right.len() as f64 * step,
+    )
+}
+
+/**
+ * Finds the tab under a point on the titlebar of a window in a tabbed
+ * container
+ */
+fn tab_at(_wm: &WindowManager, _win: &Window, x: i32, width: i32) -> Option<usize> {
+    let (tabs, _) = tree::tabs(_wm, _win.frame)?;
+    let (left, right) = title_span(_wm, _win, width as f64);
+    let x = x as f64;
+    if x < left || x >= right {
+        return None;
+    }
+
+    let index = ((x - left) / (right - left) * tabs.len() as f64) as usize;
+    Some(index.min(tabs.len() - 1))
+}
+
+/**
+ * Renders a title clipped to a part of the titlebar, centered on the given
+ * center when there is room for it and cut off at the end otherwise
+ */
+fn draw_text(_wm: &WindowManager, _win: &Window, text: &str, left: f64, right: f64, center: f64) {
+    let _ctx = _win.decoration_context;
+    let titlebar = _win.metrics.titlebar as f64;
+    if right <= left || text.is_empty() {
+        return;
+    }
+
+    unsafe {
+        let text_width = _wm.font.text_width(_ctx, text);
+        let top = (titlebar - _wm.font.height(_ctx)) / 2.0;
+        let x = (center - text_width / 2.0).max(left);
+
+        cairo_sys::cairo_save(_ctx);
+        cairo_sys::cairo_rectangle(_ctx, left, 0.0, right - left, titlebar);
+        cairo_sys::cairo_clip(_ctx);
+        set_color(_ctx, palette(&_wm.config).text);
+        _wm.font.draw_text(_ctx, text, x, top);
+        cairo_sys::cairo_restore(_ctx);
+    }
+}
+
+/**
+ * Renders the title of a window between the buttons, centered on the
+ * titlebar when there is room for it and cut off at the buttons otherwise
+ */
+fn draw_title(_wm: &WindowManager, _win: &Window, width: f64) {
+    let (left, right) = title_span(_wm, _win, width);
+    draw_text(_wm, _win, &_win.title, left, right, width / 2.0);
+}
+
+/**
+ * Renders one tab for each window of a tabbed container in the space
+ * between the buttons, the one of the window itself highlighted
+ */
+fn draw_tabs(_wm: &WindowManager, _win: &Window, width: f64, tabs: &[xlib::Window], own: usize) {
+    let _ctx = _win.decoration_context;
+    let (left, right) = title_span(_wm, _win, width);
+    let tab_width = (right - left) / tabs.len() as f64;
+    let palette = palette(&_wm.config);
+
+    for (i, _w) in tabs.iter().enumerate() {
+        let start = left + tab_width * i as f64;
+
+        unsafe {
+            if i == own {
+                set_color(_ctx, palette.tab);
+            } else {
+                set_color(_ctx, palette.border);
+            }
+            cairo_sys::cairo_rectangle(
+                _ctx,
+                start + 1.0,
+                2.0,
+                tab_width - 2.0,
+                _win.metrics.titlebar as f64 - 4.0,
+            );
+            cairo_sys::cairo_fill(_ctx);
+        }
+
+        let title = _wm.windows.get(_w).map_or("", |win| win.title.as_str());
+        draw_text(
+            _wm,
+            _win,
+            title,
+            start + 4.0,
+            start + tab_width - 4.0,
+            start + tab_width / 2.0,
+        );
+    }
+}
+
+/**
+ * Renders a window decoration
+ */
+pub fn draw(_wm: &WindowManager, _win: &Window) {
+    let _ctx = _win.decoration_context;
+    let palette = palette(&_wm.config);
+
+    unsafe {
+        let mut attrs: xlib::XWindowAttributes = zeroed();
+        xlib::XGetWindowAttributes(_wm.display, _win.frame, &mut attrs);
+
+        set_color(_ctx, palette.frame);
+        cairo_sys::cairo_paint(_ctx);
+
+        // Windows asking for attention stand out with an orange titlebar
+        if is_urgent(_win) {
+            set_color(_ctx, palette.urgent);
+        } else {
+            set_color(_ctx, palette.titlebar);
+        }
+        cairo_sys::cairo_rectangle(
+            _ctx,
+            0.0,
+            0.0,
+            attrs.width as f64,
+            _win.metrics.titlebar as f64,
+        );
+        cairo_sys::cairo_fill(_ctx);
+
+        set_color(_ctx, palette.border);
+        cairo_sys::cairo_set_line_width(_ctx, 5.0);
+        cairo_sys::cairo_rectangle(_ctx, 0.0, 0.0, attrs.width as f64, attrs.height as f64);
+        cairo_sys::cairo_stroke(_ctx);
+    }
+
+    for (button, x, y) in layout(_wm, _win) {
+        draw_button(_ctx, _win, palette, button, x, y);
+    }
+
+    let width = unsafe { cairo_sys::cairo_xlib_surface_get_width(_win.decoration_surface) };
+    match tree::tabs(_wm, _win.frame) {
+        Some((tabs, own)) => draw_tabs(_wm, _win, width as f64, &tabs, own),
+        None => draw_title(_wm, _win, width as f64),
+    }
+
+    unsafe {
+        cairo_sys::cairo_surface_flush(_win.decoration_surface);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+use vector2d::Vector2D;
+use workspaces;
+use x11::xlib;
+use {focus_window, query_pointer, Region, Timer, WindowManager};
+
+/**
+ * Pixels from the opposite edge the pointer lands at after flipping, so it
+ * has to go back to the edge to flip again
+ */
+const LANDING: i32 = 32;
+
+/**
+ * Finds the left and right edges of the whole screen, past every monitor
+ */
+fn screen_edges(_wm: &WindowManager) -> (i32, i32) {
+    let left = _wm.monitors.iter().map(|m| m.position.x).min().unwrap_or(0);
+    let right = _wm
+        .monitors
+        .iter()
+        .map(|m| m.position.x + m.size.x as i32)
+        .max()
+        .unwrap_or(0);
+
+    (left, right - 1)
+}
+
+/**
+ * Gives the workspace past the screen edge under the pointer, next when on
+ * the right edge and previous when on the left one
+ */
+fn target(_wm: &WindowManager, pointer: Vector2D<i32>) -> Option<usize> {
+    let (left, right) = screen_edges(_wm);
+
+    if pointer.x <= left {
+        _wm.workspace.checked_sub(1)
+    } else if pointer.x >= right {
+        Some(_wm.workspace + 1).filter(|&index| index < _wm.workspaces.len())
+    } else {
+        None
+    }
+}
+
+/**
+ * Starts waiting to flip when a window dragged by its titlebar reaches the
+ * left or right edge of the screen, and stops once it leaves it
+ */
+pub fn update(_wm: &mut WindowManager, pointer: Vector2D<i32>) {
+    let target = target(_wm, pointer);
+
+    if target == _wm.edge_flip {
+        return;
+    }
+
+    _wm.edge_flip = target;
+    _wm.timers.cancel(&Timer::EdgeFlip);
+    if target.is_some() {
+        let delay = Duration::from_millis(_wm.config.edge_flip as u64);
+        _wm.timers.schedule(delay, Timer::EdgeFlip);
+    }
+}
+
+/**
+ * Stops waiting to flip
+ */
+pub fn cancel(_wm: &mut WindowManager) {
+    _wm.edge_flip = None;
+    _wm.timers.cancel(&Timer::EdgeFlip);
+}
+
+/**
+ * Switches to the workspace past the edge the pointer paused at, taking the
+ * dragged window along. The pointer comes back in from the opposite edge,
+ * and the window goes with it as the drag goes on.
+ */
+pub fn on_timeout(_wm: &mut WindowManager) {
+    let index = match _wm.edge_flip.take() {
+        Some(index) => index,
+        None => return,
+    };
+
+    let _w = match _wm.active_window {
+        Some(_w) if _wm.drag_region == Region::Titlebar && !_wm.drag_keys => _w,
+        _ => return,
+    };
+
+    let forward = index > _wm.workspace;
+    debug!(
+        "Flipping {} to workspace {}",
+        _w, _wm.workspaces[index].name
+    );
+    workspaces::send_to(_wm, _w, index);
+    workspaces::switch(_wm, index);
+    focus_window(_wm, Some(_w));
+
+    let (left, right) = screen_edges(_wm);
+    let (pointer, _) = query_pointer(_wm);
+    let landing = Vector2D::new(
+        if forward {
+            left + LANDING
+        } else {
+            right - LANDING
+        },
+        pointer.y,
+    );
+
+    let win = &_wm.windows[&_w];
+    let position = win.drag_start + (landing - _wm.drag_start);
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, win.frame, position.x, position.y);
+        xlib::XWarpPointer(_wm.display, 0, _wm.root, 0, 0, 0, 0, landing.x, landing.y);
+    }
+}
@@ -0,0 +1,190 @@
+use x11::xlib;
+use {focus_window, raise_window, WindowManager};
+
+/**
+ * Names of the focus policies
+ */
+pub const POLICIES: [&str; 3] = ["click", "sloppy", "strict"];
+
+/**
+ * What a focus policy wants done with the focus after something happened
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Change {
+    Keep,
+    Focus(xlib::Window),
+    Clear,
+}
+
+/**
+ * Decides which window gets the focus as the pointer moves and clicks.
+ * Policies only tell what should change, so they do not depend on the event
+ * loop or the server.
+ */
+pub trait FocusPolicy {
+    /**
+     * Name of the policy in the configuration
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Tells what to do when the pointer enters a client, or the desktop
+     * when there is none
+     */
+    fn entered(&self, _w: Option<xlib::Window>, _focused: Option<xlib::Window>) -> Change {
+        Change::Keep
+    }
+
+    /**
+     * Tells what to do when a client is clicked
+     */
+    fn clicked(&self, _w: xlib::Window, _focused: Option<xlib::Window>) -> Change {
+        Change::Focus(_w)
+    }
+
+    /**
+     * Checks if the policy needs to know when the pointer enters the
+     * desktop
+     */
+    fn watches_desktop(&self) -> bool {
+        false
+    }
+}
+
+/**
+ * Only clicking a window focuses it
+ */
+pub struct Click;
+
+impl FocusPolicy for Click {
+    fn name(&self) -> &'static str {
+        "click"
+    }
+}
+
+/**
+ * The window under the pointer gets the focus, which stays on it when the
+ * pointer moves on to the desktop
+ */
+pub struct Sloppy;
+
+impl FocusPolicy for Sloppy {
+    fn name(&self) -> &'static str {
+        "sloppy"
+    }
+
+    fn entered(&self, _w: Option<xlib::Window>, focused: Option<xlib::Window>) -> Change {
+        match _w {
+            Some(_w) if focused != Some(_w) => Change::Focus(_w),
+            _ => Change::Keep,
+        }
+    }
+}
+
+/**
+ * Only the window under the pointer has the focus, and nothing has it with
+ * the pointer on the desktop
+ */
+pub struct Strict;
+
+impl FocusPolicy for Strict {
+    fn name(&self) -> &'static str {
+        "strict"
+    }
+
+    fn entered(&self, _w: Option<xlib::Window>, focused: Option<xlib::Window>) -> Change {
+        match _w {
+            Some(_w) if focused != Some(_w) => Change::Focus(_w),
+            Some(_) => Change::Keep,
+            None if focused.is_some() => Change::Clear,
+            None => Change::Keep,
+        }
+    }
+
+    fn watches_desktop(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Finds a focus policy by name, falling back to click to focus
+ */
+pub fn by_name(name: &str) -> Box<dyn FocusPolicy> {
+    match name {
+        "sloppy" => Box::new(Sloppy),
+        "strict" => Box::new(Strict),
+        _ => Box::new(Click),
+    }
+}
+
+/**
+ * Checks if a name picks a focus policy
+ */
+pub fn exists(name: &str) -> bool {
+    POLICIES.contains(&name)
+}
+
+/**
+ * Carries out what the focus policy decided. Windows given the focus are
+ * also raised when configured so.
+ */
+pub fn apply(_wm: &mut WindowManager, change: Change) {
+    match change {
+        Change::Keep => {}
+        Change::Focus(_w) => {
+            if _wm.config.raise_on_focus {
+                raise_window(_wm, _w);
+            }
+            focus_window(_wm, Some(_w));
+        }
+        Change::Clear => focus_window(_wm, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entering_the_focused_window_keeps_the_focus() {
+        assert_eq!(Sloppy.entered(Some(1), Some(1)), Change::Keep);
+        assert_eq!(Strict.entered(Some(1), Some(1)), Change::Keep);
+        assert_eq!(Click.entered(Some(1), Some(1)), Change::Keep);
+    }
+
+    #[test]
+    fn entering_another_window_focuses_it() {
+        assert_eq!(Sloppy.entered(Some(2), Some(1)), Change::Focus(2));
+        assert_eq!(Strict.entered(Some(2), None), Change::Focus(2));
+        assert_eq!(Click.entered(Some(2), Some(1)), Change::Keep);
+    }
+
+    #[test]
+    fn entering_the_desktop() {
+        assert_eq!(Strict.entered(None, Some(1)), Change::Clear);
+        assert_eq!(Strict.entered(None, None), Change::Keep);
+        assert_eq!(Sloppy.entered(None, Some(1)), Change::Keep);
+        assert_eq!(Click.entered(None, Some(1)), Change::Keep);
+    }
+
+    #[test]
+    fn clicking_focuses() {
+        for name in POLICIES.iter() {
+            let policy = by_name(name);
+            assert_eq!(policy.clicked(2, Some(1)), Change::Focus(2));
+            assert_eq!(policy.clicked(1, Some(1)), Change::Focus(1));
+            assert_eq!(policy.clicked(1, None), Change::Focus(1));
+        }
+    }
+
+    #[test]
+    fn unknown_names_fall_back_to_click() {
+        assert_eq!(by_name("hover").name(), "click");
+        assert_eq!(by_name("").name(), "click");
+        assert!(!exists("hover"));
+        for name in POLICIES.iter() {
+            assert_eq!(by_name(name).name(), *name);
+            assert!(exists(name));
+        }
+    }
+}
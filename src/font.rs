@@ -0,0 +1,299 @@
+use cairo_sys;
+use std::ffi::{CStr, CString};
+use std::mem::zeroed;
+use std::os::raw::{c_char, c_double, c_int, c_void};
+use std::ptr;
+
+pub enum FcPattern {}
+pub enum FcCharSet {}
+
+const FC_MATCH_PATTERN: c_int = 0;
+const FC_RESULT_MATCH: c_int = 0;
+
+#[link(name = "fontconfig")]
+extern "C" {
+    fn FcNameParse(name: *const c_char) -> *mut FcPattern;
+    fn FcPatternCreate() -> *mut FcPattern;
+    fn FcPatternDestroy(p: *mut FcPattern);
+    fn FcPatternAddString(p: *mut FcPattern, object: *const c_char, s: *const c_char) -> c_int;
+    fn FcPatternAddDouble(p: *mut FcPattern, object: *const c_char, d: c_double) -> c_int;
+    fn FcPatternGetString(
+        p: *mut FcPattern,
+        object: *const c_char,
+        n: c_int,
+        s: *mut *const c_char,
+    ) -> c_int;
+    fn FcPatternGetDouble(
+        p: *mut FcPattern,
+        object: *const c_char,
+        n: c_int,
+        d: *mut c_double,
+    ) -> c_int;
+    fn FcPatternGetCharSet(
+        p: *mut FcPattern,
+        object: *const c_char,
+        n: c_int,
+        c: *mut *mut FcCharSet,
+    ) -> c_int;
+    fn FcConfigSubstitute(config: *mut c_void, p: *mut FcPattern, kind: c_int) -> c_int;
+    fn FcDefaultSubstitute(p: *mut FcPattern);
+    fn FcFontMatch(config: *mut c_void, p: *mut FcPattern, result: *mut c_int) -> *mut FcPattern;
+    fn FcCharSetHasChar(c: *const FcCharSet, ucs4: u32) -> c_int;
+}
+
+// Part of cairo-ft, which is not covered by cairo-sys
+extern "C" {
+    fn cairo_ft_font_face_create_for_pattern(
+        pattern: *mut FcPattern,
+    ) -> *mut cairo_sys::cairo_font_face_t;
+}
+
+/**
+ * A single font resolved through fontconfig
+ */
+struct Font {
+    pattern: *mut FcPattern,
+    charset: *mut FcCharSet,
+    face: *mut cairo_sys::cairo_font_face_t,
+    size: f64,
+}
+
+/**
+ * A font with its fallback chain, used for all rendered text
+ */
+pub struct FontSet {
+    fonts: Vec<Font>,
+}
+
+/**
+ * Builds a fontconfig pattern from either a fontconfig pattern
+ * ("Sans-10:bold") or a "Family Name 10" description. Gives a null pattern
+ * for descriptions that cannot be passed to fontconfig.
+ */
+fn create_pattern(spec: &str) -> *mut FcPattern {
+    if spec.contains('\0') {
+        return ptr::null_mut();
+    }
+
+    unsafe {
+        if spec.contains(':') {
+            let name = CString::new(spec).unwrap();
+            return FcNameParse(name.as_ptr());
+        }
+
+        let mut parts = spec.trim().rsplitn(2, ' ');
+        let last = parts.next().unwrap_or("");
+        let (family, size) = match (last.parse::<f64>(), parts.next()) {
+            (Ok(size), Some(family)) => (family, Some(size)),
+            _ => (spec.trim(), None),
+        };
+
+        let pattern = FcPatternCreate();
+        let family = CString::new(family).unwrap();
+        let object = CString::new("family").unwrap();
+        FcPatternAddString(pattern, object.as_ptr(), family.as_ptr());
+
+        if let Some(size) = size {
+            let object = CString::new("size").unwrap();
+            FcPatternAddDouble(pattern, object.as_ptr(), size);
+        }
+
+        pattern
+    }
+}
+
+/**
+ * Resolves a font description to the best matching installed font
+ */
+fn load_font(spec: &str) -> Option<Font> {
+    let charset_object = CString::new("charset").unwrap();
+    let size_object = CString::new("pixelsize").unwrap();
+    let family_object = CString::new("family").unwrap();
+
+    unsafe {
+        let pattern = create_pattern(spec);
+        if pattern.is_null() {
+            warn!("Invalid font description '{}'", spec);
+            return None;
+        }
+
+        FcConfigSubstitute(ptr::null_mut(), pattern, FC_MATCH_PATTERN);
+        FcDefaultSubstitute(pattern);
+
+        let mut result: c_int = 0;
+        let matched = FcFontMatch(ptr::null_mut(), pattern, &mut result);
+        FcPatternDestroy(pattern);
+
+        if matched.is_null() {
+            warn!("No font matches '{}'", spec);
+            return None;
+        }
+
+        let mut charset: *mut FcCharSet = ptr::null_mut();
+        FcPatternGetCharSet(matched, charset_object.as_ptr(), 0, &mut charset);
+
+        let mut size: c_double = 0.0;
+        if FcPatternGetDouble(matched, size_object.as_ptr(), 0, &mut size) != FC_RESULT_MATCH {
+            size = 13.0;
+        }
+
+        let mut family: *const c_char = ptr::null();
+        if FcPatternGetString(matched, family_object.as_ptr(), 0, &mut family) == FC_RESULT_MATCH {
+            let family = CStr::from_ptr(family).to_string_lossy();
+            debug!("Font '{}' resolved to '{}' at {}px", spec, family, size);
+        }
+
+        Some(Font {
+            pattern: matched,
+            charset,
+            face: cairo_ft_font_face_create_for_pattern(matched),
+            size,
+        })
+    }
+}
+
+impl FontSet {
+    /**
+     * Loads the primary font followed by its fallbacks
+     */
+    pub fn load(primary: &str, fallback: &[String]) -> FontSet {
+        let fonts = Some(primary)
+            .into_iter()
+            .chain(fallback.iter().map(|s| s.as_str()))
+            .filter_map(load_font)
+            .collect();
+
+        FontSet { fonts }
+    }
+
+    /**
+     * Picks the first font in the chain that has a glyph for the character
+     */
+    fn font_for(&self, c: char) -> usize {
+        self.fonts
+            .iter()
+            .position(|font| {
+                !font.charset.is_null() && unsafe { FcCharSetHasChar(font.charset, c as u32) } != 0
+            })
+            .unwrap_or(0)
+    }
+
+    /**
+     * Splits text into runs that are rendered with the same font
+     */
+    fn runs<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+
+        for (i, c) in text.char_indices() {
+            let font = self.font_for(c);
+            if let Some(previous) = current {
+                if previous != font {
+                    runs.push((previous, &text[start..i]));
+                    start = i;
+                }
+            }
+            current = Some(font);
+        }
+
+        if let Some(font) = current {
+            runs.push((font, &text[start..]));
+        }
+
+        runs
+    }
+
+    /**
+     * Selects a font from the chain on a cairo context
+     */
+    fn apply(&self, _ctx: *mut cairo_sys::cairo_t, index: usize) {
+        let font = &self.fonts[index];
+
+        unsafe {
+            cairo_sys::cairo_set_font_face(_ctx, font.face);
+            cairo_sys::cairo_set_font_size(_ctx, font.size);
+        }
+    }
+
+    /**
+     * Gets the line height of the primary font
+     */
+    pub fn height(&self, _ctx: *mut cairo_sys::cairo_t) -> f64 {
+        if self.fonts.is_empty() {
+            return 0.0;
+        }
+
+        self.apply(_ctx, 0);
+
+        unsafe {
+            let mut extents: cairo_sys::FontExtents = zeroed();
+            cairo_sys::cairo_font_extents(_ctx, &mut extents);
+            extents.ascent + extents.descent
+        }
+    }
+
+    /**
+     * Measures the horizontal advance of a piece of text
+     */
+    pub fn text_width(&self, _ctx: *mut cairo_sys::cairo_t, text: &str) -> f64 {
+        if self.fonts.is_empty() {
+            return 0.0;
+        }
+
+        self.runs(text)
+            .iter()
+            .map(|&(index, run)| {
+                self.apply(_ctx, index);
+
+                let run = CString::new(run).unwrap_or_default();
+                unsafe {
+                    let mut extents: cairo_sys::TextExtents = zeroed();
+                    cairo_sys::cairo_text_extents(_ctx, run.as_ptr(), &mut extents);
+                    extents.x_advance
+                }
+            })
+            .sum()
+    }
+
+    /**
+     * Draws text with its top left corner at the given position
+     */
+    pub fn draw_text(&self, _ctx: *mut cairo_sys::cairo_t, text: &str, x: f64, y: f64) {
+        if self.fonts.is_empty() {
+            return;
+        }
+
+        let ascent = unsafe {
+            self.apply(_ctx, 0);
+            let mut extents: cairo_sys::FontExtents = zeroed();
+            cairo_sys::cairo_font_extents(_ctx, &mut extents);
+            extents.ascent
+        };
+
+        unsafe {
+            cairo_sys::cairo_move_to(_ctx, x, y + ascent);
+        }
+
+        for (index, run) in self.runs(text) {
+            self.apply(_ctx, index);
+
+            // Each run continues from where the previous one left the current point
+            let run = CString::new(run).unwrap_or_default();
+            unsafe {
+                cairo_sys::cairo_show_text(_ctx, run.as_ptr());
+            }
+        }
+    }
+}
+
+impl Drop for FontSet {
+    fn drop(&mut self) {
+        for font in &self.fonts {
+            unsafe {
+                cairo_sys::cairo_font_face_destroy(font.face);
+                FcPatternDestroy(font.pattern);
+            }
+        }
+    }
+}
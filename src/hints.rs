@@ -0,0 +1,139 @@
+use std::cmp::{max, min};
+use std::mem::zeroed;
+use vector2d::Vector2D;
+use x11::xlib;
+
+/**
+ * The sizes a client accepts, from its WM_NORMAL_HINTS
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct SizeHints {
+    pub min: Vector2D<u32>,
+    pub max: Option<Vector2D<u32>>,
+    pub base: Vector2D<u32>,
+    pub increment: Vector2D<u32>,
+    pub aspect: Option<(f64, f64)>,
+}
+
+impl Default for SizeHints {
+    fn default() -> SizeHints {
+        SizeHints {
+            min: Vector2D::new(0, 0),
+            max: None,
+            base: Vector2D::new(0, 0),
+            increment: Vector2D::new(1, 1),
+            aspect: None,
+        }
+    }
+}
+
+/**
+ * Turns a hint field into a size, treating nonsense as unset
+ */
+fn size(width: i32, height: i32) -> Option<Vector2D<u32>> {
+    if width > 0 || height > 0 {
+        Some(Vector2D::new(max(width, 0) as u32, max(height, 0) as u32))
+    } else {
+        None
+    }
+}
+
+impl SizeHints {
+    /**
+     * Reads the hints of a client. As ICCCM says, the base size stands in for
+     * a missing minimum size and the other way around.
+     */
+    pub fn read(display: *mut xlib::Display, window: xlib::Window) -> SizeHints {
+        let mut hints = SizeHints::default();
+
+        let raw = unsafe {
+            let mut raw: xlib::XSizeHints = zeroed();
+            let mut supplied = 0;
+            if xlib::XGetWMNormalHints(display, window, &mut raw, &mut supplied) == 0 {
+                return hints;
+            }
+            raw
+        };
+
+        let min_size = if raw.flags & xlib::PMinSize != 0 {
+            size(raw.min_width, raw.min_height)
+        } else {
+            None
+        };
+        let base_size = if raw.flags & xlib::PBaseSize != 0 {
+            size(raw.base_width, raw.base_height)
+        } else {
+            None
+        };
+
+        hints.min = min_size.or(base_size).unwrap_or(hints.min);
+        hints.base = base_size.or(min_size).unwrap_or(hints.base);
+
+        if raw.flags & xlib::PMaxSize != 0 {
+            // A zero maximum along an axis means no limit along it
+            hints.max = size(raw.max_width, raw.max_height).map(|m| {
+                Vector2D::new(
+                    if m.x == 0 { u32::MAX } else { m.x },
+                    if m.y == 0 { u32::MAX } else { m.y },
+                )
+            });
+        }
+
+        if raw.flags & xlib::PResizeInc != 0 {
+            hints.increment =
+                Vector2D::new(max(raw.width_inc, 1) as u32, max(raw.height_inc, 1) as u32);
+        }
+
+        if raw.flags & xlib::PAspect != 0 {
+            let (min_aspect, max_aspect) = (raw.min_aspect, raw.max_aspect);
+            if min_aspect.x > 0 && min_aspect.y > 0 && max_aspect.x > 0 && max_aspect.y > 0 {
+                hints.aspect = Some((
+                    min_aspect.x as f64 / min_aspect.y as f64,
+                    max_aspect.x as f64 / max_aspect.y as f64,
+                ));
+            }
+        }
+
+        hints
+    }
+
+    /**
+     * Adjusts a client size to the closest one the hints allow, never
+     * growing it past what was asked for except to reach the minimum
+     */
+    pub fn constrain(&self, size: Vector2D<u32>) -> Vector2D<u32> {
+        let mut width = max(size.x, self.min.x) as i64;
+        let mut height = max(size.y, self.min.y) as i64;
+        let (base_x, base_y) = (self.base.x as i64, self.base.y as i64);
+
+        // The ratio only covers the part of the client above its base size
+        if let Some((min_ratio, max_ratio)) = self.aspect {
+            let (w, h) = (
+                max(width - base_x, 1) as f64,
+                max(height - base_y, 1) as f64,
+            );
+            if w / h > max_ratio {
+                width = base_x + (h * max_ratio).round() as i64;
+            } else if w / h < min_ratio {
+                height = base_y + (w / min_ratio).round() as i64;
+            }
+        }
+
+        if width > base_x {
+            width -= (width - base_x) % self.increment.x as i64;
+        }
+        if height > base_y {
+            height -= (height - base_y) % self.increment.y as i64;
+        }
+
+        let mut result = Vector2D::new(
+            max(width, self.min.x as i64) as u32,
+            max(height, self.min.y as i64) as u32,
+        );
+        if let Some(limit) = self.max {
+            result = Vector2D::new(min(result.x, limit.x), min(result.y, limit.y));
+        }
+
+        result
+    }
+}
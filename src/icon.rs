@@ -0,0 +1,289 @@
+use cairo_sys;
+use std::os::raw::c_void;
+use std::ptr;
+use x11::xlib;
+use WindowManager;
+
+/**
+ * Largest _NET_WM_ICON read, in 32 bit units, enough for a 256x256 icon
+ * along with the smaller sizes
+ */
+const MAX_LENGTH: i64 = 1 << 17;
+
+/**
+ * Where the icon of a window was read from: _NET_WM_ICON, or else the icon
+ * pixmap and mask of WM_HINTS, none when unset
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    Net,
+    Hints(xlib::Pixmap, xlib::Pixmap),
+}
+
+/**
+ * Reads the raw contents of _NET_WM_ICON, widths and heights followed by
+ * their pixels for every size the client offers
+ */
+fn read(_wm: &WindowManager, _w: xlib::Window) -> Vec<u64> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            _wm.display,
+            _w,
+            _wm.atoms.net_wm_icon,
+            0,
+            MAX_LENGTH,
+            xlib::False,
+            xlib::XA_CARDINAL,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return Vec::new();
+        }
+
+        // Xlib hands out 32 bit items as longs
+        let values = if actual_format == 32 {
+            std::slice::from_raw_parts(data as *const u64, count as usize).to_vec()
+        } else {
+            Vec::new()
+        };
+
+        xlib::XFree(data as *mut c_void);
+        values
+    }
+}
+
+/**
+ * Copies a pixmap out of the server, or gives a null pointer when it is gone
+ * or too large to be an icon
+ */
+unsafe fn get_image(_wm: &WindowManager, pixmap: xlib::Pixmap) -> *mut xlib::XImage {
+    let mut root: xlib::Window = 0;
+    let (mut x, mut y) = (0, 0);
+    let (mut width, mut height, mut border, mut depth) = (0, 0, 0, 0);
+
+    let found = xlib::XGetGeometry(
+        _wm.display,
+        pixmap,
+        &mut root,
+        &mut x,
+        &mut y,
+        &mut width,
+        &mut height,
+        &mut border,
+        &mut depth,
+    );
+    if found == 0 || width == 0 || height == 0 || (width * height) as i64 > MAX_LENGTH {
+        return ptr::null_mut();
+    }
+
+    xlib::XGetImage(_wm.display, pixmap, 0, 0, width, height, !0, xlib::ZPixmap)
+}
+
+/**
+ * Reads the icon pixmap and mask set in WM_HINTS, 0 for those left out
+ */
+pub fn hint_pixmaps(_wm: &WindowManager, _w: xlib::Window) -> (xlib::Pixmap, xlib::Pixmap) {
+    unsafe {
+        let hints = xlib::XGetWMHints(_wm.display, _w);
+        if hints.is_null() {
+            return (0, 0);
+        }
+
+        let (flags, pixmap, mask) = ((*hints).flags, (*hints).icon_pixmap, (*hints).icon_mask);
+        xlib::XFree(hints as *mut c_void);
+        (
+            if flags & xlib::IconPixmapHint != 0 {
+                pixmap
+            } else {
+                0
+            },
+            if flags & xlib::IconMaskHint != 0 {
+                mask
+            } else {
+                0
+            },
+        )
+    }
+}
+
+/**
+ * Reads the icon pixmap and mask of WM_HINTS that older toolkits set instead
+ * of _NET_WM_ICON, laid out like _NET_WM_ICON. Bitmaps are drawn black on
+ * white, and deeper pixmaps are taken as 24 bit true color.
+ */
+fn read_hints(_wm: &WindowManager, pixmap: xlib::Pixmap, mask: xlib::Pixmap) -> Vec<u64> {
+    unsafe {
+        if pixmap == 0 {
+            return Vec::new();
+        }
+
+        let image = get_image(_wm, pixmap);
+        if image.is_null() {
+            return Vec::new();
+        }
+        let mask = if mask != 0 {
+            get_image(_wm, mask)
+        } else {
+            ptr::null_mut()
+        };
+
+        let (width, height) = ((*image).width, (*image).height);
+        let mut values = vec![width as u64, height as u64];
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = xlib::XGetPixel(image, x, y);
+                let color = match (*image).depth {
+                    1 if pixel != 0 => 0x000000,
+                    1 => 0xFFFFFF,
+                    _ => pixel & 0xFFFFFF,
+                };
+                let opaque = mask.is_null()
+                    || x >= (*mask).width
+                    || y >= (*mask).height
+                    || xlib::XGetPixel(mask, x, y) != 0;
+
+                values.push(if opaque { 0xFF000000 | color } else { 0 });
+            }
+        }
+
+        xlib::XDestroyImage(image);
+        if !mask.is_null() {
+            xlib::XDestroyImage(mask);
+        }
+        values
+    }
+}
+
+/**
+ * Picks the smallest image at least as large as the given size, or the
+ * largest one when all are smaller
+ */
+fn pick(data: &[u64], size: u32) -> Option<(usize, usize, &[u64])> {
+    let mut images = Vec::new();
+    let mut rest = data;
+
+    while rest.len() >= 2 {
+        let (width, height) = (rest[0] as usize, rest[1] as usize);
+        let length = match width.checked_mul(height) {
+            Some(length) if length > 0 && rest.len() - 2 >= length => length,
+            _ => break,
+        };
+
+        images.push((width, height, &rest[2..2 + length]));
+        rest = &rest[2 + length..];
+    }
+
+    let size = size as usize;
+    let fitting = images
+        .iter()
+        .filter(|(w, h, _)| *w >= size && *h >= size)
+        .min_by_key(|(w, h, _)| w * h);
+
+    fitting
+        .or_else(|| images.iter().max_by_key(|(w, h, _)| w * h))
+        .cloned()
+}
+
+/**
+ * Copies non-premultiplied ARGB pixels into a cairo image, which wants them
+ * premultiplied
+ */
+fn to_surface(width: usize, height: usize, pixels: &[u64]) -> *mut cairo_sys::cairo_surface_t {
+    unsafe {
+        let surface = cairo_sys::cairo_image_surface_create(
+            cairo_sys::FORMAT_A_RGB32,
+            width as i32,
+            height as i32,
+        );
+        cairo_sys::cairo_surface_flush(surface);
+
+        let data = cairo_sys::cairo_image_surface_get_data(surface);
+        let stride = cairo_sys::cairo_image_surface_get_stride(surface) as usize;
+        if data.is_null() {
+            cairo_sys::cairo_surface_destroy(surface);
+            return ptr::null_mut();
+        }
+
+        for y in 0..height {
+            let row = data.add(y * stride) as *mut u32;
+            for x in 0..width {
+                let argb = pixels[y * width + x] as u32;
+                let alpha = argb >> 24;
+                let scale = |c: u32| (c * alpha + 127) / 255;
+
+                *row.add(x) = (alpha << 24)
+                    | (scale((argb >> 16) & 0xFF) << 16)
+                    | (scale((argb >> 8) & 0xFF) << 8)
+                    | scale(argb & 0xFF);
+            }
+        }
+
+        cairo_sys::cairo_surface_mark_dirty(surface);
+        surface
+    }
+}
+
+/**
+ * Loads the icon of a client as an image close to the given size, from
+ * _NET_WM_ICON or else WM_HINTS, or gives a null pointer when it has none.
+ * Also tells where it was read from.
+ */
+pub fn load(
+    _wm: &WindowManager,
+    _w: xlib::Window,
+    size: u32,
+) -> (*mut cairo_sys::cairo_surface_t, Source) {
+    let mut data = read(_wm, _w);
+    let mut source = Source::Net;
+    if data.is_empty() {
+        let (pixmap, mask) = hint_pixmaps(_wm, _w);
+        data = read_hints(_wm, pixmap, mask);
+        source = Source::Hints(pixmap, mask);
+    }
+
+    let icon = match pick(&data, size) {
+        Some((width, height, pixels)) => to_surface(width, height, pixels),
+        None => ptr::null_mut(),
+    };
+    (icon, source)
+}
+
+/**
+ * Paints an icon scaled into a square at the given position
+ */
+pub fn draw(
+    _ctx: *mut cairo_sys::cairo_t,
+    icon: *mut cairo_sys::cairo_surface_t,
+    x: f64,
+    y: f64,
+    size: f64,
+) {
+    unsafe {
+        let width = cairo_sys::cairo_image_surface_get_width(icon) as f64;
+        let height = cairo_sys::cairo_image_surface_get_height(icon) as f64;
+        let scale = size / width.max(height);
+
+        cairo_sys::cairo_save(_ctx);
+        cairo_sys::cairo_translate(_ctx, x, y);
+        cairo_sys::cairo_scale(_ctx, scale, scale);
+        cairo_sys::cairo_set_source_surface(_ctx, icon, 0.0, 0.0);
+        cairo_sys::cairo_pattern_set_filter(
+            cairo_sys::cairo_get_source(_ctx),
+            cairo_sys::FILTER_GOOD,
+        );
+        cairo_sys::cairo_paint(_ctx);
+        cairo_sys::cairo_restore(_ctx);
+    }
+}
@@ -0,0 +1,398 @@
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/**
+ * How long a connected client gets to send its whole request
+ */
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/**
+ * How long a client gets to take in its reply
+ */
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/**
+ * Largest request accepted, far more than any command needs
+ */
+const MAX_REQUEST: usize = 64 * 1024;
+
+/**
+ * Location of the command socket for the current display
+ */
+pub fn socket_path() -> PathBuf {
+    let display = env::var("DISPLAY").unwrap_or_default();
+    let name = format!("gothite{}.sock", display.replace('/', "_"));
+
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join(name),
+        None => env::temp_dir().join(name),
+    }
+}
+
+/**
+ * Selects the windows a command applies to. Every given field has to match,
+ * and no fields at all means the focused window.
+ */
+#[derive(Default, Debug)]
+pub struct Criteria {
+    pub class: Option<String>,
+    pub title: Option<String>,
+    pub id: Option<u64>,
+}
+
+impl Criteria {
+    /**
+     * Checks if no criteria were given
+     */
+    pub fn is_empty(&self) -> bool {
+        self.class.is_none() && self.title.is_none() && self.id.is_none()
+    }
+
+    /**
+     * Checks a window against the criteria. Class names are compared
+     * ignoring case and titles only need to contain the text.
+     */
+    pub fn matches(&self, ids: &[u64], class: &[String], title: &str) -> bool {
+        let class_matches = self
+            .class
+            .as_ref()
+            .is_none_or(|wanted| class.iter().any(|name| name.eq_ignore_ascii_case(wanted)));
+        let title_matches = self
+            .title
+            .as_ref()
+            .is_none_or(|wanted| title.to_lowercase().contains(&wanted.to_lowercase()));
+        let id_matches = self.id.is_none_or(|wanted| ids.contains(&wanted));
+
+        class_matches && title_matches && id_matches
+    }
+}
+
+/**
+ * A way to go from one window or monitor to another on screen
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /**
+     * Gives the direction named in a command
+     */
+    pub fn named(name: &str) -> Option<Direction> {
+        match name {
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Things that can be done to the selected windows
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    Focus,
+    FocusDirection(Direction),
+    Raise,
+    Close,
+    Move(i32, i32),
+    Float,
+    Reload,
+    AddRule(Vec<(String, String)>, bool),
+    RemoveRule(usize),
+    ListRules,
+    Workspace(String),
+    Send(String),
+    Clients(Option<String>, bool),
+    Visible(Option<usize>, bool),
+    Profile(Option<String>),
+    Layout(Option<String>),
+    Nightlight(Option<String>),
+}
+
+/**
+ * A command with the windows it applies to
+ */
+#[derive(Debug)]
+pub struct Request {
+    pub command: Command,
+    pub criteria: Criteria,
+}
+
+impl Request {
+    /**
+     * Parses the arguments given to gothite-msg
+     */
+    pub fn parse(args: &[String]) -> Result<Request, String> {
+        let mut args = args.iter();
+        let name = args.next().ok_or("missing command")?;
+        let mut criteria = Criteria::default();
+        let mut positional = Vec::new();
+        let mut save = false;
+        let mut all = false;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--class" => criteria.class = Some(value()?),
+                "--title" => criteria.title = Some(value()?),
+                "--id" => criteria.id = Some(parse_id(&value()?)?),
+                "--save" => save = true,
+                "--all" => all = true,
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => positional.push(arg),
+            }
+        }
+
+        let focus_usage = "focus takes at most a direction: left, right, up or down";
+        let command = match (name.as_str(), positional.as_slice()) {
+            ("focus", []) => Command::Focus,
+            ("focus", [direction]) if criteria.is_empty() => {
+                Command::FocusDirection(Direction::named(direction).ok_or(focus_usage)?)
+            }
+            ("raise", []) => Command::Raise,
+            ("close", []) => Command::Close,
+            ("float", []) => Command::Float,
+            ("reload", []) if criteria.is_empty() => Command::Reload,
+            ("reload", _) => return Err("reload takes no arguments".to_string()),
+            ("move", [x, y]) => Command::Move(parse_number(x)?, parse_number(y)?),
+            ("move", _) => return Err("move takes an x and y position".to_string()),
+            ("focus", _) => return Err(focus_usage.to_string()),
+            ("raise", _) | ("close", _) | ("float", _) => {
+                return Err(format!("{} takes no arguments", name))
+            }
+            ("workspace", [name]) if criteria.is_empty() => Command::Workspace(name.to_string()),
+            ("workspace", _) => return Err("workspace takes a workspace name".to_string()),
+            ("send", [name]) => Command::Send(name.to_string()),
+            ("send", _) => return Err("send takes a workspace name".to_string()),
+            ("profile", []) if criteria.is_empty() => Command::Profile(None),
+            ("profile", [name]) if criteria.is_empty() => Command::Profile(Some(name.to_string())),
+            ("profile", _) => return Err("profile takes at most a profile name".to_string()),
+            ("layout", []) if criteria.is_empty() => Command::Layout(None),
+            ("layout", [name]) if criteria.is_empty() => Command::Layout(Some(name.to_string())),
+            ("layout", _) => return Err("layout takes at most a layout name".to_string()),
+            ("nightlight", []) if criteria.is_empty() => Command::Nightlight(None),
+            ("nightlight", [value]) if criteria.is_empty() => {
+                Command::Nightlight(Some(value.to_string()))
+            }
+            ("nightlight", _) => {
+                return Err("nightlight takes at most a temperature, off or auto".to_string())
+            }
+            ("clients", []) => Command::Clients(None, all),
+            ("clients", [name]) => Command::Clients(Some(name.to_string()), all),
+            ("clients", _) => return Err("clients takes at most a workspace name".to_string()),
+            ("visible", []) => Command::Visible(None, all),
+            ("visible", [number]) => Command::Visible(Some(parse_monitor(number)?), all),
+            ("visible", _) => return Err("visible takes at most a monitor number".to_string()),
+            ("rule", _) if !criteria.is_empty() => {
+                return Err("rule commands take no criteria".to_string())
+            }
+            ("rule", [sub, fields @ ..]) if sub.as_str() == "add" && !fields.is_empty() => {
+                Command::AddRule(parse_fields(fields)?, save)
+            }
+            ("rule", [sub, index]) if sub.as_str() == "remove" => {
+                Command::RemoveRule(parse_index(index)?)
+            }
+            ("rule", [sub]) if sub.as_str() == "list" => Command::ListRules,
+            ("rule", _) => return Err("rule takes add KEY=VALUE..., remove N or list".to_string()),
+            _ => return Err(format!("unknown command {}", name)),
+        };
+
+        if save && !matches!(command, Command::AddRule(..)) {
+            return Err("--save only applies to rule add".to_string());
+        }
+        if all && !matches!(command, Command::Clients(..) | Command::Visible(..)) {
+            return Err("--all only applies to clients and visible".to_string());
+        }
+
+        Ok(Request { command, criteria })
+    }
+}
+
+/**
+ * Parses a window id, either in decimal or as 0x prefixed hex like xwininfo
+ * prints it
+ */
+fn parse_id(text: &str) -> Result<u64, String> {
+    let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+        u64::from_str_radix(&text[2..], 16)
+    } else {
+        text.parse()
+    };
+
+    parsed.map_err(|_| format!("invalid window id {}", text))
+}
+
+/**
+ * Splits the KEY=VALUE options of a rule
+ */
+fn parse_fields(fields: &[&String]) -> Result<Vec<(String, String)>, String> {
+    fields
+        .iter()
+        .map(|field| match field.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(format!("invalid rule option {}, expected KEY=VALUE", field)),
+        })
+        .collect()
+}
+
+/**
+ * Parses the number of a rule, counting from 1 as they are listed
+ */
+fn parse_index(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(index) if index > 0 => Ok(index),
+        _ => Err(format!("invalid rule number {}", text)),
+    }
+}
+
+/**
+ * Parses the number of a monitor, counting from 1 like the rules
+ */
+fn parse_monitor(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("invalid monitor number {}", text)),
+    }
+}
+
+/**
+ * Parses a coordinate
+ */
+fn parse_number(text: &str) -> Result<i32, String> {
+    text.parse().map_err(|_| format!("invalid number {}", text))
+}
+
+/**
+ * Listening end of the command socket
+ */
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Server {
+    /**
+     * Starts listening on the socket, replacing one left behind by a
+     * previous run
+     */
+    pub fn bind() -> Option<Server> {
+        let path = socket_path();
+
+        if UnixStream::connect(&path).is_ok() {
+            return None;
+        }
+
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).ok()?;
+        listener.set_nonblocking(true).ok()?;
+
+        Some(Server { listener, path })
+    }
+
+    /**
+     * Where the socket lives
+     */
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /**
+     * Descriptor to wait on for new connections
+     */
+    pub fn fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+
+    /**
+     * Takes the next waiting connection, if there is one
+     */
+    pub fn accept(&self) -> Option<Connection> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false).ok()?;
+                    return Some(Connection { stream });
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/**
+ * A connected client, which sends one request and gets one reply
+ */
+pub struct Connection {
+    stream: UnixStream,
+}
+
+impl Connection {
+    /**
+     * Reads the arguments of the request, which are separated by NUL bytes.
+     * Requests run on the event loop, so clients that take too long or send
+     * too much are given up on.
+     */
+    pub fn read(&mut self) -> Option<Vec<String>> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let mut bytes = Vec::new();
+        let mut buffer = [0; 4096];
+
+        loop {
+            let left = deadline.checked_duration_since(Instant::now())?;
+            if left.is_zero() {
+                return None;
+            }
+            self.stream.set_read_timeout(Some(left)).ok()?;
+
+            match self.stream.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => bytes.extend_from_slice(&buffer[..count]),
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return None,
+            }
+
+            if bytes.len() > MAX_REQUEST {
+                return None;
+            }
+        }
+
+        let text = String::from_utf8(bytes).ok()?;
+        Some(text.split('\0').map(|arg| arg.to_string()).collect())
+    }
+
+    /**
+     * Answers the client and hangs up, dropping the reply if the client does
+     * not take it in time
+     */
+    pub fn reply(mut self, text: &str) {
+        let _ = self.stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        let _ = self.stream.write_all(text.as_bytes());
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
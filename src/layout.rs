@@ -0,0 +1,810 @@
+use cairo_sys;
+use config::Config;
+use decoration;
+use monitor;
+use std::cmp::max;
+use tree::{self, Containers};
+use vector2d::Vector2D;
+use workspaces;
+use x11::xlib;
+use {
+    client_size, extents, focus_window, frame_size, get_geometry, publish_client_list,
+    publish_opaque_region, raise_window, shape_frame, work_area, Direction, Layer, WindowManager,
+};
+
+/**
+ * Name of the mode where windows stay where they are put
+ */
+pub const FLOATING: &str = "floating";
+
+/**
+ * Sets up a tiling layout from the configuration
+ */
+type NewLayout = fn(&Config) -> Box<dyn Layout>;
+
+/**
+ * The tiling layouts by name, each with how it is set up
+ */
+const LAYOUTS: [(&str, NewLayout); 4] = [
+    ("grid", |_| Box::new(Grid)),
+    ("master", |config| {
+        Box::new(MasterStack {
+            width: config.master_width,
+        })
+    }),
+    ("monocle", |_| Box::new(Monocle)),
+    ("tree", |_| Box::new(Containers)),
+];
+
+/**
+ * A place on screen, as a position and a size
+ */
+pub type Area = (Vector2D<i32>, Vector2D<u32>);
+
+/**
+ * Percent of the width the master column grows or shrinks by from the
+ * keyboard
+ */
+const MASTER_STEP: u32 = 5;
+
+/**
+ * Pixels the gaps grow or shrink by from the keyboard
+ */
+const GAP_STEP: u32 = 4;
+
+/**
+ * Space left between tiled windows, and between them and the edges of the
+ * work area
+ */
+pub struct Gaps {
+    pub inner: u32,
+    pub outer: u32,
+}
+
+impl Gaps {
+    /**
+     * Gets the gaps set in the configuration
+     */
+    pub fn configured(config: &Config) -> Gaps {
+        Gaps {
+            inner: config.gaps_inner,
+            outer: config.gaps_outer,
+        }
+    }
+}
+
+/**
+ * A way of arranging the tiled windows of a monitor
+ */
+pub trait Layout {
+    /**
+     * Name the layout is picked by in the configuration
+     */
+    fn name(&self) -> &'static str;
+
+    /**
+     * Splits an area into the places of the given windows of a workspace,
+     * which come in the order they were mapped
+     */
+    fn arrange(
+        &self,
+        _wm: &WindowManager,
+        workspace: usize,
+        area: Area,
+        windows: &[xlib::Window],
+    ) -> Vec<Area>;
+
+    /**
+     * Picks the given windows of a workspace that are kept out of sight
+     * behind another
+     */
+    fn hidden(
+        &self,
+        _wm: &WindowManager,
+        _workspace: usize,
+        _windows: &[xlib::Window],
+    ) -> Vec<xlib::Window> {
+        Vec::new()
+    }
+
+    /**
+     * Tells if focusing a window may change which windows are kept out of
+     * sight
+     */
+    fn stacked(&self) -> bool {
+        false
+    }
+}
+
+/**
+ * Rows of equally sized windows, as many columns as rows or one more. The
+ * last row may have fewer windows, which then get wider.
+ */
+pub struct Grid;
+
+impl Layout for Grid {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn arrange(
+        &self,
+        _wm: &WindowManager,
+        _workspace: usize,
+        area: Area,
+        windows: &[xlib::Window],
+    ) -> Vec<Area> {
+        let count = windows.len();
+        let (origin, size) = area;
+        let columns = (1..).find(|c| c * c >= count).unwrap_or(1);
+        let rows = count.div_ceil(columns.max(1));
+
+        (0..count)
+            .map(|i| {
+                let (row, column) = (i / columns, i % columns);
+                let in_row = columns.min(count - row * columns);
+
+                let (left, right) = split(size.x, column, in_row);
+                let (top, bottom) = split(size.y, row, rows);
+                (
+                    Vector2D::new(origin.x + left, origin.y + top),
+                    Vector2D::new((right - left) as u32, (bottom - top) as u32),
+                )
+            })
+            .collect()
+    }
+}
+
+/**
+ * One window in a column on the left, the master, and the others stacked
+ * above each other on the right. The master column takes a share of the
+ * width, in percent, unless the workspace was given its own.
+ */
+pub struct MasterStack {
+    pub width: u32,
+}
+
+impl Layout for MasterStack {
+    fn name(&self) -> &'static str {
+        "master"
+    }
+
+    fn arrange(
+        &self,
+        _wm: &WindowManager,
+        workspace: usize,
+        area: Area,
+        windows: &[xlib::Window],
+    ) -> Vec<Area> {
+        let count = windows.len();
+        let (origin, size) = area;
+        if count < 2 {
+            return vec![area; count];
+        }
+
+        let width = _wm
+            .workspaces
+            .get(workspace)
+            .and_then(|ws| ws.master_width)
+            .unwrap_or(self.width);
+        let master = size.x * width.clamp(5, 95) / 100;
+        let stack = size.x - master;
+
+        let mut places = vec![(origin, Vector2D::new(master, size.y))];
+        places.extend((0..count - 1).map(|i| {
+            let (top, bottom) = split(size.y, i, count - 1);
+            (
+                Vector2D::new(origin.x + master as i32, origin.y + top),
+                Vector2D::new(stack, (bottom - top) as u32),
+            )
+        }));
+        places
+    }
+}
+
+/**
+ * Every window fills the work area, and only the focused one is shown
+ */
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn arrange(
+        &self,
+        _wm: &WindowManager,
+        _workspace: usize,
+        area: Area,
+        windows: &[xlib::Window],
+    ) -> Vec<Area> {
+        vec![area; windows.len()]
+    }
+
+    fn hidden(
+        &self,
+        _wm: &WindowManager,
+        _workspace: usize,
+        windows: &[xlib::Window],
+    ) -> Vec<xlib::Window> {
+        let front = in_front(_wm, windows);
+        windows.iter().filter(|w| **w != front).cloned().collect()
+    }
+
+    fn stacked(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Gives where the part of the given index starts and ends when a length is
+ * split into equal parts, spreading the leftover pixels
+ */
+fn split(length: u32, index: usize, parts: usize) -> (i32, i32) {
+    let length = length as usize;
+    (
+        (length * index / parts) as i32,
+        (length * (index + 1) / parts) as i32,
+    )
+}
+
+/**
+ * Finds a tiling layout by name, set up as configured
+ */
+pub fn by_name(name: &str, config: &Config) -> Option<Box<dyn Layout>> {
+    LAYOUTS
+        .iter()
+        .find(|(layout, _)| *layout == name)
+        .map(|(_, new)| new(config))
+}
+
+/**
+ * Gives the name of the layout of a workspace: the one picked for it, or
+ * else the configured one
+ */
+pub fn name_of(_wm: &WindowManager, workspace: usize) -> &str {
+    _wm.workspaces
+        .get(workspace)
+        .and_then(|ws| ws.layout.as_deref())
+        .unwrap_or(&_wm.config.layout)
+}
+
+/**
+ * Gives the tiling layout of a workspace, or nothing when it floats
+ */
+pub fn of(_wm: &WindowManager, workspace: usize) -> Option<Box<dyn Layout>> {
+    by_name(name_of(_wm, workspace), &_wm.config)
+}
+
+/**
+ * Picks the layout of a workspace, which it keeps when switching away and
+ * back. Its containers are kept for when it uses the tree layout again.
+ */
+pub fn set(_wm: &mut WindowManager, workspace: usize, name: &str) {
+    info!(
+        "Workspace {} now uses {}",
+        _wm.workspaces[workspace].name, name
+    );
+    _wm.workspaces[workspace].layout = Some(name.to_string());
+    schedule(_wm, workspace);
+}
+
+/**
+ * Switches the current workspace to the next layout, floating included
+ */
+pub fn next(_wm: &mut WindowManager) {
+    let workspace = _wm.workspace;
+    let names: Vec<&str> = Some(FLOATING)
+        .into_iter()
+        .chain(LAYOUTS.iter().map(|(name, _)| *name))
+        .collect();
+    let index = names
+        .iter()
+        .position(|name| *name == name_of(_wm, workspace))
+        .unwrap_or(0);
+
+    set(_wm, workspace, names[(index + 1) % names.len()]);
+}
+
+/**
+ * Checks if a name picks a layout, tiling or not
+ */
+pub fn exists(name: &str) -> bool {
+    name == FLOATING || LAYOUTS.iter().any(|(layout, _)| *layout == name)
+}
+
+/**
+ * Checks if a window takes part in the layout of its workspace. Dialogs,
+ * fixed size windows and windows outside the normal layer float, and so do
+ * the ones that are minimized, maximized or fullscreen.
+ */
+fn is_tiled(_wm: &WindowManager, _w: xlib::Window, workspace: usize) -> bool {
+    match _wm.windows.get(&_w) {
+        Some(win) => {
+            _wm.workspaces[workspace].tree.contains(_w)
+                && win.layer == Layer::Normal
+                && !win.floating
+                && !win.minimized
+                && !win.fullscreen
+                && (!win.maximized_horz || !win.maximized_vert)
+        }
+        None => false,
+    }
+}
+
+/**
+ * Shrinks an area on every side by a number of pixels, down to nothing
+ * within the area however large they are
+ */
+fn inset(area: Area, left: u32, right: u32, top: u32, bottom: u32) -> Area {
+    let (position, size) = area;
+    (
+        Vector2D::new(
+            position.x + left.min(size.x) as i32,
+            position.y + top.min(size.y) as i32,
+        ),
+        Vector2D::new(
+            size.x.saturating_sub(left.saturating_add(right)),
+            size.y.saturating_sub(top.saturating_add(bottom)),
+        ),
+    )
+}
+
+/**
+ * Leaves half of the inner gap on the sides of a place that face another
+ * window rather than the edge of the tiled area
+ */
+fn apart(place: Area, bounds: Area, gap: u32) -> Area {
+    let (position, size) = place;
+    let (origin, extent) = bounds;
+    let half = |inside: bool| if inside { gap / 2 } else { 0 };
+
+    inset(
+        place,
+        half(position.x > origin.x),
+        half(position.x + (size.x as i32) < origin.x + extent.x as i32),
+        half(position.y > origin.y),
+        half(position.y + (size.y as i32) < origin.y + extent.y as i32),
+    )
+}
+
+/**
+ * Moves and resizes the frame of a window to a place given by the layout
+ */
+fn place(_wm: &WindowManager, _w: xlib::Window, area: Area) {
+    let win = &_wm.windows[&_w];
+    let ext = extents(win);
+    let (position, size) = area;
+
+    // Crowded monitors still leave every client its minimum size
+    let smallest = frame_size(
+        ext,
+        Vector2D::new(_wm.config.min_width, _wm.config.min_height),
+    );
+    let size = Vector2D::new(max(size.x, smallest.x), max(size.y, smallest.y));
+    let client = client_size(ext, size);
+
+    unsafe {
+        xlib::XMoveResizeWindow(
+            _wm.display,
+            win.frame,
+            position.x,
+            position.y,
+            size.x,
+            size.y,
+        );
+        xlib::XResizeWindow(_wm.display, _w, client.x, client.y);
+
+        cairo_sys::cairo_xlib_surface_set_size(
+            win.decoration_surface,
+            size.x as i32,
+            size.y as i32,
+        );
+    }
+
+    if win.shaped {
+        shape_frame(_wm, _w);
+    }
+    publish_opaque_region(_wm, win, size);
+}
+
+/**
+ * Finds the monitor a window is on. With a single monitor there is no need
+ * to ask the server where the frame is.
+ */
+fn monitor_of(_wm: &WindowManager, _w: xlib::Window) -> usize {
+    if _wm.monitors.len() < 2 {
+        return 0;
+    }
+
+    let (position, size) = get_geometry(_wm, _wm.windows[&_w].frame);
+    monitor::index_at(&_wm.monitors, position + size.as_i32s() / 2)
+}
+
+/**
+ * Tiles the windows of a workspace, each monitor on its own within the
+ * given work areas
+ */
+fn arrange(_wm: &mut WindowManager, layout: &dyn Layout, workspace: usize, areas: &[Area]) {
+    let mut covered = Vec::new();
+    let tiled: Vec<(xlib::Window, usize)> = _wm
+        .clients
+        .iter()
+        .filter(|w| is_tiled(_wm, **w, workspace))
+        .map(|w| (*w, monitor_of(_wm, *w)))
+        .collect();
+
+    for (index, area) in areas.iter().enumerate() {
+        let windows: Vec<xlib::Window> = tiled
+            .iter()
+            .filter(|(_, monitor)| *monitor == index)
+            .map(|(w, _)| *w)
+            .collect();
+        if windows.is_empty() {
+            continue;
+        }
+
+        debug!(
+            "Tiling {} windows on monitor {} with {}",
+            windows.len(),
+            index,
+            layout.name()
+        );
+        let outer = _wm.gaps.outer;
+        let area = inset(*area, outer, outer, outer, outer);
+        let places = layout.arrange(_wm, workspace, area, &windows);
+        for (w, place_area) in windows.iter().zip(places) {
+            place(_wm, *w, apart(place_area, area, _wm.gaps.inner));
+        }
+
+        covered.extend(layout.hidden(_wm, workspace, &windows));
+    }
+
+    uncover(_wm, workspace, &covered);
+
+    // Tabs on the titlebars follow the windows in their containers
+    for (_w, _) in tiled.iter().filter(|(w, _)| !covered.contains(w)) {
+        decoration::draw(_wm, &_wm.windows[_w]);
+    }
+}
+
+/**
+ * Picks the window shown by a stacked layout: the focused one, or else the
+ * one shown before
+ */
+fn in_front(_wm: &WindowManager, windows: &[xlib::Window]) -> xlib::Window {
+    *windows
+        .iter()
+        .find(|w| Some(**w) == _wm.focused)
+        .or_else(|| windows.iter().find(|w| !_wm.windows[w].covered))
+        .unwrap_or(&windows[0])
+}
+
+/**
+ * Hides or shows the frame of a window kept behind another by a stacked
+ * layout
+ */
+fn set_covered(_wm: &mut WindowManager, _w: xlib::Window, covered: bool) {
+    let current = workspaces::of(_wm, _w) == _wm.workspace;
+    let win = match _wm.windows.get_mut(&_w) {
+        Some(win) if win.covered != covered => win,
+        _ => return,
+    };
+    win.covered = covered;
+
+    unsafe {
+        if covered {
+            xlib::XUnmapWindow(_wm.display, win.frame);
+        } else if current && !win.minimized && !win.hidden {
+            xlib::XMapWindow(_wm.display, win.frame);
+        }
+    }
+}
+
+/**
+ * Shows the frame of a window kept behind another right away, so it can be
+ * focused before the layout is applied again
+ */
+pub fn reveal(_wm: &mut WindowManager, _w: xlib::Window) {
+    set_covered(_wm, _w, false);
+}
+
+/**
+ * Covers the given windows of a workspace and shows all others covered so
+ * far, which may have left the layout
+ */
+fn uncover(_wm: &mut WindowManager, workspace: usize, covered: &[xlib::Window]) {
+    let windows: Vec<xlib::Window> = _wm.workspaces[workspace]
+        .tree
+        .clients()
+        .into_iter()
+        .filter(|w| _wm.windows[w].covered || covered.contains(w))
+        .collect();
+
+    for _w in windows {
+        set_covered(_wm, _w, covered.contains(&_w));
+    }
+}
+
+/**
+ * Has a workspace tiled again once the events at hand are handled, so that
+ * a burst of windows mapping or going away is laid out only once
+ */
+pub fn schedule(_wm: &mut WindowManager, workspace: usize) {
+    _wm.relayout.insert(workspace);
+}
+
+/**
+ * Brings the newly focused window of a workspace to the front when the
+ * layout only shows one, and has new windows open next to it in the tree
+ */
+pub fn focus_changed(_wm: &mut WindowManager, workspace: usize) {
+    tree::focus_changed(_wm, workspace);
+
+    if of(_wm, workspace).is_some_and(|layout| layout.stacked()) {
+        schedule(_wm, workspace);
+    }
+}
+
+/**
+ * Has every workspace tiled again, for when the layout or the work area
+ * changed
+ */
+pub fn schedule_all(_wm: &mut WindowManager) {
+    for workspace in 0.._wm.workspaces.len() {
+        schedule(_wm, workspace);
+    }
+}
+
+/**
+ * Tiles the workspaces that changed since the last time. The work areas
+ * are the same for all of them, so they are only worked out once. A focused
+ * window brought to the front is focused again, since its frame was not
+ * mapped when it got the focus.
+ */
+pub fn flush(_wm: &mut WindowManager) {
+    if _wm.relayout.is_empty() {
+        return;
+    }
+
+    let pending: Vec<usize> = _wm.relayout.drain().collect();
+    let covered = _wm
+        .focused
+        .filter(|w| _wm.windows.get(w).is_some_and(|win| win.covered));
+    let areas: Vec<Area> = (0.._wm.monitors.len())
+        .map(|index| work_area(_wm, index))
+        .collect();
+
+    for workspace in pending {
+        if workspace >= _wm.workspaces.len() {
+            continue;
+        }
+
+        match of(_wm, workspace) {
+            Some(layout) => arrange(_wm, layout.as_ref(), workspace, &areas),
+            // Windows covered by a stacked layout come back once floating
+            None => uncover(_wm, workspace, &[]),
+        }
+    }
+
+    if let Some(_w) = covered.filter(|w| _wm.windows.get(w).is_some_and(|win| !win.covered)) {
+        focus_window(_wm, Some(_w));
+    }
+}
+
+/**
+ * Gives the tiled windows of a workspace on the monitor of a window, in
+ * tiling order
+ */
+fn tiled_beside(_wm: &WindowManager, _w: xlib::Window, workspace: usize) -> Vec<xlib::Window> {
+    let monitor = monitor_of(_wm, _w);
+    _wm.clients
+        .iter()
+        .filter(|w| is_tiled(_wm, **w, workspace) && monitor_of(_wm, **w) == monitor)
+        .cloned()
+        .collect()
+}
+
+/**
+ * Makes a window the first tiled one on its monitor, which is the master
+ * of the master and stack layout. The master itself trades places with the
+ * window after it.
+ */
+pub fn promote(_wm: &mut WindowManager, _w: xlib::Window) {
+    if !tiles(_wm, _w) {
+        return;
+    }
+    let workspace = workspaces::of(_wm, _w);
+
+    let tiled = tiled_beside(_wm, _w, workspace);
+    let (first, other) = match tiled.iter().position(|w| *w == _w) {
+        Some(0) if tiled.len() > 1 => (_w, tiled[1]),
+        Some(index) if index > 0 => (tiled[0], _w),
+        _ => return,
+    };
+
+    debug!("Promoting {} to master", other);
+    let a = _wm.clients.iter().position(|w| *w == first).unwrap();
+    let b = _wm.clients.iter().position(|w| *w == other).unwrap();
+    let promoted = _wm.clients.remove(b);
+    _wm.clients.insert(a, promoted);
+
+    publish_client_list(_wm);
+    schedule(_wm, workspace);
+}
+
+/**
+ * Checks if a window is tiled by the layout of its workspace
+ */
+pub fn tiles(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    let workspace = workspaces::of(_wm, _w);
+    of(_wm, workspace).is_some() && is_tiled(_wm, _w, workspace)
+}
+
+/**
+ * Has two tiled windows trade places, in the tiling order and on screen, so
+ * windows on different monitors swap monitors too
+ */
+pub fn swap(_wm: &mut WindowManager, a: xlib::Window, b: xlib::Window) {
+    let workspace = workspaces::of(_wm, a);
+    debug!("Swapping {} with {}", a, b);
+
+    let i = _wm.clients.iter().position(|w| *w == a).unwrap();
+    let j = _wm.clients.iter().position(|w| *w == b).unwrap();
+    _wm.clients.swap(i, j);
+
+    _wm.workspaces[workspace].tree.swap(a, b);
+
+    // The layout puts windows on the monitor their frame is on
+    let (frame_a, frame_b) = (_wm.windows[&a].frame, _wm.windows[&b].frame);
+    let (position_a, position_b) = (get_geometry(_wm, frame_a).0, get_geometry(_wm, frame_b).0);
+    unsafe {
+        xlib::XMoveWindow(_wm.display, frame_a, position_b.x, position_b.y);
+        xlib::XMoveWindow(_wm.display, frame_b, position_a.x, position_a.y);
+    }
+
+    publish_client_list(_wm);
+    schedule(_wm, workspace);
+}
+
+/**
+ * Grows or shrinks the master column of the current workspace, which keeps
+ * its width when switching away and back
+ */
+pub fn resize_master(_wm: &mut WindowManager, grow: bool) {
+    let workspace = _wm.workspace;
+    if of(_wm, workspace).is_none() {
+        return;
+    }
+
+    let current = _wm.workspaces[workspace]
+        .master_width
+        .unwrap_or(_wm.config.master_width)
+        .clamp(5, 95);
+    let width = if grow {
+        current + MASTER_STEP
+    } else {
+        current - MASTER_STEP
+    };
+
+    debug!("Master width of workspace {} is now {}%", workspace, width);
+    _wm.workspaces[workspace].master_width = Some(width.clamp(5, 95));
+    schedule(_wm, workspace);
+}
+
+/**
+ * Resizes the focused tiled window from the keyboard: right and down grow
+ * it, left and up shrink it. In containers this changes the share of the
+ * innermost one split that way, and in the master and stack layout the
+ * width of the master column, which shrinks as a window in the stack grows.
+ */
+pub fn resize_tiled(_wm: &mut WindowManager, direction: Direction) {
+    let focused = match _wm.focused {
+        Some(_w) if tiles(_wm, _w) => _w,
+        _ => return,
+    };
+    let workspace = workspaces::of(_wm, focused);
+    let vertical = matches!(direction, Direction::Up | Direction::Down);
+    let grow = matches!(direction, Direction::Right | Direction::Down);
+
+    match of(_wm, workspace).map(|layout| layout.name()) {
+        Some("tree") => {
+            let resized = _wm.workspaces[workspace]
+                .tree
+                .resize(focused, vertical, grow);
+            if resized {
+                debug!("Resized {} {:?}", focused, direction);
+                schedule(_wm, workspace);
+            }
+        }
+        Some("master") if !vertical && workspace == _wm.workspace => {
+            let master = tiled_beside(_wm, focused, workspace).first() == Some(&focused);
+            resize_master(_wm, grow == master);
+        }
+        _ => {}
+    }
+}
+
+/**
+ * Grows or shrinks the gaps between tiled windows, or the ones around them
+ */
+pub fn adjust_gaps(_wm: &mut WindowManager, outer: bool, grow: bool) {
+    let gap = if outer {
+        &mut _wm.gaps.outer
+    } else {
+        &mut _wm.gaps.inner
+    };
+
+    *gap = if grow {
+        gap.saturating_add(GAP_STEP)
+    } else {
+        gap.saturating_sub(GAP_STEP)
+    };
+
+    debug!(
+        "Gaps are now {} inside, {} outside",
+        _wm.gaps.inner, _wm.gaps.outer
+    );
+    schedule_all(_wm);
+}
+
+/**
+ * Takes a window out of the tiling layout or puts it back in. The place it
+ * had while floating is kept for the next time it floats.
+ */
+pub fn toggle_floating(_wm: &mut WindowManager, _w: xlib::Window) {
+    let (frame, floating) = match _wm.windows.get(&_w) {
+        Some(win) => (win.frame, win.floating),
+        None => return,
+    };
+    let workspace = workspaces::of(_wm, _w);
+
+    if floating {
+        debug!("Tiling {}", _w);
+        let geometry = get_geometry(_wm, frame);
+        let win = _wm.windows.get_mut(&_w).unwrap();
+        win.float_geometry = Some(geometry);
+        win.floating = false;
+    } else {
+        debug!("Floating {}", _w);
+        let win = _wm.windows.get_mut(&_w).unwrap();
+        win.floating = true;
+
+        if let Some(area) = win.float_geometry {
+            place(_wm, _w, area);
+        }
+        raise_window(_wm, _w);
+    }
+
+    schedule(_wm, workspace);
+}
+
+/**
+ * Focuses the next or previous tiled window on the monitor of the focused
+ * one, in tiling order, bringing it to the front
+ */
+pub fn cycle(_wm: &mut WindowManager, forward: bool) {
+    let focused = match _wm.focused {
+        Some(_w) if tiles(_wm, _w) => _w,
+        _ => return,
+    };
+
+    let (workspace, monitor) = (workspaces::of(_wm, focused), monitor_of(_wm, focused));
+    let tiled: Vec<xlib::Window> = _wm
+        .clients
+        .iter()
+        .filter(|w| is_tiled(_wm, **w, workspace) && monitor_of(_wm, **w) == monitor)
+        .cloned()
+        .collect();
+
+    let index = tiled.iter().position(|w| *w == focused).unwrap();
+    let next = if forward {
+        (index + 1) % tiled.len()
+    } else {
+        (index + tiled.len() - 1) % tiled.len()
+    };
+
+    raise_window(_wm, tiled[next]);
+    focus_window(_wm, Some(tiled[next]));
+}
@@ -6,26 +6,187 @@
 #[macro_use]
 extern crate log;
 extern crate cairo_sys;
+extern crate libc;
+extern crate regex;
+extern crate toml;
 extern crate vector2d;
 extern crate x11;
 
-use std::cmp::max;
-use std::collections::HashMap;
-use std::ffi::CString;
-use std::mem::uninitialized;
-use std::os::raw::c_void;
+mod atoms;
+mod autostart;
+mod bar;
+mod check;
+mod clipboard;
+mod config;
+mod dbus;
+mod decoration;
+mod flip;
+mod focus;
+mod font;
+mod hints;
+mod icon;
+mod ipc;
+mod layout;
+mod menu;
+mod monitor;
+mod nightlight;
+mod notify;
+mod profiles;
+mod rules;
+mod shape;
+mod snap;
+mod startup;
+mod strut;
+mod timer;
+mod tree;
+mod workspaces;
+mod zoom;
+
+use atoms::Atoms;
+use config::Config;
+use decoration::{Button, Metrics, Region};
+use font::FontSet;
+use hints::SizeHints;
+use ipc::Direction;
+use menu::{Action, Item};
+use monitor::Monitor;
+use std::cmp::{max, min};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::{CStr, CString};
+use std::mem::{uninitialized, zeroed};
+use std::os::raw::{c_char, c_void};
+use std::process;
 use std::ptr;
+use std::time::{Duration, Instant};
+use strut::Strut;
+use timer::Timers;
 use vector2d::Vector2D;
 use x11::keysym;
 use x11::xlib;
 
-const DECORATION_PADDING: i32 = 10;
+/**
+ * Space taken up by the decoration on each side of a client
+ */
+#[derive(Clone, Copy, PartialEq)]
+struct Extents {
+    left: i32,
+    right: i32,
+    top: i32,
+    bottom: i32,
+}
+
+const NO_EXTENTS: Extents = Extents {
+    left: 0,
+    right: 0,
+    top: 0,
+    bottom: 0,
+};
 
 // These are not in the x11 crate
 // Taken from https://tronche.com/gui/x/xlib/appendix/b/
 const XC_ARROW: u32 = 2;
+const XC_BOTTOM_LEFT_CORNER: u32 = 12;
+const XC_BOTTOM_RIGHT_CORNER: u32 = 14;
+const XC_BOTTOM_SIDE: u32 = 16;
 const XC_CROSSHAIR: u32 = 34;
 const XC_FLEUR: u32 = 52;
+const XC_HAND2: u32 = 60;
+const XC_LEFT_SIDE: u32 = 70;
+const XC_RIGHT_SIDE: u32 = 96;
+const XC_TOP_LEFT_CORNER: u32 = 134;
+const XC_TOP_RIGHT_CORNER: u32 = 136;
+const XC_TOP_SIDE: u32 = 138;
+const XC_WATCH: u32 = 150;
+
+const FRAME_POOL_SIZE: usize = 4;
+
+/**
+ * Shortcuts grabbed on the root window, which work whatever has focus
+ */
+const ROOT_SHORTCUTS: [(u32, u32); 30] = [
+    (keysym::XK_Tab, xlib::Mod1Mask),
+    (keysym::XK_d, xlib::Mod4Mask),
+    (keysym::XK_u, xlib::Mod4Mask),
+    (keysym::XK_Return, xlib::Mod4Mask),
+    (keysym::XK_j, xlib::Mod4Mask),
+    (keysym::XK_k, xlib::Mod4Mask),
+    (keysym::XK_b, xlib::Mod4Mask),
+    (keysym::XK_v, xlib::Mod4Mask),
+    (keysym::XK_z, xlib::Mod4Mask),
+    (keysym::XK_t, xlib::Mod4Mask),
+    (keysym::XK_f, xlib::Mod4Mask),
+    (keysym::XK_h, xlib::Mod4Mask),
+    (keysym::XK_l, xlib::Mod4Mask),
+    (keysym::XK_equal, xlib::Mod4Mask),
+    (keysym::XK_minus, xlib::Mod4Mask),
+    (keysym::XK_equal, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_minus, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_Left, xlib::Mod4Mask),
+    (keysym::XK_Right, xlib::Mod4Mask),
+    (keysym::XK_Up, xlib::Mod4Mask),
+    (keysym::XK_Down, xlib::Mod4Mask),
+    (keysym::XK_Left, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_Right, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_Up, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_Down, xlib::Mod4Mask | xlib::ShiftMask),
+    (keysym::XK_Left, xlib::Mod4Mask | xlib::ControlMask),
+    (keysym::XK_Right, xlib::Mod4Mask | xlib::ControlMask),
+    (keysym::XK_Up, xlib::Mod4Mask | xlib::ControlMask),
+    (keysym::XK_Down, xlib::Mod4Mask | xlib::ControlMask),
+    (keysym::XK_space, xlib::Mod4Mask),
+];
+
+/**
+ * Arrow keys that move the focus with SUPER, the focused window with
+ * SUPER+SHIFT and resize it when tiled with SUPER+CTRL
+ */
+const DIRECTION_KEYS: [(u32, Direction); 4] = [
+    (keysym::XK_Left, Direction::Left),
+    (keysym::XK_Right, Direction::Right),
+    (keysym::XK_Up, Direction::Up),
+    (keysym::XK_Down, Direction::Down),
+];
+
+// Values of WM_STATE, from ICCCM
+const WITHDRAWN_STATE: i64 = 0;
+const NORMAL_STATE: i64 = 1;
+const ICONIC_STATE: i64 = 3;
+
+const UNHANDLED_SUMMARY_INTERVAL: Duration = Duration::from_secs(30);
+
+const LAUNCH_BURST_INTERVAL: Duration = Duration::from_secs(2);
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+const HUNG_PULSE_INTERVAL: Duration = Duration::from_millis(50);
+
+const ORPHAN_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/**
+ * Buttons that may be held down during a drag
+ */
+const DRAG_BUTTONS: u32 = xlib::Button1Mask
+    | xlib::Button2Mask
+    | xlib::Button3Mask
+    | xlib::Button4Mask
+    | xlib::Button5Mask;
+
+/**
+ * How often a drag is checked for a grab that outlived it
+ */
+const GRAB_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/**
+ * How long a drag without a button held may go without moving before it
+ * is given up
+ */
+const DRAG_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/**
+ * How long the place of a crashed window is kept for its client to restart
+ */
+const CRASH_INTERVAL: Duration = Duration::from_secs(10);
 
 /**
  * The catch-all error reporter
@@ -39,6 +200,41 @@ extern "C" fn error_handler(_display: *mut xlib::Display, _ev: *mut xlib::XError
     0
 }
 
+/**
+ * Timeouts handled by the event loop
+ */
+#[derive(PartialEq)]
+enum Timer {
+    StartupTimeout(String),
+    UnhandledSummary,
+    PingTimeout(xlib::Window),
+    HungPulse,
+    BarRefresh,
+    NotificationExpire(u32),
+    ClipboardTimeout(xlib::Window),
+    OrphanCheck,
+    Profile,
+    ZoomRefresh,
+    KeyDrag,
+    GrabWatchdog,
+    EdgeFlip,
+    Nightlight,
+}
+
+/**
+ * Stacking layers, from bottom to top
+ */
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Layer {
+    Desktop,
+    Below,
+    Normal,
+    Above,
+    Dock,
+    Fullscreen,
+    Popup,
+}
+
 /**
  * Window structure
  */
@@ -48,6 +244,109 @@ struct Window {
     decoration_context: *mut cairo_sys::cairo_t,
     drag_start: Vector2D<i32>,
     drag_start_size: Vector2D<u32>,
+    layer: Layer,
+    skip_taskbar: bool,
+    skip_pager: bool,
+    hovered: Option<Button>,
+    region: Region,
+    hung: bool,
+    minimized: bool,
+    maximized_horz: bool,
+    maximized_vert: bool,
+    passthrough: bool,
+    click_through: bool,
+    restore_geometry: Option<(Vector2D<i32>, Vector2D<u32>)>,
+    fullscreen: bool,
+    fullscreen_restore: Option<(Vector2D<i32>, Vector2D<u32>)>,
+    fullscreen_monitors: Option<[usize; 4]>,
+    startup_id: Option<String>,
+    mapped_at: Instant,
+    shaped: bool,
+    hidden: bool,
+    size_hints: SizeHints,
+    accepts_input: bool,
+    class: Vec<String>,
+    closing: bool,
+    urgent: bool,
+    demands_attention: bool,
+    decorated: bool,
+    title: String,
+    icon: *mut cairo_sys::cairo_surface_t,
+    icon_source: icon::Source,
+    gtk_extents: Extents,
+    metrics: Metrics,
+    floating: bool,
+    float_geometry: Option<(Vector2D<i32>, Vector2D<u32>)>,
+    covered: bool,
+}
+
+impl Direction {
+    /**
+     * Splits an offset into how far it goes in the direction and how far it
+     * strays from it sideways
+     */
+    fn project(self, offset: Vector2D<i32>) -> (i32, i32) {
+        match self {
+            Direction::Left => (-offset.x, offset.y),
+            Direction::Right => (offset.x, offset.y),
+            Direction::Up => (-offset.y, offset.x),
+            Direction::Down => (offset.y, offset.x),
+        }
+    }
+
+    /**
+     * Rates how far away an offset is in the direction, straying sideways
+     * counting double, or gives nothing when it points elsewhere
+     */
+    fn distance(self, offset: Vector2D<i32>) -> Option<i64> {
+        match self.project(offset) {
+            (along, across) if along > 0 => Some(along as i64 + 2 * across.abs() as i64),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Handles a ClientMessage of the type it was registered for
+ */
+type MessageHandler = fn(&mut WindowManager, xlib::XClientMessageEvent);
+
+/**
+ * The place of a window whose client went away without being closed
+ */
+struct Lost {
+    class: Vec<String>,
+    position: Vector2D<i32>,
+    size: Vector2D<u32>,
+    workspace: usize,
+    at: Instant,
+}
+
+/**
+ * A frame window and its decoration surface, kept around for reuse
+ */
+struct Frame {
+    window: xlib::Window,
+    surface: *mut cairo_sys::cairo_surface_t,
+    context: *mut cairo_sys::cairo_t,
+}
+
+/**
+ * What is known about a client before it gets a frame
+ */
+struct Candidate {
+    window: xlib::Window,
+    attrs: xlib::XWindowAttributes,
+    types: Vec<xlib::Atom>,
+    states: Vec<xlib::Atom>,
+    class: Vec<String>,
+    desktop: Option<usize>,
+    startup_id: Option<String>,
+    iconic: bool,
+    decorated: bool,
+    click_through: bool,
+    follow: bool,
+    floating: bool,
 }
 
 /**
@@ -57,8 +356,55 @@ struct WindowManager {
     display: *mut xlib::Display,
     root: xlib::Window,
     windows: HashMap<xlib::Window, Window>,
+    clients: Vec<xlib::Window>,
+    stack: Vec<xlib::Window>,
     drag_start: Vector2D<i32>,
-    active_window: *const Window,
+    drag_region: Region,
+    drag_grab: bool,
+    drag_keys: bool,
+    drag_offset: Vector2D<i32>,
+    held_key: Option<(u32, Vector2D<i32>)>,
+    drag_touched: Instant,
+    drag_buttons: bool,
+    active_window: Option<xlib::Window>,
+    focused: Option<xlib::Window>,
+    config: Config,
+    atoms: Atoms,
+    timers: Timers<Timer>,
+    startup: startup::Assembler,
+    launches: HashMap<String, usize>,
+    autostart: Vec<autostart::Pending>,
+    autostart_done: Vec<process::Child>,
+    workspaces: Vec<workspaces::Workspace>,
+    workspace_names: Vec<String>,
+    workspace_focus: HashMap<usize, xlib::Window>,
+    font: FontSet,
+    monitors: Vec<Monitor>,
+    menu: Option<menu::Menu>,
+    workspace: usize,
+    unhandled: HashMap<i32, u32>,
+    frame_pool: Vec<Frame>,
+    shortcuts_inhibited: bool,
+    unmanaged: Vec<(xlib::Window, Layer)>,
+    struts: HashMap<xlib::Window, Strut>,
+    ipc: Option<ipc::Server>,
+    shape_event: Option<i32>,
+    showing_desktop: bool,
+    bar: bar::Bar,
+    event_time: xlib::Time,
+    user_time: xlib::Time,
+    notifications: Option<notify::Notifications>,
+    clipboard: clipboard::Clipboard,
+    lost: Vec<Lost>,
+    message_handlers: HashMap<xlib::Atom, MessageHandler>,
+    relayout: HashSet<usize>,
+    gaps: layout::Gaps,
+    focus_policy: Box<dyn focus::FocusPolicy>,
+    zoom: Option<zoom::Zoom>,
+    snap_preview: Option<snap::Preview>,
+    edge_flip: Option<usize>,
+    nightlight: nightlight::Nightlight,
+    profile: Option<String>,
 }
 
 /**
@@ -83,11 +429,25 @@ fn reparent_initial_windows(_wm: &mut WindowManager) {
         );
 
         if root == _wm.root {
-            debug!("Reparenting {} windows", count);
+            // Look at everything first, so windows are framed from a consistent
+            // snapshot instead of one that changes as frames are created
+            let (candidates, unframed): (Vec<Candidate>, Vec<Candidate>) = (0..count as isize)
+                .map(|_i| inspect_window(_wm, *windows.offset(_i)))
+                .filter(|candidate| should_adopt(_wm, candidate))
+                .partition(|candidate| should_frame(_wm, &candidate.types));
 
-            for _i in 0..count {
-                create_window_frame(_wm, *windows.offset(_i as isize), true);
+            debug!("Reparenting {} of {} windows", candidates.len(), count);
+
+            // XQueryTree lists the children bottom to top, so the stacking is kept
+            for candidate in &candidates {
+                create_window_frame(_wm, candidate, true);
+            }
+
+            for candidate in &unframed {
+                add_unmanaged(_wm, candidate);
             }
+
+            restack(_wm);
         }
 
         xlib::XFree(windows as *mut c_void);
@@ -96,531 +456,4655 @@ fn reparent_initial_windows(_wm: &mut WindowManager) {
 }
 
 /**
- * Binds a input button to a window
+ * Fetches the attributes, type and state of a client
  */
-fn bind_window_button(_wm: &WindowManager, _w: xlib::Window, _b: u32, _m: u32, _c: u32) {
+fn inspect_window(_wm: &WindowManager, _w: xlib::Window) -> Candidate {
+    let mut attrs: xlib::XWindowAttributes = unsafe { zeroed() };
     unsafe {
-        xlib::XGrabButton(
-            _wm.display,
-            _b,
-            _m,
-            _w,
-            0,
-            xlib::ButtonPressMask as u32
-                | xlib::ButtonReleaseMask as u32
-                | xlib::ButtonMotionMask as u32,
-            xlib::GrabModeAsync,
-            xlib::GrabModeAsync,
-            0,
-            xlib::XCreateFontCursor(_wm.display, _c),
-        );
+        xlib::XGetWindowAttributes(_wm.display, _w, &mut attrs);
     }
-}
 
-/**
- * Binds a input key to a window
- */
-fn bind_window_key(_wm: &WindowManager, _w: xlib::Window, _k: u32, _m: u32) {
-    unsafe {
-        xlib::XGrabKey(
-            _wm.display,
-            xlib::XKeysymToKeycode(_wm.display, _k as u64) as i32,
-            _m,
-            _w,
-            0,
-            xlib::GrabModeAsync,
-            xlib::GrabModeAsync,
-        );
+    Candidate {
+        window: _w,
+        attrs,
+        types: get_atom_list(_wm, _w, _wm.atoms.net_wm_window_type),
+        states: get_atom_list(_wm, _w, _wm.atoms.net_wm_state),
+        class: get_class(_wm, _w),
+        desktop: get_cardinal_list(_wm, _w, _wm.atoms.net_wm_desktop)
+            .first()
+            .map(|d| *d as usize),
+        startup_id: get_string_property(_wm, _w, _wm.atoms.net_startup_id),
+        iconic: get_cardinal_list(_wm, _w, _wm.atoms.wm_state).first() == Some(&ICONIC_STATE),
+        decorated: wants_decoration(_wm, _w),
+        click_through: false,
+        follow: false,
+        floating: wants_floating(_wm, _w),
     }
 }
 
 /**
- * Resizes a window
+ * Checks if a client is better left out of tiling: dialogs, windows that
+ * belong to another one and windows of a fixed size
  */
-fn resize_window(_wm: &WindowManager, _w: xlib::Window, _win: &Window, delta: Vector2D<i32>) {
-    let new_dimension = _win.drag_start_size.as_i32s() + delta;
-    let new_dimension = Vector2D::new(max(10, new_dimension.x), max(10, new_dimension.y)).as_u32s();
-
-    unsafe {
-        let width = new_dimension.x + (DECORATION_PADDING as u32 * 2);
-        let height = new_dimension.y + (DECORATION_PADDING as u32 * 2);
-
-        xlib::XResizeWindow(_wm.display, _win.frame, width as u32, height as u32);
-        xlib::XResizeWindow(_wm.display, _w, new_dimension.x, new_dimension.y);
+fn wants_floating(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    let types = get_atom_list(_wm, _w, _wm.atoms.net_wm_window_type);
+    if types.contains(&_wm.atoms.net_wm_window_type_dialog)
+        || types.contains(&_wm.atoms.net_wm_window_type_utility)
+    {
+        return true;
+    }
 
-        cairo_sys::cairo_xlib_surface_set_size(
-            _win.decoration_surface,
-            width as i32,
-            height as i32,
-        );
+    let mut owner: xlib::Window = 0;
+    if unsafe { xlib::XGetTransientForHint(_wm.display, _w, &mut owner) } != 0 {
+        return true;
     }
+
+    let hints = SizeHints::read(_wm.display, _w);
+    hints.max == Some(hints.min)
 }
 
 /**
- * Moves a window
+ * Reads the decorations field of _MOTIF_WM_HINTS, where clients drawing
+ * their own borders ask for none
  */
-fn move_window(_wm: &WindowManager, _w: xlib::Window, _win: &Window, delta: Vector2D<i32>) {
-    let new_position = _win.drag_start + delta;
+fn wants_decoration(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    const MWM_HINTS_DECORATIONS: i64 = 1 << 1;
 
-    unsafe {
-        xlib::XMoveWindow(_wm.display, _win.frame, new_position.x, new_position.y);
+    let hints = get_cardinal_list(_wm, _w, _wm.atoms.motif_wm_hints);
+    match (hints.first(), hints.get(2)) {
+        (Some(flags), Some(decorations)) if flags & MWM_HINTS_DECORATIONS != 0 => *decorations != 0,
+        _ => true,
     }
 }
 
 /**
- * Re-stacks window(s)
+ * Reads _GTK_FRAME_EXTENTS, the invisible margin for shadows that clients
+ * drawing their own decorations keep around themselves. The margin is kept
+ * within the client, whatever the client says.
  */
-fn restack_windows(_wm: &mut WindowManager, _w: xlib::Window) {
-    unsafe {
-        let mut root: xlib::Window = uninitialized();
-        let mut parent: xlib::Window = uninitialized();
-        let mut windows: *mut xlib::Window = uninitialized();
-        let mut count: u32 = 0;
+fn get_gtk_extents(_wm: &WindowManager, _w: xlib::Window) -> Extents {
+    match get_cardinal_list(_wm, _w, _wm.atoms.gtk_frame_extents).as_slice() {
+        &[left, right, top, bottom] => {
+            let (_, size) = get_geometry(_wm, _w);
+            let (width, height) = (size.x as i64, size.y as i64);
+            let left = left.clamp(0, width);
+            let top = top.clamp(0, height);
 
-        if xlib::XQueryTree(
-            _wm.display,
-            _wm.root,
-            &mut root,
-            &mut parent,
-            &mut windows,
-            &mut count,
-        ) != 0
-        {
-            for _i in 0..count {
-                let next = *windows.offset(_i as isize);
-                if next != _w {
-                    xlib::XRaiseWindow(_wm.display, next);
-                    xlib::XSetInputFocus(
-                        _wm.display,
-                        next,
-                        xlib::RevertToPointerRoot,
-                        xlib::CurrentTime,
-                    );
-                    break;
-                }
+            Extents {
+                left: left as i32,
+                right: right.clamp(0, width - left) as i32,
+                top: top as i32,
+                bottom: bottom.clamp(0, height - top) as i32,
             }
         }
+        _ => NO_EXTENTS,
     }
 }
 
 /**
- * Checks if a window can be gracefully killed
+ * Gets the space a frame adds around a client. The invisible margin of
+ * client-side decorations is taken off, which makes the extents negative
+ * when the frame has to clip it.
  */
-fn can_kill_window_gracefully(_wm: &mut WindowManager, _w: xlib::Window) -> bool {
-    let mut atoms: *mut xlib::Atom = unsafe { uninitialized() };
-    let mut atom_count: i32 = 0;
-
-    let result = unsafe { xlib::XGetWMProtocols(_wm.display, _w, &mut atoms, &mut atom_count) };
-
-    if result == 0 {
-        return false;
-    }
-
-    let wm_delete_window_str = CString::new("WM_DELETE_WINDOW").unwrap();
-    let delete_atom =
-        unsafe { xlib::XInternAtom(_wm.display, wm_delete_window_str.as_ptr(), xlib::False) };
+fn frame_extents(metrics: Metrics, decorated: bool, gtk: Extents) -> Extents {
+    let ext = if decorated {
+        metrics.extents()
+    } else {
+        NO_EXTENTS
+    };
 
-    // FIXME There must be an alternative for a loop
-    for _i in 0..atom_count {
-        let v = unsafe { atoms.offset(_i as isize) };
-        if unsafe { *v == delete_atom } {
-            return true;
-        }
+    Extents {
+        left: ext.left - gtk.left,
+        right: ext.right - gtk.right,
+        top: ext.top - gtk.top,
+        bottom: ext.bottom - gtk.bottom,
     }
-
-    return false;
 }
 
 /**
- * Kills a window
+ * Gets the space the frame of a window adds around its client
  */
-fn kill_window(_wm: &mut WindowManager, _w: xlib::Window) {
-    if can_kill_window_gracefully(_wm, _w) {
-        let mut ev: xlib::XEvent = unsafe { uninitialized() };
-        let wm_protocols_str = CString::new("WM_PROTOCOLS").unwrap();
-        let wm_delete_window_str = CString::new("WM_DELETE_WINDOW").unwrap();
-
-        unsafe {
-            let wm_protocols =
-                xlib::XInternAtom(_wm.display, wm_protocols_str.as_ptr(), xlib::False);
-            let wm_delete_window =
-                xlib::XInternAtom(_wm.display, wm_delete_window_str.as_ptr(), xlib::False);
-
-            ev.client_message.type_ = xlib::ClientMessage;
-            ev.client_message.message_type = wm_protocols;
-            ev.client_message.window = _w;
-            ev.client_message.format = 32;
-            ev.client_message.data.set_long(0, wm_delete_window as i64);
-
-            xlib::XSendEvent(_wm.display, _w, xlib::False, 0, &mut ev);
-        }
+fn extents(_win: &Window) -> Extents {
+    frame_extents(_win.metrics, _win.decorated, _win.gtk_extents)
+}
 
-        debug!("Gracefully killed window");
-    } else {
-        unsafe {
-            xlib::XKillClient(_wm.display, _w);
-        }
+/**
+ * Decides if a window found at startup should get a frame
+ */
+fn should_adopt(_wm: &WindowManager, candidate: &Candidate) -> bool {
+    let is_frame = _wm
+        .frame_pool
+        .iter()
+        .any(|frame| frame.window == candidate.window);
 
-        debug!("Killed window");
-    }
+    !is_frame
+        && candidate.attrs.override_redirect == 0
+        && (candidate.attrs.map_state == xlib::IsViewable || candidate.iconic)
 }
 
 /**
- * Renders a window decoration
+ * Decides if a window gets a frame, or is shown as it is because of its type
  */
-fn draw_window_decoration(_wm: &WindowManager, _w: xlib::Window, _ctx: *mut cairo_sys::cairo_t) {
-    unsafe {
-        let mut attrs: xlib::XWindowAttributes = uninitialized();
-        xlib::XGetWindowAttributes(_wm.display, _w, &mut attrs);
-
-        cairo_sys::cairo_set_source_rgb(_ctx, 0.231, 0.25, 0.322);
-        cairo_sys::cairo_paint(_ctx);
+fn should_frame(_wm: &WindowManager, types: &[xlib::Atom]) -> bool {
+    let atoms = &_wm.atoms;
+    let unframed = [
+        atoms.net_wm_window_type_dock,
+        atoms.net_wm_window_type_desktop,
+        atoms.net_wm_window_type_tooltip,
+        atoms.net_wm_window_type_notification,
+        atoms.net_wm_window_type_dropdown_menu,
+        atoms.net_wm_window_type_popup_menu,
+        atoms.net_wm_window_type_combo,
+        atoms.net_wm_window_type_dnd,
+        atoms.net_wm_window_type_splash,
+    ];
 
-        cairo_sys::cairo_set_source_rgb(_ctx, 0.298, 0.337, 0.416);
-        cairo_sys::cairo_set_line_width(_ctx, 5.0);
-        cairo_sys::cairo_rectangle(_ctx, 0.0, 0.0, attrs.width as f64, attrs.height as f64);
-        cairo_sys::cairo_stroke(_ctx);
-    }
+    !types.iter().any(|t| unframed.contains(t))
 }
 
 /**
- * Removes a window frame
+ * Keeps track of a window shown without a frame, so it can be stacked in
+ * its layer
  */
-fn remove_window_frame(_wm: &mut WindowManager, _w: xlib::Window) {
-    if !_wm.windows.contains_key(&_w) {
+fn add_unmanaged(_wm: &mut WindowManager, candidate: &Candidate) {
+    let _w = candidate.window;
+    if _wm.unmanaged.iter().any(|(w, _)| *w == _w) {
         return;
     }
 
-    let win = _wm.windows.get(&_w).unwrap();
-    unsafe {
-        cairo_sys::cairo_surface_destroy(win.decoration_surface);
-        cairo_sys::cairo_destroy(win.decoration_context);
-        //cairo_sys::cairo_close_x11_surface(win.decoration_surface);
+    let layer = window_layer(_wm, &candidate.types, &candidate.states);
+    debug!("Showing {} without a frame in {:?}", _w, layer);
 
-        xlib::XUnmapWindow(_wm.display, win.frame);
-        xlib::XReparentWindow(_wm.display, _w, _wm.root, 0, 0);
-        xlib::XRemoveFromSaveSet(_wm.display, _w);
-        xlib::XDestroyWindow(_wm.display, win.frame);
+    unsafe {
+        // The root does not watch its substructure, so ask the window itself.
+        // Panels may also change their struts at any time.
+        xlib::XSelectInput(
+            _wm.display,
+            _w,
+            xlib::StructureNotifyMask | xlib::PropertyChangeMask,
+        );
     }
 
-    _wm.windows.remove(&_w);
+    _wm.unmanaged.push((_w, layer));
+    update_strut(_wm, _w);
+
+    if layer == Layer::Desktop {
+        fit_desktop(_wm, _w);
+    }
 }
 
 /**
- * Creates a window frame
+ * Stretches a desktop window, like the one of a file manager showing icons
+ * on the background, over the monitors it is on. A window spanning the
+ * whole screen keeps covering every monitor.
  */
-fn create_window_frame(_wm: &mut WindowManager, _w: xlib::Window, early: bool) {
-    unsafe {
-        let mut attrs: xlib::XWindowAttributes = uninitialized();
-
-        xlib::XGetWindowAttributes(_wm.display, _w, &mut attrs);
-
-        if early && (attrs.override_redirect > 0 || attrs.map_state != xlib::IsViewable) {
-            return;
-        }
+fn fit_desktop(_wm: &WindowManager, _w: xlib::Window) {
+    let (position, size) = get_geometry(_wm, _w);
+    let end = position + size.as_i32s();
 
-        let screen = xlib::XDefaultScreen(_wm.display);
-        let visual = xlib::XDefaultVisual(_wm.display, screen);
-        let depth = xlib::XDefaultDepth(_wm.display, screen);
-
-        let mut attributes: xlib::XSetWindowAttributes = uninitialized();
-        attributes.background_pixel = 0; //xlib::XBlackPixel(_wm.display, screen);
-        attributes.border_pixel = 0; //xlib::XBlackPixel(_wm.display, screen);
-        attributes.event_mask =
-            xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask | xlib::ExposureMask;
+    let mut covered = _wm.monitors.iter().filter(|monitor| {
+        let monitor_end = monitor.position + monitor.size.as_i32s();
+        position.x < monitor_end.x
+            && monitor.position.x < end.x
+            && position.y < monitor_end.y
+            && monitor.position.y < end.y
+    });
 
-        let frame = xlib::XCreateWindow(
-            _wm.display,
-            _wm.root,
-            attrs.x,
-            attrs.y,
-            (attrs.width + (DECORATION_PADDING * 2)) as u32,
-            (attrs.height + (DECORATION_PADDING * 2)) as u32,
-            0,
-            depth,
-            xlib::InputOutput as u32,
-            visual,
-            xlib::CWBorderPixel | xlib::CWEventMask, /* | xlib::CWBackPixel */
-            &mut attributes,
+    let first = match covered.next() {
+        Some(monitor) => monitor,
+        None => &_wm.monitors[monitor::index_at(&_wm.monitors, position)],
+    };
+    let (mut start, mut stop) = (first.position, first.position + first.size.as_i32s());
+    for monitor in covered {
+        let monitor_end = monitor.position + monitor.size.as_i32s();
+        start = Vector2D::new(
+            min(start.x, monitor.position.x),
+            min(start.y, monitor.position.y),
         );
+        stop = Vector2D::new(max(stop.x, monitor_end.x), max(stop.y, monitor_end.y));
+    }
 
-        bind_window_button(_wm, _w, xlib::Button1, xlib::Mod1Mask, XC_CROSSHAIR);
-        bind_window_button(_wm, _w, xlib::Button3, xlib::Mod1Mask, XC_FLEUR);
-        bind_window_key(_wm, _w, keysym::XK_F4, xlib::Mod1Mask);
-        bind_window_key(_wm, _w, keysym::XK_Tab, xlib::Mod1Mask);
-
-        xlib::XAddToSaveSet(_wm.display, _w);
+    if (start, stop) == (position, end) {
+        return;
+    }
 
-        xlib::XReparentWindow(
+    debug!("Fitting desktop {} to {:?} {:?}", _w, start, stop - start);
+    unsafe {
+        xlib::XMoveResizeWindow(
             _wm.display,
             _w,
-            frame,
-            DECORATION_PADDING,
-            DECORATION_PADDING,
-        );
-
-        xlib::XMapWindow(_wm.display, frame);
-
-        let surface = cairo_sys::cairo_xlib_surface_create(
-            _wm.display,
-            frame,
-            visual,
-            attrs.width + (DECORATION_PADDING * 2),
-            attrs.height + (DECORATION_PADDING * 2),
+            start.x,
+            start.y,
+            (stop.x - start.x) as u32,
+            (stop.y - start.y) as u32,
         );
-
-        let context = cairo_sys::cairo_create(surface);
-
-        let _win = Window {
-            frame: frame,
-            decoration_surface: surface,
-            decoration_context: context,
-            drag_start: Vector2D::new(0, 0),
-            drag_start_size: Vector2D::new(0, 0),
-        };
-
-        _wm.windows.insert(_w, _win);
     }
 }
 
 /**
- * Handle reparent notification event
+ * Fits every desktop window to the monitors again, after they changed
  */
-fn on_reparent_notify(_wm: &WindowManager, _e: xlib::XReparentEvent) {
-    // Ignore for now
+fn fit_desktops(_wm: &WindowManager) {
+    for (_w, layer) in _wm.unmanaged.iter() {
+        if *layer == Layer::Desktop {
+            fit_desktop(_wm, *_w);
+        }
+    }
 }
 
 /**
- * Handle unmap notification event
+ * Forgets a window shown without a frame once it goes away
  */
-fn on_unmap_notify(_wm: &mut WindowManager, _e: xlib::XUnmapEvent) {
-    if !_wm.windows.contains_key(&_e.window) {
-        warn!("Ignoring UnmapNotify for {}", _e.window);
-        return;
-    }
-
-    if _e.event == _wm.root {
-        debug!("Ignoring UnmapNotify for root");
-        return;
-    }
-
-    let win = _wm.windows.get(&_e.window).unwrap();
+fn remove_unmanaged(_wm: &mut WindowManager, _w: xlib::Window) -> bool {
+    let count = _wm.unmanaged.len();
+    _wm.unmanaged.retain(|(w, _)| *w != _w);
 
-    unsafe {
-        // FIXME This triggers an error
-        xlib::XUnmapWindow(_wm.display, win.frame);
-        xlib::XReparentWindow(_wm.display, _e.window, _wm.root, 0, 0);
-        xlib::XRemoveFromSaveSet(_wm.display, _e.window);
-        xlib::XDestroyWindow(_wm.display, win.frame);
+    if _wm.struts.remove(&_w).is_some() {
+        publish_workarea(_wm);
+        layout::schedule_all(_wm);
     }
 
-    remove_window_frame(_wm, _e.window);
+    _wm.unmanaged.len() != count
 }
 
 /**
- * Handle map notification event
+ * Works out where the frame of a client goes. Adopted clients keep their
+ * place on screen, with the frame growing around them, as long as the
+ * titlebar stays in the work area of the monitor.
  */
-fn on_map_notify(_wm: &WindowManager, _e: xlib::XMapEvent) {
-    // Ignore for now
+fn place_frame(_wm: &WindowManager, candidate: &Candidate, adopted: bool) -> Vector2D<i32> {
+    let ext = frame_extents(
+        decoration::metrics(&_wm.config),
+        candidate.decorated,
+        get_gtk_extents(_wm, candidate.window),
+    );
+    let client = Vector2D::new(candidate.attrs.x, candidate.attrs.y);
+    let (origin, _) = work_area(_wm, monitor::index_at(&_wm.monitors, client));
+    if !adopted {
+        // New windows keep the position they asked for, unless it puts
+        // the titlebar under a panel
+        return Vector2D::new(max(origin.x, client.x), max(origin.y, client.y));
+    }
+
+    Vector2D::new(
+        max(origin.x, client.x - ext.left),
+        max(origin.y, client.y - ext.top),
+    )
 }
 
 /**
- * Handle map request event
+ * Binds a input button to a window
  */
-fn on_map_request(_wm: &mut WindowManager, _e: xlib::XMapRequestEvent) {
-    create_window_frame(_wm, _e.window, false);
-
+fn bind_window_button(_wm: &WindowManager, _w: xlib::Window, _b: u32, _m: u32, _c: u32) {
     unsafe {
-        xlib::XMapWindow(_wm.display, _e.window);
+        xlib::XGrabButton(
+            _wm.display,
+            _b,
+            _m,
+            _w,
+            0,
+            xlib::ButtonPressMask as u32
+                | xlib::ButtonReleaseMask as u32
+                | xlib::ButtonMotionMask as u32,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            0,
+            xlib::XCreateFontCursor(_wm.display, _c),
+        );
     }
 }
 
 /**
- * Handle motion notification event
+ * Catches plain clicks on a client so it can be focused, the click is
+ * replayed to the client afterwards
  */
-fn on_motion_notify(_wm: &WindowManager, _e: xlib::XMotionEvent) {
+fn bind_focus_click(_wm: &WindowManager, _w: xlib::Window) {
+    unsafe {
+        xlib::XGrabButton(
+            _wm.display,
+            xlib::AnyButton as u32,
+            0,
+            _w,
+            0,
+            xlib::ButtonPressMask as u32,
+            xlib::GrabModeSync,
+            xlib::GrabModeAsync,
+            0,
+            0,
+        );
+    }
+}
+
+/**
+ * Binds a input key to a window
+ */
+fn bind_window_key(_wm: &WindowManager, _w: xlib::Window, _k: u32, _m: u32) {
+    unsafe {
+        xlib::XGrabKey(
+            _wm.display,
+            xlib::XKeysymToKeycode(_wm.display, _k as u64) as i32,
+            _m,
+            _w,
+            0,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+        );
+    }
+}
+
+/**
+ * Grabs the window manager shortcuts on a client
+ */
+fn bind_shortcuts(_wm: &WindowManager, _w: xlib::Window) {
+    bind_window_key(_wm, _w, keysym::XK_F4, xlib::Mod1Mask);
+    bind_window_key(_wm, _w, keysym::XK_F10, xlib::Mod1Mask);
+    bind_window_key(_wm, _w, keysym::XK_Tab, xlib::Mod1Mask);
+    bind_window_key(_wm, _w, keysym::XK_space, xlib::Mod1Mask);
+}
+
+/**
+ * Releases the window manager shortcuts on a client
+ */
+fn unbind_shortcuts(_wm: &WindowManager, _w: xlib::Window) {
+    unsafe {
+        xlib::XUngrabKey(_wm.display, xlib::AnyKey, xlib::AnyModifier, _w);
+    }
+}
+
+/**
+ * Grabs or releases the shortcuts bound on the root window, which would
+ * otherwise win over a client that wants every key for itself
+ */
+fn inhibit_root_shortcuts(_wm: &mut WindowManager, inhibit: bool) {
+    if _wm.shortcuts_inhibited == inhibit {
+        return;
+    }
+
+    _wm.shortcuts_inhibited = inhibit;
+    debug!(
+        "Shortcuts {}",
+        if inhibit { "inhibited" } else { "restored" }
+    );
+
+    grab_root_shortcuts(_wm.display, _wm.root, !inhibit);
+}
+
+/**
+ * Grabs or releases the shortcuts on the root window
+ */
+fn grab_root_shortcuts(display: *mut xlib::Display, root: xlib::Window, grab: bool) {
+    for (sym, modifiers) in ROOT_SHORTCUTS.iter() {
+        unsafe {
+            let keycode = xlib::XKeysymToKeycode(display, *sym as u64) as i32;
+            if grab {
+                xlib::XGrabKey(
+                    display,
+                    keycode,
+                    *modifiers,
+                    root,
+                    0,
+                    xlib::GrabModeAsync,
+                    xlib::GrabModeAsync,
+                );
+            } else {
+                xlib::XUngrabKey(display, keycode, *modifiers, root);
+            }
+        }
+    }
+
+    workspaces::grab_keys(display, root, grab);
+}
+
+/**
+ * Lets a client receive the window manager shortcuts, for virtual machine
+ * viewers and remote desktops
+ */
+fn set_passthrough(_wm: &mut WindowManager, _w: xlib::Window, enable: bool) {
+    match _wm.windows.get_mut(&_w) {
+        Some(win) if win.passthrough != enable => win.passthrough = enable,
+        _ => return,
+    }
+
+    if enable {
+        unbind_shortcuts(_wm, _w);
+    } else {
+        bind_shortcuts(_wm, _w);
+    }
+
+    if _wm.focused == Some(_w) {
+        inhibit_root_shortcuts(_wm, enable);
+    }
+}
+
+/**
+ * Lets clicks on a window go through to whatever is below it, for overlays
+ * like timers and subtitles. The window menu can still be opened on it from
+ * the keyboard to turn this off.
+ */
+fn set_click_through(_wm: &mut WindowManager, _w: xlib::Window, enable: bool) {
+    let frame = match _wm.windows.get_mut(&_w) {
+        Some(win) if win.click_through != enable && _wm.shape_event.is_some() => {
+            win.click_through = enable;
+            win.frame
+        }
+        _ => return,
+    };
+
+    debug!("Click-through of {} is now {}", _w, enable);
+    shape::set_click_through(_wm.display, frame, enable);
+}
+
+/**
+ * Reads the instance and class names from WM_CLASS
+ */
+fn get_class(_wm: &WindowManager, _w: xlib::Window) -> Vec<String> {
+    let mut names = Vec::new();
+
+    unsafe {
+        let mut hint: xlib::XClassHint = zeroed();
+        if xlib::XGetClassHint(_wm.display, _w, &mut hint) == 0 {
+            return names;
+        }
+
+        for name in &[hint.res_name, hint.res_class] {
+            if !name.is_null() {
+                names.push(CStr::from_ptr(*name).to_string_lossy().into_owned());
+                xlib::XFree(*name as *mut _);
+            }
+        }
+    }
+
+    names
+}
+
+/**
+ * Reads a property holding a list of atoms
+ */
+fn get_atom_list(_wm: &WindowManager, _w: xlib::Window, property: xlib::Atom) -> Vec<xlib::Atom> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+    let mut result = Vec::new();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            _wm.display,
+            _w,
+            property,
+            0,
+            1024,
+            xlib::False,
+            xlib::XA_ATOM,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return result;
+        }
+
+        if actual_format == 32 {
+            let atoms = data as *const xlib::Atom;
+            for _i in 0..count {
+                result.push(*atoms.offset(_i as isize));
+            }
+        }
+
+        xlib::XFree(data as *mut c_void);
+    }
+
+    result
+}
+
+/**
+ * Replaces a property holding a list of atoms
+ */
+fn set_atom_list(
+    _wm: &WindowManager,
+    _w: xlib::Window,
+    property: xlib::Atom,
+    atoms: &[xlib::Atom],
+) {
+    unsafe {
+        xlib::XChangeProperty(
+            _wm.display,
+            _w,
+            property,
+            xlib::XA_ATOM,
+            32,
+            xlib::PropModeReplace,
+            atoms.as_ptr() as *const u8,
+            atoms.len() as i32,
+        );
+    }
+}
+
+/**
+ * Replaces a property holding a list of windows
+ */
+fn set_window_list(
+    _wm: &WindowManager,
+    _w: xlib::Window,
+    property: xlib::Atom,
+    windows: &[xlib::Window],
+) {
+    unsafe {
+        xlib::XChangeProperty(
+            _wm.display,
+            _w,
+            property,
+            xlib::XA_WINDOW,
+            32,
+            xlib::PropModeReplace,
+            windows.as_ptr() as *const u8,
+            windows.len() as i32,
+        );
+    }
+}
+
+/**
+ * Reads a property holding a list of 32 bit values, whatever its type
+ */
+fn get_cardinal_list(_wm: &WindowManager, _w: xlib::Window, property: xlib::Atom) -> Vec<i64> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+    let mut result = Vec::new();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            _wm.display,
+            _w,
+            property,
+            0,
+            1024,
+            xlib::False,
+            xlib::AnyPropertyType as u64,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return result;
+        }
+
+        // Xlib hands out 32 bit items as longs
+        if actual_format == 32 {
+            let values = data as *const i64;
+            for _i in 0..count {
+                result.push(*values.offset(_i as isize));
+            }
+        }
+
+        xlib::XFree(data as *mut c_void);
+    }
+
+    result
+}
+
+/**
+ * Sets the ICCCM state of a client, which has no icon window
+ */
+fn set_wm_state(_wm: &WindowManager, _w: xlib::Window, state: i64) {
+    let values: [i64; 2] = [state, 0];
+
+    unsafe {
+        xlib::XChangeProperty(
+            _wm.display,
+            _w,
+            _wm.atoms.wm_state,
+            _wm.atoms.wm_state,
+            32,
+            xlib::PropModeReplace,
+            values.as_ptr() as *const u8,
+            values.len() as i32,
+        );
+    }
+}
+
+/**
+ * Replaces a property holding a list of cardinals
+ */
+fn set_cardinal_list(_wm: &WindowManager, _w: xlib::Window, property: xlib::Atom, values: &[i64]) {
+    unsafe {
+        xlib::XChangeProperty(
+            _wm.display,
+            _w,
+            property,
+            xlib::XA_CARDINAL,
+            32,
+            xlib::PropModeReplace,
+            values.as_ptr() as *const u8,
+            values.len() as i32,
+        );
+    }
+}
+
+/**
+ * Replaces a property holding UTF-8 text
+ */
+fn set_string_property(_wm: &WindowManager, _w: xlib::Window, property: xlib::Atom, value: &str) {
+    unsafe {
+        xlib::XChangeProperty(
+            _wm.display,
+            _w,
+            property,
+            _wm.atoms.utf8_string,
+            8,
+            xlib::PropModeReplace,
+            value.as_ptr(),
+            value.len() as i32,
+        );
+    }
+}
+
+/**
+ * Reads a text property as UTF-8
+ */
+fn get_string_property(
+    _wm: &WindowManager,
+    _w: xlib::Window,
+    property: xlib::Atom,
+) -> Option<String> {
+    let mut actual_type: xlib::Atom = 0;
+    let mut actual_format: i32 = 0;
+    let mut count: u64 = 0;
+    let mut remaining: u64 = 0;
+    let mut data: *mut u8 = ptr::null_mut();
+
+    unsafe {
+        let status = xlib::XGetWindowProperty(
+            _wm.display,
+            _w,
+            property,
+            0,
+            1024,
+            xlib::False,
+            xlib::AnyPropertyType as u64,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut remaining,
+            &mut data,
+        );
+
+        if status != xlib::Success as i32 || data.is_null() {
+            return None;
+        }
+
+        let result = if actual_format == 8 {
+            let bytes = std::slice::from_raw_parts(data, count as usize);
+            Some(String::from_utf8_lossy(bytes).into_owned())
+        } else {
+            None
+        };
+
+        xlib::XFree(data as *mut c_void);
+        result
+    }
+}
+
+/**
+ * Works out which layer a window belongs in from its type and state
+ */
+fn window_layer(_wm: &WindowManager, types: &[xlib::Atom], states: &[xlib::Atom]) -> Layer {
+    let atoms = &_wm.atoms;
+    let has_type = |atom: xlib::Atom| types.contains(&atom);
+    let has_state = |atom: xlib::Atom| states.contains(&atom);
+
+    if has_type(atoms.net_wm_window_type_desktop) {
+        Layer::Desktop
+    } else if has_type(atoms.net_wm_window_type_dock) {
+        Layer::Dock
+    } else if has_type(atoms.net_wm_window_type_dropdown_menu)
+        || has_type(atoms.net_wm_window_type_popup_menu)
+        || has_type(atoms.net_wm_window_type_tooltip)
+        || has_type(atoms.net_wm_window_type_notification)
+        || has_type(atoms.net_wm_window_type_combo)
+        || has_type(atoms.net_wm_window_type_dnd)
+    {
+        Layer::Popup
+    } else if has_type(atoms.net_wm_window_type_splash) {
+        Layer::Above
+    } else if has_state(atoms.net_wm_state_fullscreen) {
+        Layer::Fullscreen
+    } else if has_state(atoms.net_wm_state_above) {
+        Layer::Above
+    } else if has_state(atoms.net_wm_state_below) {
+        Layer::Below
+    } else {
+        Layer::Normal
+    }
+}
+
+/**
+ * Publishes the desktop size and viewport on the root window
+ */
+fn publish_desktop_geometry(_wm: &WindowManager) {
+    let (width, height) = unsafe {
+        let mut attrs: xlib::XWindowAttributes = zeroed();
+        xlib::XGetWindowAttributes(_wm.display, _wm.root, &mut attrs);
+        (attrs.width as i64, attrs.height as i64)
+    };
+
+    // There is no large desktop support, so the viewport never moves
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_desktop_geometry,
+        &[width, height],
+    );
+    set_cardinal_list(_wm, _wm.root, _wm.atoms.net_desktop_viewport, &[0, 0]);
+}
+
+/**
+ * Names a window of the window manager itself through WM_NAME and WM_CLASS,
+ * so that xprop and xwininfo show what it is and compositors can match it
+ */
+fn name_window(display: *mut xlib::Display, _w: xlib::Window, name: &str) {
+    let name = CString::new(name).unwrap();
+    let class = CString::new("Gothite").unwrap();
+
+    unsafe {
+        xlib::XStoreName(display, _w, name.as_ptr());
+
+        let mut hint = xlib::XClassHint {
+            res_name: name.as_ptr() as *mut c_char,
+            res_class: class.as_ptr() as *mut c_char,
+        };
+        xlib::XSetClassHint(display, _w, &mut hint);
+    }
+}
+
+/**
+ * Announces the window manager to clients through _NET_SUPPORTED and
+ * a _NET_SUPPORTING_WM_CHECK child window
+ */
+fn publish_wm_check(_wm: &WindowManager) {
+    let check = unsafe { xlib::XCreateSimpleWindow(_wm.display, _wm.root, -1, -1, 1, 1, 0, 0, 0) };
+
+    set_atom_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_supported,
+        &_wm.atoms.supported(),
+    );
+
+    for _w in &[_wm.root, check] {
+        set_window_list(_wm, *_w, _wm.atoms.net_supporting_wm_check, &[check]);
+    }
+
+    set_string_property(_wm, check, _wm.atoms.net_wm_name, "gothite");
+    name_window(_wm.display, check, "gothite-check");
+}
+
+/**
+ * Shows the busy cursor on the root while applications are launching
+ */
+fn update_root_cursor(_wm: &WindowManager) {
+    let shape = if _wm.launches.is_empty() {
+        XC_ARROW
+    } else {
+        XC_WATCH
+    };
+
+    unsafe {
+        let cursor = xlib::XCreateFontCursor(_wm.display, shape);
+        xlib::XDefineCursor(_wm.display, _wm.root, cursor);
+        xlib::XFreeCursor(_wm.display, cursor);
+    }
+}
+
+/**
+ * Stops waiting for a launched application
+ */
+fn finish_launch(_wm: &mut WindowManager, id: &str) {
+    if _wm.launches.remove(id).is_some() {
+        debug!("Launch {} finished", id);
+        _wm.timers.cancel(&Timer::StartupTimeout(id.to_string()));
+        update_root_cursor(_wm);
+    }
+}
+
+/**
+ * Gets the position and size of a window
+ */
+fn get_geometry(_wm: &WindowManager, _w: xlib::Window) -> (Vector2D<i32>, Vector2D<u32>) {
+    let mut x: i32 = 0;
+    let mut y: i32 = 0;
+    let mut w: u32 = 0;
+    let mut h: u32 = 0;
+    let mut border: u32 = 0;
+    let mut depth: u32 = 0;
+
+    unsafe {
+        let mut root: xlib::Window = 0;
+        xlib::XGetGeometry(
+            _wm.display,
+            _w,
+            &mut root,
+            &mut x,
+            &mut y,
+            &mut w,
+            &mut h,
+            &mut border,
+            &mut depth,
+        );
+    }
+
+    (Vector2D::new(x, y), Vector2D::new(w, h))
+}
+
+/**
+ * Gets the size of the frame around a client of the given size
+ */
+fn frame_size(ext: Extents, size: Vector2D<u32>) -> Vector2D<u32> {
+    Vector2D::new(
+        (size.x as i32).saturating_add(ext.left + ext.right).max(1) as u32,
+        (size.y as i32).saturating_add(ext.top + ext.bottom).max(1) as u32,
+    )
+}
+
+/**
+ * Gets the size of the client inside a frame of the given size
+ */
+fn client_size(ext: Extents, size: Vector2D<u32>) -> Vector2D<u32> {
+    Vector2D::new(
+        (size.x as i32).saturating_sub(ext.left + ext.right).max(1) as u32,
+        (size.y as i32).saturating_sub(ext.top + ext.bottom).max(1) as u32,
+    )
+}
+
+/**
+ * Resizes a client together with its frame
+ */
+fn resize_client(_wm: &WindowManager, _w: xlib::Window, _win: &Window, size: Vector2D<u32>) {
+    let size = Vector2D::new(
+        max(_wm.config.min_width, size.x),
+        max(_wm.config.min_height, size.y),
+    );
+    let outer = frame_size(extents(_win), size);
+
+    unsafe {
+        xlib::XResizeWindow(_wm.display, _win.frame, outer.x, outer.y);
+        xlib::XResizeWindow(_wm.display, _w, size.x, size.y);
+
+        cairo_sys::cairo_xlib_surface_set_size(
+            _win.decoration_surface,
+            outer.x as i32,
+            outer.y as i32,
+        );
+    }
+
+    if _win.shaped {
+        shape_frame(_wm, _w);
+    }
+    publish_opaque_region(_wm, _win, outer);
+}
+
+/**
+ * Shapes the frame of a shaped client to match it, so only the titlebar is
+ * added around it. Fullscreen frames take the shape of the client alone.
+ */
+fn shape_frame(_wm: &WindowManager, _w: xlib::Window) {
+    let win = match _wm.windows.get(&_w) {
+        Some(win) if _wm.shape_event.is_some() => win,
+        _ => return,
+    };
+
+    if !win.shaped {
+        shape::clear(_wm.display, win.frame);
+        return;
+    }
+
+    let (_, size) = get_geometry(_wm, win.frame);
+    let ext = extents(win);
+    if win.fullscreen || !win.decorated {
+        shape::copy(_wm.display, win.frame, _w, (0, 0), (0, 0));
+    } else {
+        shape::copy(
+            _wm.display,
+            win.frame,
+            _w,
+            (ext.left, ext.top),
+            (size.x, ext.top as u32),
+        );
+    }
+}
+
+/**
+ * Resizes a window by dragging one or two of its edges
+ */
+fn resize_window(
+    _wm: &WindowManager,
+    _w: xlib::Window,
+    _win: &Window,
+    region: Region,
+    delta: Vector2D<i32>,
+) {
+    let (left, right, top, bottom) = region.edges();
+    let dx = if left {
+        -delta.x
+    } else if right {
+        delta.x
+    } else {
+        0
+    };
+    let dy = if top {
+        -delta.y
+    } else if bottom {
+        delta.y
+    } else {
+        0
+    };
+
+    // The drag started from the frame size, so take the decoration back off
+    let ext = extents(_win);
+    let decoration = Vector2D::new(ext.left + ext.right, ext.top + ext.bottom);
+    let start = _win.drag_start_size.as_i32s();
+    let client = Vector2D::new(
+        max(_wm.config.min_width as i32, start.x + dx - decoration.x),
+        max(_wm.config.min_height as i32, start.y + dy - decoration.y),
+    );
+
+    // Terminals and the like only take sizes in whole cells
+    let client = _win.size_hints.constrain(client.as_u32s()).as_i32s();
+
+    resize_client(_wm, _w, _win, client.as_u32s());
+
+    // Keep the opposite edge in place when dragging the left or top edge
+    if left || top {
+        let outer = client + decoration;
+        let x = if left {
+            _win.drag_start.x + start.x - outer.x
+        } else {
+            _win.drag_start.x
+        };
+        let y = if top {
+            _win.drag_start.y + start.y - outer.y
+        } else {
+            _win.drag_start.y
+        };
+
+        unsafe {
+            xlib::XMoveWindow(_wm.display, _win.frame, x, y);
+        }
+    }
+}
+
+/**
+ * Moves a window
+ */
+fn move_window(_wm: &WindowManager, _w: xlib::Window, _win: &Window, delta: Vector2D<i32>) {
+    let new_position = _win.drag_start + delta;
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, _win.frame, new_position.x, new_position.y);
+    }
+}
+
+/**
+ * Hides a window until it is picked from the switcher
+ */
+fn minimize_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    if let Some(win) = _wm.windows.get_mut(&_w) {
+        win.minimized = true;
+        win.hovered = None;
+
+        unsafe {
+            xlib::XUnmapWindow(_wm.display, win.frame);
+        }
+    }
+
+    set_wm_state(_wm, _w, ICONIC_STATE);
+    bar::update(_wm);
+    unfocus_window(_wm, _w);
+    workspaces::update_names(_wm);
+    layout::schedule(_wm, workspaces::of(_wm, _w));
+}
+
+/**
+ * Brings back a minimized window
+ */
+fn restore_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    if let Some(win) = _wm.windows.get_mut(&_w) {
+        if win.minimized {
+            win.minimized = false;
+
+            unsafe {
+                // Iconic clients adopted at startup are unmapped themselves
+                xlib::XMapWindow(_wm.display, _w);
+                xlib::XMapWindow(_wm.display, win.frame);
+            }
+
+            set_wm_state(_wm, _w, NORMAL_STATE);
+        }
+    }
+
+    workspaces::update_names(_wm);
+    layout::schedule(_wm, workspaces::of(_wm, _w));
+}
+
+/**
+ * Hides every window on the current workspace to uncover the desktop, or
+ * brings back the ones that were hidden that way
+ */
+fn set_showing_desktop(_wm: &mut WindowManager, enable: bool) {
+    if _wm.showing_desktop == enable {
+        return;
+    }
+
+    debug!("{} the desktop", if enable { "Showing" } else { "Hiding" });
+    _wm.showing_desktop = enable;
+
+    let current = _wm.workspaces[_wm.workspace].tree.clients();
+    for (w, win) in _wm.windows.iter_mut() {
+        let here = current.contains(w);
+        if enable && here && !win.minimized {
+            win.hidden = true;
+            win.hovered = None;
+
+            unsafe {
+                xlib::XUnmapWindow(_wm.display, win.frame);
+            }
+        } else if !enable && win.hidden {
+            win.hidden = false;
+
+            unsafe {
+                if here && !win.minimized && !win.covered {
+                    xlib::XMapWindow(_wm.display, win.frame);
+                }
+            }
+        }
+    }
+
+    if enable {
+        focus_window(_wm, None);
+    }
+    bar::update(_wm);
+
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_showing_desktop,
+        &[enable as i64],
+    );
+}
+
+/**
+ * Handle a pager or taskbar asking to show or hide the desktop
+ */
+fn on_showing_desktop_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    set_showing_desktop(_wm, _e.data.get_long(0) != 0);
+}
+
+/**
+ * Handle a client asking to be iconified through WM_CHANGE_STATE
+ */
+fn on_change_state_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    if _e.data.get_long(0) == ICONIC_STATE && _wm.windows.contains_key(&_e.window) {
+        debug!("{} asked to be iconified", _e.window);
+        minimize_window(_wm, _e.window);
+    }
+}
+
+/**
+ * Tells a client how much the frame adds around it. Sides where the frame
+ * clips an invisible margin add nothing.
+ */
+fn publish_frame_extents(_wm: &WindowManager, _w: xlib::Window, extents: Extents) {
+    let values = [
+        max(extents.left, 0) as i64,
+        max(extents.right, 0) as i64,
+        max(extents.top, 0) as i64,
+        max(extents.bottom, 0) as i64,
+    ];
+    set_cardinal_list(_wm, _w, _wm.atoms.net_frame_extents, &values);
+
+    // Screenshot tools that pick the frame find the decoration there too
+    if let Some(win) = _wm.windows.get(&_w) {
+        set_cardinal_list(_wm, win.frame, _wm.atoms.net_frame_extents, &values);
+    }
+}
+
+/**
+ * Tells compositors that a frame of the given size hides what is below it.
+ * Frames have no alpha channel, so all of it is opaque unless it is shaped.
+ */
+fn publish_opaque_region(_wm: &WindowManager, _win: &Window, size: Vector2D<u32>) {
+    if _win.shaped {
+        unsafe {
+            xlib::XDeleteProperty(_wm.display, _win.frame, _wm.atoms.net_wm_opaque_region);
+        }
+        return;
+    }
+
+    set_cardinal_list(
+        _wm,
+        _win.frame,
+        _wm.atoms.net_wm_opaque_region,
+        &[0, 0, size.x as i64, size.y as i64],
+    );
+}
+
+/**
+ * Handle a client asking for its frame extents before it is mapped
+ */
+fn on_request_frame_extents_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    let types = get_atom_list(_wm, _e.window, _wm.atoms.net_wm_window_type);
+    let decorated = should_frame(_wm, &types) && wants_decoration(_wm, _e.window);
+    let extents = frame_extents(
+        decoration::metrics(&_wm.config),
+        decorated,
+        get_gtk_extents(_wm, _e.window),
+    );
+
+    publish_frame_extents(_wm, _e.window, extents);
+}
+
+/**
+ * Publishes the workspace a client is on
+ */
+fn publish_window_desktop(_wm: &WindowManager, _w: xlib::Window) {
+    if _wm.windows.contains_key(&_w) {
+        let workspace = workspaces::of(_wm, _w);
+        set_cardinal_list(_wm, _w, _wm.atoms.net_wm_desktop, &[workspace as i64]);
+    }
+}
+
+/**
+ * Moves a window to another monitor, keeping its place relative to the monitor
+ */
+fn send_to_monitor(_wm: &mut WindowManager, _w: xlib::Window, index: usize) {
+    let frame = match _wm.windows.get(&_w) {
+        Some(win) => win.frame,
+        None => return,
+    };
+
+    let target = match _wm.monitors.get(index) {
+        Some(monitor) => monitor,
+        None => return,
+    };
+
+    let (position, size) = get_geometry(_wm, frame);
+    let center = position + size.as_i32s() / 2;
+    let source_index = monitor::index_at(&_wm.monitors, center);
+    let source = &_wm.monitors[source_index];
+
+    let offset = position - source.position;
+    let x = min(offset.x, target.size.x as i32 - size.x as i32).max(0);
+    let y = min(offset.y, target.size.y as i32 - size.y as i32).max(0);
+
+    unsafe {
+        xlib::XMoveWindow(
+            _wm.display,
+            frame,
+            target.position.x + x,
+            target.position.y + y,
+        );
+    }
+
+    follow_monitor(_wm, _w, source_index);
+}
+
+/**
+ * Maximizes a window again on the work area of the monitor it was moved to
+ * from another, taking its restore geometry along so that restoring it
+ * keeps it there
+ */
+fn follow_monitor(_wm: &mut WindowManager, _w: xlib::Window, source: usize) {
+    let (frame, horz, vert) = match _wm.windows.get(&_w) {
+        Some(win) if win.maximized_horz || win.maximized_vert => {
+            (win.frame, win.maximized_horz, win.maximized_vert)
+        }
+        _ => return,
+    };
+
+    let current = get_geometry(_wm, frame);
+    let target = monitor::index_at(&_wm.monitors, current.0 + current.1.as_i32s() / 2);
+    if target == source || source >= _wm.monitors.len() {
+        return;
+    }
+
+    debug!("Maximizing {} again on monitor {}", _w, target);
+    let from = _wm.monitors[source].position;
+    let (to, to_size) = (_wm.monitors[target].position, _wm.monitors[target].size);
+    let (origin, area) = work_area(_wm, target);
+
+    let win = _wm.windows.get_mut(&_w).unwrap();
+    if let Some((ref mut position, size)) = win.restore_geometry {
+        let offset = *position - from;
+        position.x = to.x + min(offset.x, to_size.x as i32 - size.x as i32).max(0);
+        position.y = to.y + min(offset.y, to_size.y as i32 - size.y as i32).max(0);
+    }
+
+    let (mut position, mut size) = current;
+    if horz {
+        position.x = origin.x;
+        size.x = area.x;
+    }
+    if vert {
+        position.y = origin.y;
+        size.y = area.y;
+    }
+
+    let win = _wm.windows.get(&_w).unwrap();
+    resize_client(_wm, _w, win, client_size(extents(win), size));
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, frame, position.x, position.y);
+    }
+}
+
+/**
+ * Area of a monitor that maximized windows may cover, which leaves out the
+ * panels along its edges
+ */
+fn work_area(_wm: &WindowManager, monitor: usize) -> (Vector2D<i32>, Vector2D<u32>) {
+    let monitor = &_wm.monitors[monitor];
+    let screen = get_geometry(_wm, _wm.root).1;
+    strut::apply(_wm.struts.values(), monitor.position, monitor.size, screen)
+}
+
+/**
+ * Publishes the part of the screen left by the panels, the same for every
+ * workspace
+ */
+fn publish_workarea(_wm: &WindowManager) {
+    let screen = get_geometry(_wm, _wm.root).1;
+    let (position, size) = strut::apply(_wm.struts.values(), Vector2D::new(0, 0), screen, screen);
+    let area = [
+        position.x as i64,
+        position.y as i64,
+        size.x as i64,
+        size.y as i64,
+    ];
+
+    let values: Vec<i64> = area
+        .iter()
+        .cycle()
+        .take(area.len() * _wm.workspaces.len())
+        .cloned()
+        .collect();
+    set_cardinal_list(_wm, _wm.root, _wm.atoms.net_workarea, &values);
+}
+
+/**
+ * Rereads the space a window reserves along the screen edges
+ */
+fn update_strut(_wm: &mut WindowManager, _w: xlib::Window) {
+    let mut values = get_cardinal_list(_wm, _w, _wm.atoms.net_wm_strut_partial);
+    if values.is_empty() {
+        values = get_cardinal_list(_wm, _w, _wm.atoms.net_wm_strut);
+    }
+
+    match Strut::parse(&values) {
+        Some(strut) => {
+            debug!("Window {} reserves {:?}", _w, strut);
+            _wm.struts.insert(_w, strut);
+        }
+        None => {
+            if _wm.struts.remove(&_w).is_none() {
+                return;
+            }
+        }
+    }
+
+    publish_workarea(_wm);
+    layout::schedule_all(_wm);
+}
+
+/**
+ * Grows a window to the work area along either axis, or puts it back where
+ * it was before it got maximized
+ */
+fn set_maximized(_wm: &mut WindowManager, _w: xlib::Window, horz: bool, vert: bool) {
+    let frame = match _wm.windows.get(&_w) {
+        Some(win)
+            if !win.fullscreen && (win.maximized_horz, win.maximized_vert) != (horz, vert) =>
+        {
+            win.frame
+        }
+        _ => return,
+    };
+
+    let current = get_geometry(_wm, frame);
+    let center = current.0 + current.1.as_i32s() / 2;
+    let (origin, area) = work_area(_wm, monitor::index_at(&_wm.monitors, center));
+
+    let win = _wm.windows.get_mut(&_w).unwrap();
+    win.maximized_horz = horz;
+    win.maximized_vert = vert;
+
+    // Each axis either spans the work area or goes back to its old geometry
+    let (mut position, mut size) = if horz || vert {
+        *win.restore_geometry.get_or_insert(current)
+    } else {
+        win.restore_geometry.take().unwrap_or(current)
+    };
+
+    if horz {
+        position.x = origin.x;
+        size.x = area.x;
+    }
+
+    if vert {
+        position.y = origin.y;
+        size.y = area.y;
+    }
+
+    let win = _wm.windows.get(&_w).unwrap();
+    resize_client(_wm, _w, win, client_size(extents(win), size));
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, frame, position.x, position.y);
+    }
+
+    let (horz_atom, vert_atom) = (
+        _wm.atoms.net_wm_state_maximized_horz,
+        _wm.atoms.net_wm_state_maximized_vert,
+    );
+    let mut states = get_atom_list(_wm, _w, _wm.atoms.net_wm_state);
+    states.retain(|a| *a != horz_atom && *a != vert_atom);
+    if horz {
+        states.push(horz_atom);
+    }
+    if vert {
+        states.push(vert_atom);
+    }
+    set_atom_list(_wm, _w, _wm.atoms.net_wm_state, &states);
+    layout::schedule(_wm, workspaces::of(_wm, _w));
+}
+
+/**
+ * Adds or takes away the decoration of a window, keeping its client where
+ * it is on screen
+ */
+fn set_decorated(_wm: &mut WindowManager, _w: xlib::Window, enable: bool) {
+    let win = match _wm.windows.get_mut(&_w) {
+        Some(win) if win.decorated != enable => win,
+        _ => return,
+    };
+
+    debug!("Decoration of {} is now {}", _w, enable);
+    let before = extents(win);
+    win.decorated = enable;
+    let after = extents(win);
+
+    // Fullscreen windows get the new extents once they leave fullscreen
+    if win.fullscreen {
+        return;
+    }
+
+    let win = &_wm.windows[&_w];
+    let (position, size) = get_geometry(_wm, win.frame);
+    let client = client_size(before, size);
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, _w, after.left, after.top);
+        xlib::XMoveWindow(
+            _wm.display,
+            win.frame,
+            position.x + before.left - after.left,
+            position.y + before.top - after.top,
+        );
+    }
+
+    resize_client(_wm, _w, win, client);
+    publish_frame_extents(_wm, _w, after);
+    shape_frame(_wm, _w);
+}
+
+/**
+ * Takes the new invisible margin of a client-side decorated window into
+ * account, keeping its frame where it is on screen
+ */
+fn set_gtk_extents(_wm: &mut WindowManager, _w: xlib::Window, gtk: Extents) {
+    let win = match _wm.windows.get_mut(&_w) {
+        Some(win) if win.gtk_extents != gtk => win,
+        _ => return,
+    };
+
+    debug!("Frame extents of {} changed", _w);
+    let before = extents(win);
+    win.gtk_extents = gtk;
+    refit_frame(_wm, _w, before);
+}
+
+/**
+ * Gives a window the sizes of the decoration in use, for when enlarged hit
+ * targets were turned on or off
+ */
+fn set_metrics(_wm: &mut WindowManager, _w: xlib::Window, metrics: Metrics) {
+    let win = match _wm.windows.get_mut(&_w) {
+        Some(win) if win.metrics != metrics => win,
+        _ => return,
+    };
+
+    let before = extents(win);
+    win.metrics = metrics;
+    refit_frame(_wm, _w, before);
+}
+
+/**
+ * Fits a frame to the new extents of its window, keeping the frame where it
+ * is on screen and the client at its size
+ */
+fn refit_frame(_wm: &WindowManager, _w: xlib::Window, before: Extents) {
+    let win = &_wm.windows[&_w];
+    let after = extents(win);
+    if win.fullscreen {
+        return;
+    }
+
+    let (_, size) = get_geometry(_wm, win.frame);
+    let client = client_size(before, size);
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, _w, after.left, after.top);
+    }
+
+    resize_client(_wm, _w, win, client);
+    publish_frame_extents(_wm, _w, after);
+    shape_frame(_wm, _w);
+}
+
+/**
+ * Maximizes a window in both directions, or restores it if it already is
+ */
+fn toggle_maximize(_wm: &mut WindowManager, _w: xlib::Window) {
+    let maximized = match _wm.windows.get(&_w) {
+        Some(win) => win.maximized_horz && win.maximized_vert,
+        None => return,
+    };
+
+    set_maximized(_wm, _w, !maximized, !maximized);
+}
+
+/**
+ * Makes a client cover its whole monitor without decorations, or puts it
+ * back in its frame where it was
+ */
+fn set_fullscreen(_wm: &mut WindowManager, _w: xlib::Window, enable: bool) {
+    let frame = match _wm.windows.get(&_w) {
+        Some(win) if win.fullscreen != enable => win.frame,
+        _ => return,
+    };
+
+    let (position, size) = get_geometry(_wm, frame);
+    let mut states = get_atom_list(_wm, _w, _wm.atoms.net_wm_state);
+    states.retain(|a| *a != _wm.atoms.net_wm_state_fullscreen);
+
+    if enable {
+        let ext = extents(&_wm.windows[&_w]);
+        let client = client_size(ext, size);
+
+        let win = _wm.windows.get_mut(&_w).unwrap();
+        win.fullscreen = true;
+        win.fullscreen_restore = Some((position, client));
+        win.layer = Layer::Fullscreen;
+
+        place_fullscreen(_wm, _w);
+        states.push(_wm.atoms.net_wm_state_fullscreen);
+        publish_frame_extents(_wm, _w, NO_EXTENTS);
+    } else {
+        let types = get_atom_list(_wm, _w, _wm.atoms.net_wm_window_type);
+        let layer = window_layer(_wm, &types, &states);
+        let client = size;
+
+        let win = _wm.windows.get_mut(&_w).unwrap();
+        let (position, client) = win.fullscreen_restore.take().unwrap_or((position, client));
+        win.fullscreen = false;
+        win.layer = layer;
+
+        let win = _wm.windows.get(&_w).unwrap();
+        let ext = extents(win);
+        unsafe {
+            xlib::XMoveWindow(_wm.display, _w, ext.left, ext.top);
+            xlib::XMoveWindow(_wm.display, frame, position.x, position.y);
+        }
+
+        resize_client(_wm, _w, win, client);
+        publish_frame_extents(_wm, _w, ext);
+    }
+
+    set_atom_list(_wm, _w, _wm.atoms.net_wm_state, &states);
+    restack(_wm);
+    layout::schedule(_wm, workspaces::of(_wm, _w));
+}
+
+/**
+ * Works out the area a fullscreen window covers: the monitors it asked for
+ * through _NET_WM_FULLSCREEN_MONITORS, or else the one it is on
+ */
+fn fullscreen_area(_wm: &WindowManager, _w: xlib::Window) -> (Vector2D<i32>, Vector2D<u32>) {
+    let win = _wm.windows.get(&_w).unwrap();
+    let monitors = &_wm.monitors;
+
+    if let Some([top, bottom, left, right]) = win.fullscreen_monitors {
+        if [top, bottom, left, right]
+            .iter()
+            .all(|i| *i < monitors.len())
+        {
+            let x0 = monitors[left].position.x;
+            let y0 = monitors[top].position.y;
+            let x1 = monitors[right].position.x + monitors[right].size.x as i32;
+            let y1 = monitors[bottom].position.y + monitors[bottom].size.y as i32;
+
+            if x1 > x0 && y1 > y0 {
+                return (
+                    Vector2D::new(x0, y0),
+                    Vector2D::new((x1 - x0) as u32, (y1 - y0) as u32),
+                );
+            }
+        }
+    }
+
+    let (position, size) = win
+        .fullscreen_restore
+        .unwrap_or_else(|| get_geometry(_wm, win.frame));
+    let center = position + size.as_i32s() / 2;
+    let monitor = &monitors[monitor::index_at(monitors, center)];
+    (monitor.position, monitor.size)
+}
+
+/**
+ * Moves a fullscreen window over its area
+ */
+fn place_fullscreen(_wm: &WindowManager, _w: xlib::Window) {
+    let (origin, area) = fullscreen_area(_wm, _w);
+    let win = _wm.windows.get(&_w).unwrap();
+
+    // The frame shrinks to the client, which hides the decoration
+    unsafe {
+        xlib::XMoveResizeWindow(_wm.display, win.frame, origin.x, origin.y, area.x, area.y);
+        xlib::XMoveResizeWindow(_wm.display, _w, 0, 0, area.x, area.y);
+        cairo_sys::cairo_xlib_surface_set_size(
+            win.decoration_surface,
+            area.x as i32,
+            area.y as i32,
+        );
+    }
+    publish_opaque_region(_wm, win, area);
+
+    shape_frame(_wm, _w);
+}
+
+/**
+ * Handle a client choosing the monitors its fullscreen state spans
+ */
+fn on_fullscreen_monitors_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    let monitors = [
+        _e.data.get_long(0) as usize,
+        _e.data.get_long(1) as usize,
+        _e.data.get_long(2) as usize,
+        _e.data.get_long(3) as usize,
+    ];
+
+    let fullscreen = match _wm.windows.get_mut(&_e.window) {
+        Some(win) => {
+            win.fullscreen_monitors = Some(monitors);
+            win.fullscreen
+        }
+        None => return,
+    };
+
+    let values: Vec<i64> = monitors.iter().map(|i| *i as i64).collect();
+    set_cardinal_list(
+        _wm,
+        _e.window,
+        _wm.atoms.net_wm_fullscreen_monitors,
+        &values,
+    );
+
+    if fullscreen {
+        place_fullscreen(_wm, _e.window);
+    }
+}
+
+/**
+ * Applies the stacking order to the frames, keeping every layer in place
+ */
+fn restack(_wm: &WindowManager) {
+    let order = stacking_order(_wm);
+
+    let mut layers: Vec<(Layer, xlib::Window)> = order
+        .iter()
+        .map(|w| {
+            let win = _wm.windows.get(w).unwrap();
+            (win.layer, win.frame)
+        })
+        .collect();
+
+    // Unframed windows go on top of the frames in their layer
+    layers.extend(_wm.unmanaged.iter().map(|(w, layer)| (*layer, *w)));
+    layers.sort_by_key(|(layer, _)| *layer);
+
+    // XRestackWindows expects the topmost window first
+    let mut frames: Vec<xlib::Window> = layers.iter().rev().map(|(_, w)| *w).collect();
+
+    unsafe {
+        xlib::XRestackWindows(_wm.display, frames.as_mut_ptr(), frames.len() as i32);
+    }
+
+    bar::update(_wm);
+    publish_client_list(_wm);
+}
+
+/**
+ * Lists the clients from bottom to top, as they are stacked on screen
+ */
+fn stacking_order(_wm: &WindowManager) -> Vec<xlib::Window> {
+    let mut order: Vec<xlib::Window> = _wm
+        .stack
+        .iter()
+        .cloned()
+        .filter(|w| _wm.windows.contains_key(w))
+        .collect();
+
+    // Stable, so windows keep their relative order inside a layer
+    order.sort_by_key(|w| _wm.windows.get(w).unwrap().layer);
+    order
+}
+
+/**
+ * Publishes the managed clients on the root, in mapping and in stacking order
+ */
+fn publish_client_list(_wm: &WindowManager) {
+    set_window_list(_wm, _wm.root, _wm.atoms.net_client_list, &_wm.clients);
+    set_window_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_client_list_stacking,
+        &stacking_order(_wm),
+    );
+}
+
+/**
+ * Raises a window to the top of its layer
+ */
+fn raise_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    if !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    _wm.stack.retain(|w| *w != _w);
+    _wm.stack.push(_w);
+    restack(_wm);
+}
+
+/**
+ * Lists the windows that should show up in task lists and the switcher
+ */
+fn taskbar_windows(_wm: &WindowManager) -> Vec<xlib::Window> {
+    _wm.stack
+        .iter()
+        .cloned()
+        .filter(|w| _wm.windows.get(w).is_some_and(|win| !win.skip_taskbar))
+        .collect()
+}
+
+/**
+ * Re-stacks window(s)
+ */
+fn restack_windows(_wm: &mut WindowManager, _w: xlib::Window) {
+    let next = taskbar_windows(_wm).into_iter().find(|w| *w != _w);
+
+    if let Some(next) = next {
+        activate_window(_wm, next);
+    }
+}
+
+/**
+ * Gives the input focus to a client, or back to the root, and tells
+ * everyone through _NET_ACTIVE_WINDOW
+ */
+fn focus_window(_wm: &mut WindowManager, _w: Option<xlib::Window>) {
+    let _w = _w.filter(|w| _wm.windows.contains_key(w));
+
+    // ICCCM input models: clients that set the input hint get the focus
+    // directly, clients taking part in WM_TAKE_FOCUS are asked to take it
+    // themselves and clients doing neither never get keyboard input
+    let take_focus = _w.is_some_and(|w| has_protocol(_wm, w, _wm.atoms.wm_take_focus));
+    let target = match _w {
+        Some(w) if _wm.windows[&w].accepts_input => Some(w),
+        Some(_) if take_focus => None,
+        _ => Some(xlib::PointerRoot as u64),
+    };
+
+    if let Some(target) = target {
+        unsafe {
+            xlib::XSetInputFocus(
+                _wm.display,
+                target,
+                xlib::RevertToPointerRoot,
+                xlib::CurrentTime,
+            );
+        }
+    }
+
+    if let (Some(_w), true) = (_w, take_focus) {
+        send_protocol(_wm, _w, _wm.atoms.wm_take_focus, &[_wm.event_time as i64]);
+    }
+
+    if _wm.focused != _w {
+        debug!("Focus moved to {:?}", _w);
+    }
+
+    _wm.focused = _w;
+    if let Some(_w) = _w {
+        set_demands_attention(_wm, _w, false);
+
+        let workspace = workspaces::of(_wm, _w);
+        _wm.workspace_focus.insert(workspace, _w);
+        layout::focus_changed(_wm, workspace);
+    }
+
+    let inhibit = _w.is_some_and(|w| _wm.windows[&w].passthrough);
+    inhibit_root_shortcuts(_wm, inhibit);
+
+    set_window_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_active_window,
+        &[_w.unwrap_or(0)],
+    );
+    workspaces::update_names(_wm);
+}
+
+/**
+ * Reads the input field of WM_HINTS. Clients that leave it out are given
+ * the focus, as most toolkits expect.
+ */
+fn accepts_input(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    unsafe {
+        let hints = xlib::XGetWMHints(_wm.display, _w);
+        if hints.is_null() {
+            return true;
+        }
+
+        let input = (*hints).flags & xlib::InputHint == 0 || (*hints).input != 0;
+        xlib::XFree(hints as *mut c_void);
+        input
+    }
+}
+
+/**
+ * Reads the urgency flag of WM_HINTS
+ */
+fn has_urgency_hint(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    unsafe {
+        let hints = xlib::XGetWMHints(_wm.display, _w);
+        if hints.is_null() {
+            return false;
+        }
+
+        let urgent = (*hints).flags & xlib::XUrgencyHint != 0;
+        xlib::XFree(hints as *mut c_void);
+        urgent
+    }
+}
+
+/**
+ * Checks if a window wants the attention of the user, either way a client
+ * can ask for it
+ */
+fn is_urgent(_win: &Window) -> bool {
+    _win.urgent || _win.demands_attention
+}
+
+/**
+ * Shows the urgency of a window in its decoration and the bar
+ */
+fn show_urgency(_wm: &WindowManager, _w: xlib::Window) {
+    if let Some(win) = _wm.windows.get(&_w) {
+        decoration::draw(_wm, win);
+    }
+    bar::draw(_wm);
+}
+
+/**
+ * Sets or clears _NET_WM_STATE_DEMANDS_ATTENTION on a window
+ */
+fn set_demands_attention(_wm: &mut WindowManager, _w: xlib::Window, enable: bool) {
+    match _wm.windows.get_mut(&_w) {
+        Some(win) if win.demands_attention != enable => win.demands_attention = enable,
+        _ => return,
+    }
+
+    let atom = _wm.atoms.net_wm_state_demands_attention;
+    let mut states = get_atom_list(_wm, _w, _wm.atoms.net_wm_state);
+    states.retain(|a| *a != atom);
+    if enable {
+        states.push(atom);
+    }
+    set_atom_list(_wm, _w, _wm.atoms.net_wm_state, &states);
+
+    show_urgency(_wm, _w);
+}
+
+/**
+ * Gets where the pointer is on screen
+ */
+fn pointer_position(_wm: &WindowManager) -> Vector2D<i32> {
+    query_pointer(_wm).0
+}
+
+/**
+ * Gets where the pointer is on screen, along with the state of the buttons
+ * and modifier keys
+ */
+fn query_pointer(_wm: &WindowManager) -> (Vector2D<i32>, u32) {
+    let (mut root, mut child) = (0, 0);
+    let (mut x, mut y, mut win_x, mut win_y) = (0, 0, 0, 0);
+    let mut mask = 0;
+
+    unsafe {
+        xlib::XQueryPointer(
+            _wm.display,
+            _wm.root,
+            &mut root,
+            &mut child,
+            &mut x,
+            &mut y,
+            &mut win_x,
+            &mut win_y,
+            &mut mask,
+        );
+    }
+
+    (Vector2D::new(x, y), mask)
+}
+
+/**
+ * Moves the focus to the nearest window in a direction. Past the last window
+ * of a monitor it goes on to the nearest window of the next monitor that way.
+ */
+fn focus_direction(_wm: &mut WindowManager, direction: Direction) {
+    match nearest_window(_wm, direction, |_| true) {
+        Some(_w) => {
+            debug!("Focus moved {:?} to {}", direction, _w);
+            raise_window(_wm, _w);
+            focus_window(_wm, Some(_w));
+        }
+        None => debug!("No window {:?} of the focus", direction),
+    }
+}
+
+/**
+ * Moves the focused window in a direction. A tiled window trades places
+ * with the nearest tiled window that way, and a floating one is nudged by
+ * the configured step.
+ */
+fn move_direction(_wm: &mut WindowManager, direction: Direction) {
+    let _w = match _wm.focused {
+        Some(_w) => _w,
+        None => return,
+    };
+
+    if layout::tiles(_wm, _w) {
+        match nearest_window(_wm, direction, |other| layout::tiles(_wm, other)) {
+            Some(other) => layout::swap(_wm, _w, other),
+            None => debug!("No tiled window {:?} of {}", direction, _w),
+        }
+        return;
+    }
+
+    let win = &_wm.windows[&_w];
+    if win.fullscreen {
+        return;
+    }
+
+    let step = _wm.config.key_move_step as i32;
+    let offset = match direction {
+        Direction::Left => Vector2D::new(-step, 0),
+        Direction::Right => Vector2D::new(step, 0),
+        Direction::Up => Vector2D::new(0, -step),
+        Direction::Down => Vector2D::new(0, step),
+    };
+    let position = get_geometry(_wm, win.frame).0 + offset;
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, win.frame, position.x, position.y);
+    }
+}
+
+/**
+ * Finds the nearest window in a direction from the focused one, or from the
+ * pointer without focus, among the ones shown that pass a check. Past the
+ * last window of a monitor it goes on to the nearest window of the next
+ * monitor that way.
+ */
+fn nearest_window<F>(_wm: &WindowManager, direction: Direction, check: F) -> Option<xlib::Window>
+where
+    F: Fn(xlib::Window) -> bool,
+{
+    let center = |(position, size): (Vector2D<i32>, Vector2D<u32>)| position + size.as_i32s() / 2;
+
+    let origin = match _wm.focused {
+        Some(_w) => center(get_geometry(_wm, _wm.windows[&_w].frame)),
+        None => pointer_position(_wm),
+    };
+    let current = monitor::index_at(&_wm.monitors, origin);
+    let monitor_center = |index: usize| {
+        let monitor = &_wm.monitors[index];
+        center((monitor.position, monitor.size))
+    };
+
+    _wm.stack
+        .iter()
+        .filter(|w| Some(**w) != _wm.focused && check(**w))
+        .filter_map(|w| {
+            let win = &_wm.windows[w];
+            if !_wm.workspaces[_wm.workspace].tree.contains(*w)
+                || win.minimized
+                || win.hidden
+                || win.covered
+            {
+                return None;
+            }
+
+            let position = center(get_geometry(_wm, win.frame));
+            let monitor = monitor::index_at(&_wm.monitors, position);
+
+            // Windows on the same monitor come first, then the monitors
+            // further that way from the nearest on
+            let rank = if monitor == current {
+                0
+            } else {
+                direction.distance(monitor_center(monitor) - monitor_center(current))?
+            };
+            let distance = if monitor == current {
+                direction.distance(position - origin)?
+            } else {
+                let (along, across) = direction.project(position - origin);
+                max(along, 0) as i64 + 2 * across.abs() as i64
+            };
+
+            Some(((rank, distance), *w))
+        })
+        .min_by_key(|(key, _)| *key)
+        .map(|(_, w)| w)
+}
+
+/**
+ * Activates the oldest window asking for attention, wherever it is
+ */
+fn jump_to_urgent(_wm: &mut WindowManager) {
+    let urgent = _wm
+        .clients
+        .iter()
+        .find(|w| Some(**w) != _wm.focused && is_urgent(&_wm.windows[*w]))
+        .cloned();
+
+    match urgent {
+        Some(_w) => activate_window(_wm, _w),
+        None => debug!("No window asks for attention"),
+    }
+}
+
+/**
+ * Gets the server time carried by an event, for requests that should not
+ * use CurrentTime
+ */
+fn event_time(ev: &xlib::XEvent) -> Option<xlib::Time> {
+    unsafe {
+        match ev.get_type() {
+            xlib::KeyPress | xlib::KeyRelease => Some(ev.key.time),
+            xlib::ButtonPress | xlib::ButtonRelease => Some(ev.button.time),
+            xlib::MotionNotify => Some(ev.motion.time),
+            xlib::EnterNotify | xlib::LeaveNotify => Some(ev.crossing.time),
+            xlib::PropertyNotify => Some(ev.property.time),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Drops the focus if it is on the given window, used when it goes out of sight
+ */
+fn unfocus_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    if _wm.focused == Some(_w) {
+        focus_window(_wm, None);
+    }
+}
+
+/**
+ * Brings a window into view wherever it is, raises and focuses it
+ */
+fn activate_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    if !_wm.windows.contains_key(&_w) {
+        return;
+    }
+    let workspace = workspaces::of(_wm, _w);
+
+    set_showing_desktop(_wm, false);
+    workspaces::switch(_wm, workspace);
+    restore_window(_wm, _w);
+    raise_window(_wm, _w);
+    focus_window(_wm, Some(_w));
+}
+
+/**
+ * Checks if a client takes part in a WM_PROTOCOLS protocol
+ */
+fn has_protocol(_wm: &WindowManager, _w: xlib::Window, protocol: xlib::Atom) -> bool {
+    let mut atoms: *mut xlib::Atom = ptr::null_mut();
+    let mut atom_count: i32 = 0;
+
+    unsafe {
+        if xlib::XGetWMProtocols(_wm.display, _w, &mut atoms, &mut atom_count) == 0 {
+            return false;
+        }
+
+        let found = std::slice::from_raw_parts(atoms, atom_count as usize).contains(&protocol);
+        xlib::XFree(atoms as *mut c_void);
+        found
+    }
+}
+
+/**
+ * Sends a WM_PROTOCOLS message to a client
+ */
+fn send_protocol(_wm: &WindowManager, _w: xlib::Window, protocol: xlib::Atom, data: &[i64]) {
+    let mut ev: xlib::XEvent = unsafe { zeroed() };
+
+    unsafe {
+        ev.client_message.type_ = xlib::ClientMessage;
+        ev.client_message.message_type = _wm.atoms.wm_protocols;
+        ev.client_message.window = _w;
+        ev.client_message.format = 32;
+        ev.client_message.data.set_long(0, protocol as i64);
+        for (i, value) in data.iter().enumerate() {
+            ev.client_message.data.set_long(i + 1, *value);
+        }
+
+        xlib::XSendEvent(_wm.display, _w, xlib::False, 0, &mut ev);
+    }
+}
+
+/**
+ * Kills a window, asking it nicely first when it supports that. A window
+ * that stopped answering pings is killed outright the second time.
+ */
+fn kill_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    let hung = _wm.windows.get(&_w).is_some_and(|win| win.hung);
+    if let Some(win) = _wm.windows.get_mut(&_w) {
+        win.closing = true;
+    }
+
+    if !hung && has_protocol(_wm, _w, _wm.atoms.wm_delete_window) {
+        send_protocol(
+            _wm,
+            _w,
+            _wm.atoms.wm_delete_window,
+            &[xlib::CurrentTime as i64],
+        );
+
+        // Make sure somebody is still there to handle the request
+        if has_protocol(_wm, _w, _wm.atoms.net_wm_ping) {
+            send_protocol(
+                _wm,
+                _w,
+                _wm.atoms.net_wm_ping,
+                &[xlib::CurrentTime as i64, _w as i64],
+            );
+            _wm.timers.schedule(PING_TIMEOUT, Timer::PingTimeout(_w));
+        }
+
+        debug!("Gracefully killed window");
+    } else {
+        force_kill_window(_wm, _w);
+    }
+}
+
+/**
+ * Disconnects the client owning a window, for clients that will not close
+ */
+fn force_kill_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    _wm.timers.cancel(&Timer::PingTimeout(_w));
+
+    // What was copied in the client would go with it, so keep it first
+    if _wm.config.preserve_clipboard && !clipboard::is_saving(_wm) && clipboard::owned_by(_wm, _w) {
+        clipboard::save(_wm, _w);
+        _wm.timers
+            .schedule(clipboard::TIMEOUT, Timer::ClipboardTimeout(_w));
+        return;
+    }
+
+    kill_client(_wm, _w);
+}
+
+/**
+ * Disconnects a client right away
+ */
+fn kill_client(_wm: &WindowManager, _w: xlib::Window) {
+    unsafe {
+        xlib::XKillClient(_wm.display, _w);
+    }
+
+    debug!("Killed window");
+}
+
+/**
+ * Handle a client answering a ping
+ */
+fn on_ping_reply(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    let _w = _e.data.get_long(2) as xlib::Window;
+    _wm.timers.cancel(&Timer::PingTimeout(_w));
+
+    if let Some(win) = _wm.windows.get_mut(&_w) {
+        if win.hung {
+            info!("Window {} is responding again", _w);
+            win.hung = false;
+        }
+    }
+
+    if let Some(win) = _wm.windows.get(&_w) {
+        decoration::draw(_wm, win);
+    }
+}
+
+/**
+ * Finds the client belonging to a frame
+ */
+fn frame_client(_wm: &WindowManager, frame: xlib::Window) -> Option<xlib::Window> {
+    _wm.windows
+        .iter()
+        .find(|(_, win)| win.frame == frame)
+        .map(|(w, _)| *w)
+}
+
+/**
+ * Removes a window frame
+ */
+fn remove_window_frame(_wm: &mut WindowManager, _w: xlib::Window) {
+    if !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    // Clients whose windows vanish without being closed here most likely
+    // crashed, so their place is kept for when they restart
+    if !_wm.windows[&_w].closing && !window_exists(_wm, _w) {
+        remember_lost(_wm, _w);
+    }
+
+    // Tells the client it may be mapped again, which starts over
+    set_wm_state(_wm, _w, WITHDRAWN_STATE);
+
+    let frame = _wm.windows[&_w].frame;
+    unsafe {
+        xlib::XUnmapWindow(_wm.display, frame);
+        xlib::XSelectInput(_wm.display, _w, xlib::NoEventMask);
+        xlib::XReparentWindow(_wm.display, _w, _wm.root, 0, 0);
+        xlib::XRemoveFromSaveSet(_wm.display, _w);
+    }
+
+    forget_window(_wm, _w);
+}
+
+/**
+ * Drops a managed window and gives its frame back, leaving the client alone
+ * for when it is gone or somebody else's already
+ */
+fn forget_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    let win = match _wm.windows.remove(&_w) {
+        Some(win) => win,
+        None => return,
+    };
+
+    if !win.icon.is_null() {
+        unsafe {
+            cairo_sys::cairo_surface_destroy(win.icon);
+        }
+    }
+    zoom::forget(_wm, _w);
+
+    // A drag of a window that went away would keep the grabs until the
+    // watchdog gave up on it
+    if _wm.active_window == Some(_w) {
+        end_drag(_wm);
+    }
+
+    release_frame(
+        _wm,
+        Frame {
+            window: win.frame,
+            surface: win.decoration_surface,
+            context: win.decoration_context,
+        },
+    );
+
+    _wm.clients.retain(|w| *w != _w);
+    _wm.stack.retain(|w| *w != _w);
+    _wm.workspace_focus.retain(|_, w| *w != _w);
+    let workspace = workspaces::of(_wm, _w);
+    _wm.workspaces[workspace].tree.remove(_w);
+    layout::schedule(_wm, workspace);
+    publish_client_list(_wm);
+    unfocus_window(_wm, _w);
+    workspaces::remove_empty(_wm);
+    workspaces::update_names(_wm);
+}
+
+/**
+ * Finds the parent of a window, if it still exists
+ */
+fn get_parent(_wm: &WindowManager, _w: xlib::Window) -> Option<xlib::Window> {
+    let mut root: xlib::Window = 0;
+    let mut parent: xlib::Window = 0;
+    let mut children: *mut xlib::Window = ptr::null_mut();
+    let mut count: u32 = 0;
+
+    unsafe {
+        if xlib::XQueryTree(
+            _wm.display,
+            _w,
+            &mut root,
+            &mut parent,
+            &mut children,
+            &mut count,
+        ) == 0
+        {
+            return None;
+        }
+
+        if !children.is_null() {
+            xlib::XFree(children as *mut c_void);
+        }
+    }
+
+    Some(parent)
+}
+
+/**
+ * Consistency pass over the managed windows, dropping the frames of clients
+ * that are gone or were reparented without the WM noticing
+ */
+fn collect_orphans(_wm: &mut WindowManager) {
+    let orphans: Vec<xlib::Window> = _wm
+        .windows
+        .iter()
+        .filter(|(w, win)| get_parent(_wm, **w) != Some(win.frame))
+        .map(|(w, _)| *w)
+        .collect();
+
+    for _w in orphans {
+        warn!("Dropping the frame of {}, its client is gone", _w);
+        forget_window(_wm, _w);
+    }
+}
+
+/**
+ * Checks if a window was not destroyed yet
+ */
+fn window_exists(_wm: &WindowManager, _w: xlib::Window) -> bool {
+    unsafe {
+        let mut attrs: xlib::XWindowAttributes = zeroed();
+        xlib::XGetWindowAttributes(_wm.display, _w, &mut attrs) != 0
+    }
+}
+
+/**
+ * Keeps the place of a window lost to a crash
+ */
+fn remember_lost(_wm: &mut WindowManager, _w: xlib::Window) {
+    let win = &_wm.windows[&_w];
+    if win.class.is_empty() {
+        return;
+    }
+
+    let (position, frame) = get_geometry(_wm, win.frame);
+    let ext = extents(win);
+    let size = client_size(ext, frame);
+
+    info!(
+        "Window {} of {:?} vanished, keeping its place",
+        _w, win.class
+    );
+    let lost = Lost {
+        class: win.class.clone(),
+        position,
+        size,
+        workspace: workspaces::of(_wm, _w),
+        at: Instant::now(),
+    };
+
+    _wm.lost.retain(|lost| lost.at.elapsed() < CRASH_INTERVAL);
+    _wm.lost.push(lost);
+}
+
+/**
+ * Takes the place of the most recently lost window of a class, if it was
+ * lost recently enough
+ */
+fn take_lost(_wm: &mut WindowManager, class: &[String]) -> Option<Lost> {
+    _wm.lost.retain(|lost| lost.at.elapsed() < CRASH_INTERVAL);
+
+    let index = _wm.lost.iter().rposition(|lost| lost.class == class)?;
+    Some(_wm.lost.remove(index))
+}
+
+/**
+ * Creates an unmapped frame window along with its decoration surface
+ */
+fn create_frame(_wm: &WindowManager) -> Frame {
+    unsafe {
+        let screen = xlib::XDefaultScreen(_wm.display);
+        let visual = xlib::XDefaultVisual(_wm.display, screen);
+        let depth = xlib::XDefaultDepth(_wm.display, screen);
+
+        let mut attributes: xlib::XSetWindowAttributes = zeroed();
+        attributes.border_pixel = 0;
+
+        // The client is a child of the frame from now on, so its configure and
+        // map requests, unmaps and destroys are only reported to the frame
+        attributes.event_mask = xlib::SubstructureRedirectMask
+            | xlib::SubstructureNotifyMask
+            | xlib::ExposureMask
+            | xlib::ButtonPressMask
+            | xlib::ButtonReleaseMask
+            | xlib::PointerMotionMask
+            | xlib::EnterWindowMask
+            | xlib::LeaveWindowMask;
+
+        let window = xlib::XCreateWindow(
+            _wm.display,
+            _wm.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            depth,
+            xlib::InputOutput as u32,
+            visual,
+            xlib::CWBorderPixel | xlib::CWEventMask,
+            &mut attributes,
+        );
+
+        name_window(_wm.display, window, "gothite-frame");
+
+        let surface = cairo_sys::cairo_xlib_surface_create(_wm.display, window, visual, 1, 1);
+        let context = cairo_sys::cairo_create(surface);
+
+        Frame {
+            window,
+            surface,
+            context,
+        }
+    }
+}
+
+/**
+ * Takes a frame from the pool, or creates a new one when it is empty
+ */
+fn take_frame(_wm: &mut WindowManager, position: Vector2D<i32>, size: Vector2D<u32>) -> Frame {
+    let frame = match _wm.frame_pool.pop() {
+        Some(frame) => frame,
+        None => create_frame(_wm),
+    };
+
+    unsafe {
+        xlib::XMoveResizeWindow(
+            _wm.display,
+            frame.window,
+            position.x,
+            position.y,
+            size.x,
+            size.y,
+        );
+        cairo_sys::cairo_xlib_surface_set_size(frame.surface, size.x as i32, size.y as i32);
+    }
+
+    frame
+}
+
+/**
+ * Puts an unused frame back in the pool, or destroys it when the pool is full
+ */
+fn release_frame(_wm: &mut WindowManager, frame: Frame) {
+    unsafe {
+        xlib::XUnmapWindow(_wm.display, frame.window);
+
+        // The next client may not be shaped or click-through
+        if _wm.shape_event.is_some() {
+            shape::clear(_wm.display, frame.window);
+            shape::set_click_through(_wm.display, frame.window, false);
+        }
+
+        if _wm.frame_pool.len() < FRAME_POOL_SIZE {
+            xlib::XUndefineCursor(_wm.display, frame.window);
+            _wm.frame_pool.push(frame);
+            return;
+        }
+
+        cairo_sys::cairo_destroy(frame.context);
+        cairo_sys::cairo_surface_destroy(frame.surface);
+        xlib::XDestroyWindow(_wm.display, frame.window);
+    }
+}
+
+/**
+ * Creates a window frame
+ */
+fn create_window_frame(_wm: &mut WindowManager, candidate: &Candidate, adopted: bool) {
+    let _w = candidate.window;
+    let attrs = &candidate.attrs;
+
+    // A new window ends the show desktop mode, like activating one does
+    set_showing_desktop(_wm, false);
+
+    unsafe {
+        // A client restarting after a crash gets the place of its lost window
+        let lost = if adopted {
+            None
+        } else {
+            take_lost(_wm, &candidate.class)
+        };
+
+        let client_size = lost.as_ref().map_or(
+            Vector2D::new(attrs.width as u32, attrs.height as u32),
+            |lost| lost.size,
+        );
+        let gtk_extents = get_gtk_extents(_wm, _w);
+        let metrics = decoration::metrics(&_wm.config);
+        let ext = frame_extents(metrics, candidate.decorated, gtk_extents);
+        let size = frame_size(ext, client_size);
+        let position = lost.as_ref().map_or_else(
+            || place_frame(_wm, candidate, adopted),
+            |lost| lost.position,
+        );
+        let frame = take_frame(_wm, position, size);
+
+        bind_focus_click(_wm, _w);
+        bind_window_button(_wm, _w, xlib::Button1, xlib::Mod1Mask, XC_CROSSHAIR);
+        bind_window_button(_wm, _w, xlib::Button3, xlib::Mod1Mask, XC_FLEUR);
+
+        // Some clients want the shortcuts for themselves
+        let passthrough = candidate
+            .class
+            .iter()
+            .any(|name| _wm.config.passthrough_keys.contains(name));
+        if !passthrough {
+            bind_shortcuts(_wm, _w);
+        }
+
+        xlib::XAddToSaveSet(_wm.display, _w);
+
+        // For changes to the size hints
+        xlib::XSelectInput(_wm.display, _w, xlib::PropertyChangeMask);
+
+        if _wm.shape_event.is_some() {
+            shape::select(_wm.display, _w);
+        }
+
+        xlib::XReparentWindow(_wm.display, _w, frame.window, ext.left, ext.top);
+
+        // Clients that were iconified before the WM started stay hidden
+        // until they are picked from the switcher
+        let minimized = adopted && candidate.iconic;
+
+        // Clients may ask for a workspace, which is also how they keep theirs
+        // when the WM restarts. Sticky windows are not supported and stay here.
+        let workspace = lost
+            .as_ref()
+            .map(|lost| lost.workspace)
+            .or(candidate.desktop)
+            .filter(|d| *d < _wm.workspaces.len())
+            .unwrap_or(_wm.workspace);
+
+        if lost.is_some() {
+            debug!("Restoring the place of a crashed {:?}", candidate.class);
+            xlib::XResizeWindow(_wm.display, _w, client_size.x, client_size.y);
+        }
+
+        if !minimized && workspace == _wm.workspace {
+            xlib::XMapWindow(_wm.display, frame.window);
+        }
+
+        let (icon, icon_source) = icon::load(_wm, _w, metrics.button as u32);
+        let _win = Window {
+            frame: frame.window,
+            decoration_surface: frame.surface,
+            decoration_context: frame.context,
+            drag_start: Vector2D::new(0, 0),
+            drag_start_size: Vector2D::new(0, 0),
+            layer: window_layer(_wm, &candidate.types, &candidate.states),
+            skip_taskbar: candidate
+                .states
+                .contains(&_wm.atoms.net_wm_state_skip_taskbar),
+            skip_pager: candidate
+                .states
+                .contains(&_wm.atoms.net_wm_state_skip_pager),
+            hovered: None,
+            region: Region::Client,
+            hung: false,
+            minimized,
+            maximized_horz: false,
+            maximized_vert: false,
+            passthrough,
+            click_through: false,
+            restore_geometry: None,
+            fullscreen: false,
+            fullscreen_restore: None,
+            fullscreen_monitors: None,
+            startup_id: candidate.startup_id.clone(),
+            mapped_at: Instant::now(),
+            shaped: _wm.shape_event.is_some() && shape::is_shaped(_wm.display, _w),
+            hidden: false,
+            size_hints: SizeHints::read(_wm.display, _w),
+            accepts_input: accepts_input(_wm, _w),
+            class: candidate.class.clone(),
+            closing: false,
+            urgent: has_urgency_hint(_wm, _w),
+            decorated: candidate.decorated,
+            title: get_title(_wm, _w),
+            icon,
+            icon_source,
+            gtk_extents,
+            metrics,
+            floating: candidate.floating,
+            float_geometry: None,
+            covered: false,
+            demands_attention: candidate
+                .states
+                .contains(&_wm.atoms.net_wm_state_demands_attention),
+        };
+
+        _wm.windows.insert(_w, _win);
+        _wm.workspaces[workspace].tree.insert(_w);
+        _wm.clients.push(_w);
+        _wm.stack.push(_w);
+    }
+
+    let minimized = _wm.windows[&_w].minimized;
+    set_wm_state(
+        _wm,
+        _w,
+        if minimized {
+            ICONIC_STATE
+        } else {
+            NORMAL_STATE
+        },
+    );
+    publish_window_desktop(_wm, _w);
+    publish_frame_extents(_wm, _w, extents(&_wm.windows[&_w]));
+    shape_frame(_wm, _w);
+    let win = &_wm.windows[&_w];
+    publish_opaque_region(_wm, win, get_geometry(_wm, win.frame).1);
+    set_click_through(_wm, _w, candidate.click_through);
+
+    set_maximized(
+        _wm,
+        _w,
+        candidate
+            .states
+            .contains(&_wm.atoms.net_wm_state_maximized_horz),
+        candidate
+            .states
+            .contains(&_wm.atoms.net_wm_state_maximized_vert),
+    );
+
+    if candidate
+        .states
+        .contains(&_wm.atoms.net_wm_state_fullscreen)
+    {
+        set_fullscreen(_wm, _w, true);
+    }
+
+    restack(_wm);
+    workspaces::update_names(_wm);
+    layout::schedule(_wm, workspaces::of(_wm, _w));
+}
+
+/**
+ * Handle reparent notification event
+ */
+fn on_reparent_notify(_wm: &mut WindowManager, _e: xlib::XReparentEvent) {
+    // A client taken from its frame by someone else, embedders for instance,
+    // leaves an empty frame behind
+    let frame = match _wm.windows.get(&_e.window) {
+        Some(win) => win.frame,
+        None => return,
+    };
+
+    if _e.parent != frame {
+        debug!("{} was reparented away from its frame", _e.window);
+        forget_window(_wm, _e.window);
+    }
+}
+
+/**
+ * Handle unmap notification event
+ */
+fn on_unmap_notify(_wm: &mut WindowManager, _e: xlib::XUnmapEvent) {
+    if remove_unmanaged(_wm, _e.window) {
+        return;
+    }
+
+    if !_wm.windows.contains_key(&_e.window) {
+        warn!("Ignoring UnmapNotify for {}", _e.window);
+        return;
+    }
+
+    // Reparenting a mapped client unmaps it from the root, while a client
+    // that withdraws while unmapped sends a synthetic event there instead
+    if _e.event == _wm.root && _e.send_event == xlib::False {
+        debug!("Ignoring UnmapNotify for root");
+        return;
+    }
+
+    // The WM only ever unmaps frames, and iconifying goes through
+    // WM_CHANGE_STATE, so the client unmapping itself means withdrawal
+    debug!("{} withdrew", _e.window);
+    remove_window_frame(_wm, _e.window);
+}
+
+/**
+ * Handle map notification event
+ */
+fn on_map_notify(_wm: &WindowManager, _e: xlib::XMapEvent) {
+    // Ignore for now
+}
+
+/**
+ * Maps a client that already has a frame again. An iconic client mapping
+ * itself asks to be restored, as ICCCM says.
+ */
+fn remap_window(_wm: &mut WindowManager, _w: xlib::Window) {
+    let win = &_wm.windows[&_w];
+    debug!("Remapping managed window {}", _w);
+
+    if win.minimized {
+        activate_window(_wm, _w);
+        return;
+    }
+
+    unsafe {
+        xlib::XMapWindow(_wm.display, _w);
+        if workspaces::of(_wm, _w) == _wm.workspace && !win.hidden && !win.covered {
+            xlib::XMapWindow(_wm.display, win.frame);
+        }
+    }
+
+    set_wm_state(_wm, _w, NORMAL_STATE);
+}
+
+/**
+ * Handle map request event
+ */
+fn on_map_request(_wm: &mut WindowManager, _e: xlib::XMapRequestEvent) {
+    if _wm.windows.contains_key(&_e.window) {
+        remap_window(_wm, _e.window);
+        return;
+    }
+
+    let mut candidate = inspect_window(_wm, _e.window);
+    if let Some(id) = candidate.startup_id.clone() {
+        // Windows of a launch open where it was started from, unless they
+        // ask for a workspace themselves
+        if let Some(workspace) = _wm.launches.get(&id) {
+            candidate.desktop = candidate.desktop.or(Some(*workspace));
+        }
+        finish_launch(_wm, &id);
+    }
+
+    if !should_frame(_wm, &candidate.types) {
+        add_unmanaged(_wm, &candidate);
+
+        unsafe {
+            xlib::XMapWindow(_wm.display, _e.window);
+        }
+
+        restack(_wm);
+        return;
+    }
+
+    rules::apply(_wm, &mut candidate);
+    autostart::claim(_wm, &mut candidate);
+    create_window_frame(_wm, &candidate, false);
+
+    unsafe {
+        xlib::XMapWindow(_wm.display, _e.window);
+    }
+
+    // Windows that asked for another workspace show up there without focus,
+    // unless a rule has the view follow them
+    let workspace = workspaces::of(_wm, _e.window);
+    if workspace != _wm.workspace {
+        if !candidate.follow {
+            return;
+        }
+        workspaces::switch(_wm, workspace);
+    }
+
+    // A user time of zero asks for the window not to be focused
+    let user_time = get_user_time(_wm, _e.window);
+    if user_time == Some(0) {
+        return;
+    }
+
+    // A window the user last touched before using another one would take the
+    // focus from under them, so it stays behind and asks for attention
+    if let (Some(ours), Some(focused)) = (user_time, _wm.focused) {
+        if _wm.user_time != xlib::CurrentTime && time_before(ours, _wm.user_time) {
+            debug!("Keeping {} from stealing the focus", _e.window);
+
+            let index = _wm.stack.iter().position(|w| *w == focused).unwrap();
+            _wm.stack.retain(|w| *w != _e.window);
+            _wm.stack.insert(index, _e.window);
+            restack(_wm);
+            set_demands_attention(_wm, _e.window, true);
+            return;
+        }
+    }
+
+    // When a launch maps several windows at once, the one the user touched
+    // last stays in front instead of whichever mapped last
+    if let Some(sibling) = launch_sibling(_wm, _e.window) {
+        if let (Some(ours), Some(theirs)) = (user_time, get_user_time(_wm, sibling)) {
+            if time_before(ours, theirs) {
+                debug!(
+                    "Keeping {} in front of {} from the same launch",
+                    sibling, _e.window
+                );
+
+                let index = _wm.stack.iter().position(|w| *w == sibling).unwrap();
+                _wm.stack.retain(|w| *w != _e.window);
+                _wm.stack.insert(index, _e.window);
+                restack(_wm);
+                return;
+            }
+        }
+    }
+
+    focus_window(_wm, Some(_e.window));
+}
+
+/**
+ * Reads the time of the last user interaction with a client, which may be
+ * kept on a separate window to spare the client wakeups
+ */
+fn get_user_time(_wm: &WindowManager, _w: xlib::Window) -> Option<u64> {
+    let holder = get_cardinal_list(_wm, _w, _wm.atoms.net_wm_user_time_window)
+        .first()
+        .map_or(_w, |w| *w as xlib::Window);
+
+    get_cardinal_list(_wm, holder, _wm.atoms.net_wm_user_time)
+        .first()
+        .map(|t| *t as u64)
+}
+
+/**
+ * Compares X server timestamps, which wrap around every 49 days
+ */
+fn time_before(a: u64, b: u64) -> bool {
+    (a.wrapping_sub(b) as u32 as i32) < 0
+}
+
+/**
+ * Finds the topmost window mapped shortly before a client by the same launch
+ */
+fn launch_sibling(_wm: &WindowManager, _w: xlib::Window) -> Option<xlib::Window> {
+    let win = _wm.windows.get(&_w)?;
+    let id = win.startup_id.as_ref()?;
+    let tree = &_wm.workspaces[workspaces::of(_wm, _w)].tree;
+
+    _wm.stack.iter().rev().cloned().find(|w| {
+        let other = &_wm.windows[w];
+        *w != _w
+            && other.startup_id.as_ref() == Some(id)
+            && tree.contains(*w)
+            && !other.minimized
+            && win.mapped_at.duration_since(other.mapped_at) < LAUNCH_BURST_INTERVAL
+    })
+}
+
+/**
+ * Tracks the titlebar button under the pointer
+ */
+fn set_hovered_button(_wm: &mut WindowManager, _w: xlib::Window, hovered: Option<Button>) {
+    if let Some(win) = _wm.windows.get_mut(&_w) {
+        if win.hovered == hovered {
+            return;
+        }
+
+        win.hovered = hovered;
+    }
+
+    let win = _wm.windows.get(&_w).unwrap();
+    decoration::draw(_wm, win);
+}
+
+/**
+ * Updates the cursor and button highlight for the pointer hovering a frame
+ */
+fn hover_frame(_wm: &mut WindowManager, _w: xlib::Window, x: i32, y: i32) {
+    let win = _wm.windows.get(&_w).unwrap();
+    let region = decoration::region_at(_wm, win, x, y);
+
+    if win.region != region {
+        unsafe {
+            let cursor = xlib::XCreateFontCursor(_wm.display, region.cursor());
+            xlib::XDefineCursor(_wm.display, win.frame, cursor);
+            xlib::XFreeCursor(_wm.display, cursor);
+        }
+
+        _wm.windows.get_mut(&_w).unwrap().region = region;
+    }
+
+    let hovered = match region {
+        Region::Button(button) => Some(button),
+        _ => None,
+    };
+
+    set_hovered_button(_wm, _w, hovered);
+}
+
+/**
+ * Handle motion notification event
+ */
+fn on_motion_notify(_wm: &mut WindowManager, _e: xlib::XMotionEvent) {
+    if _wm.menu.is_some() {
+        menu::on_motion(_wm, Vector2D::new(_e.x_root, _e.y_root));
+        return;
+    }
+
+    // Drags requested by clients report motion on the root
+    let _w = match (frame_client(_wm, _e.window), _wm.active_window) {
+        (Some(_w), _) => _w,
+        (None, Some(active)) if _e.window == _wm.root => active,
+        (None, _) => _e.window,
+    };
+
+    if !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    if _wm.active_window != Some(_w) {
+        if _w != _e.window {
+            hover_frame(_wm, _w, _e.x, _e.y);
+        }
+        return;
+    }
+
+    // Moves from the keyboard leave the pointer out of it
+    if _wm.drag_keys {
+        return;
+    }
+    _wm.drag_touched = Instant::now();
+
+    let win = _wm.windows.get(&_w).unwrap();
+    let position = Vector2D::new(_e.x_root, _e.y_root);
+    let mut delta = position - _wm.drag_start;
+
+    // Shift keeps moves to the axis the pointer went furthest along, and
+    // resizes to the dimension that changed most
+    if _e.state & xlib::ShiftMask != 0 {
+        if delta.x.abs() >= delta.y.abs() {
+            delta.y = 0;
+        } else {
+            delta.x = 0;
+        }
+    }
+
+    if _wm.config.snap_grid > 0 && _e.state & xlib::ControlMask != 0 {
+        delta = snap_to_grid(_wm, win, delta);
+    }
+
+    match _wm.drag_region {
+        Region::Titlebar => move_window(_wm, _w, win, delta),
+        region => resize_window(_wm, _w, win, region, delta),
+    }
+
+    if _wm.drag_region == Region::Titlebar && _wm.config.edge_maximize {
+        snap::update(_wm, position);
+    }
+    if _wm.drag_region == Region::Titlebar && _wm.config.edge_flip > 0 {
+        flip::update(_wm, position);
+    }
+}
+
+/**
+ * Adjusts the delta of a drag so that the edges it moves land on the grid
+ */
+fn snap_to_grid(_wm: &WindowManager, _win: &Window, delta: Vector2D<i32>) -> Vector2D<i32> {
+    let grid = _wm.config.snap_grid as f64;
+    let snap = |value: i32| ((value as f64 / grid).round() * grid) as i32;
+
+    // Moves take the frame along by its top left corner
+    let (left, right, top, bottom) = match _wm.drag_region {
+        Region::Titlebar => (true, false, true, false),
+        region => region.edges(),
+    };
+
+    let near = _win.drag_start;
+    let far = _win.drag_start + _win.drag_start_size.as_i32s();
+    let axis = |near: i32, far: i32, delta: i32, first: bool, second: bool| {
+        if first {
+            snap(near + delta) - near
+        } else if second {
+            snap(far + delta) - far
+        } else {
+            delta
+        }
+    };
+
+    Vector2D::new(
+        axis(near.x, far.x, delta.x, left, right),
+        axis(near.y, far.y, delta.y, top, bottom),
+    )
+}
+
+/**
+ * Handle enter notification event, for frames and, when the focus policy
+ * wants to know, the desktop
+ */
+fn on_enter_notify(_wm: &mut WindowManager, _e: xlib::XCrossingEvent) {
+    // Sweeping over windows while dragging or in a menu changes nothing
+    if _wm.menu.is_some() || _wm.active_window.is_some() {
+        return;
+    }
+
+    // The root sees the pointer come back from one of its children
+    if _e.window == _wm.root {
+        let change = _wm.focus_policy.entered(None, _wm.focused);
+        focus::apply(_wm, change);
+        return;
+    }
+
+    // Moving between the client and its own frame changes nothing
+    if _e.detail == xlib::NotifyInferior || bar::on_enter(_wm, _e.window) {
+        return;
+    }
+
+    if let Some(_w) = frame_client(_wm, _e.window) {
+        let change = _wm.focus_policy.entered(Some(_w), _wm.focused);
+        focus::apply(_wm, change);
+    }
+}
+
+/**
+ * Handle leave notification event
+ */
+fn on_leave_notify(_wm: &mut WindowManager, _e: xlib::XCrossingEvent) {
+    if _e.detail != xlib::NotifyInferior && bar::on_leave(_wm, _e.window) {
+        return;
+    }
+
+    if let Some(_w) = frame_client(_wm, _e.window) {
+        set_hovered_button(_wm, _w, None);
+    }
+}
+
+/**
+ * Handle configuration notification event
+ */
+fn on_configure_notify(_wm: &mut WindowManager, _e: xlib::XConfigureEvent) {
+    if _e.window == _wm.root {
+        debug!("Root resized to {}x{}", _e.width, _e.height);
+        publish_desktop_geometry(_wm);
+        _wm.monitors = monitor::query(_wm.display, _wm.root, &_wm.config.monitor_split);
+        bar::create(_wm);
+        fit_desktops(_wm);
+        restack(_wm);
+        publish_workarea(_wm);
+        layout::schedule_all(_wm);
+        nightlight::refresh(_wm);
+    }
+}
+
+/**
+ * Handle configuration request event
+ */
+fn on_configure_request(_wm: &mut WindowManager, _e: xlib::XConfigureRequestEvent) {
+    let mut changes: xlib::XWindowChanges = unsafe { uninitialized() };
+    changes.x = _e.x;
+    changes.y = _e.y;
+
+    // Some clients ask for zero or negative sizes, which X rejects with BadValue
+    changes.width = max(_wm.config.min_width as i32, _e.width);
+    changes.height = max(_wm.config.min_height as i32, _e.height);
+    changes.border_width = _e.border_width;
+    changes.sibling = _e.above;
+    changes.stack_mode = _e.detail;
+
+    let managed = _wm.windows.contains_key(&_e.window);
+    let desktop = _wm
+        .unmanaged
+        .iter()
+        .any(|(w, layer)| *w == _e.window && *layer == Layer::Desktop);
+    if !managed {
+        unsafe {
+            xlib::XConfigureWindow(_wm.display, _e.window, _e.value_mask as u32, &mut changes);
+        }
+
+        // Desktop windows stay stretched over their monitors, below the rest
+        if desktop {
+            fit_desktop(_wm, _e.window);
+            restack(_wm);
+        }
+        return;
+    }
+
+    // The client stays put inside its frame, so positions apply to the frame.
+    // Stacking of managed windows is owned by the layers, not the client.
+    let win = _wm.windows.get(&_e.window).unwrap();
+    if win.fullscreen {
+        return;
+    }
+
+    let (position, size) = get_geometry(_wm, win.frame);
+    let mut client_size = client_size(extents(win), size);
+
+    if _e.value_mask & (xlib::CWWidth | xlib::CWHeight) as u64 != 0 {
+        if _e.value_mask & xlib::CWWidth as u64 != 0 {
+            client_size.x = changes.width as u32;
+        }
+        if _e.value_mask & xlib::CWHeight as u64 != 0 {
+            client_size.y = changes.height as u32;
+        }
+
+        let client_size = win.size_hints.constrain(client_size);
+        resize_client(_wm, _e.window, win, client_size);
+    }
+
+    if _e.value_mask & (xlib::CWX | xlib::CWY) as u64 != 0 {
+        let x = if _e.value_mask & xlib::CWX as u64 != 0 {
+            _e.x
+        } else {
+            position.x
+        };
+        let y = if _e.value_mask & xlib::CWY as u64 != 0 {
+            _e.y
+        } else {
+            position.y
+        };
+
+        unsafe {
+            xlib::XMoveWindow(_wm.display, win.frame, x, y);
+        }
+    }
+
+    bar::update(_wm);
+
+    if _e.value_mask & xlib::CWStackMode as u64 != 0 && _e.detail == xlib::Above && _e.above == 0 {
+        raise_window(_wm, _e.window);
+    }
+}
+
+/**
+ * Handle destruction notification event
+ */
+fn on_destroy_notify(_wm: &mut WindowManager, _e: xlib::XDestroyWindowEvent) {
+    remove_unmanaged(_wm, _e.window);
+
+    // Clients destroyed while unmapped never sent an UnmapNotify
+    forget_window(_wm, _e.window);
+}
+
+/**
+ * Loads the icon of a window again and shows it
+ */
+fn update_icon(_wm: &mut WindowManager, _w: xlib::Window) {
+    let size = _wm.windows[&_w].metrics.button as u32;
+    let (icon, source) = icon::load(_wm, _w, size);
+    let win = _wm.windows.get_mut(&_w).unwrap();
+    win.icon_source = source;
+    let old = std::mem::replace(&mut win.icon, icon);
+    if !old.is_null() {
+        unsafe {
+            cairo_sys::cairo_surface_destroy(old);
+        }
+    }
+    decoration::draw(_wm, &_wm.windows[&_w]);
+}
+
+/**
+ * Handle property change event
+ */
+fn on_property_notify(_wm: &mut WindowManager, _e: xlib::XPropertyEvent) {
+    if _e.atom == _wm.atoms.net_wm_strut_partial || _e.atom == _wm.atoms.net_wm_strut {
+        update_strut(_wm, _e.window);
+    } else if _e.atom == xlib::XA_WM_HINTS {
+        if _wm.windows.contains_key(&_e.window) {
+            let input = accepts_input(_wm, _e.window);
+            let urgent = has_urgency_hint(_wm, _e.window);
+            let win = _wm.windows.get_mut(&_e.window).unwrap();
+            win.accepts_input = input;
+
+            if win.urgent != urgent {
+                debug!("Urgency of {} is now {}", _e.window, urgent);
+                win.urgent = urgent;
+                show_urgency(_wm, _e.window);
+            }
+
+            // The icon pixmap may be in there too, which only matters
+            // without _NET_WM_ICON. Urgency and input changes leave it be.
+            if let icon::Source::Hints(pixmap, mask) = _wm.windows[&_e.window].icon_source {
+                if icon::hint_pixmaps(_wm, _e.window) != (pixmap, mask) {
+                    update_icon(_wm, _e.window);
+                }
+            }
+        }
+    } else if _e.atom == _wm.atoms.net_wm_name
+        || _e.atom == xlib::XA_WM_NAME
+        || _e.atom == _wm.atoms.net_wm_icon_name
+        || _e.atom == xlib::XA_WM_ICON_NAME
+    {
+        if _wm.windows.contains_key(&_e.window) {
+            let title = get_title(_wm, _e.window);
+            let win = _wm.windows.get_mut(&_e.window).unwrap();
+            if win.title != title {
+                win.title = title;
+                decoration::draw(_wm, &_wm.windows[&_e.window]);
+
+                // The tab of the window may be on the titlebar of another
+                let frame = _wm.windows[&_e.window].frame;
+                if tree::tabs(_wm, frame).is_some() {
+                    let workspace = workspaces::of(_wm, _e.window);
+                    layout::schedule(_wm, workspace);
+                }
+            }
+        }
+    } else if _e.atom == _wm.atoms.net_wm_icon {
+        if _wm.windows.contains_key(&_e.window) {
+            update_icon(_wm, _e.window);
+        }
+    } else if _e.atom == _wm.atoms.motif_wm_hints {
+        if _wm.windows.contains_key(&_e.window) {
+            let decorated = wants_decoration(_wm, _e.window);
+            set_decorated(_wm, _e.window, decorated);
+        }
+    } else if _e.atom == _wm.atoms.gtk_frame_extents {
+        if _wm.windows.contains_key(&_e.window) {
+            let gtk = get_gtk_extents(_wm, _e.window);
+            set_gtk_extents(_wm, _e.window, gtk);
+        }
+    } else if _e.atom == xlib::XA_WM_NORMAL_HINTS {
+        if let Some(win) = _wm.windows.get_mut(&_e.window) {
+            win.size_hints = SizeHints::read(_wm.display, _e.window);
+            debug!("Size hints of {} are now {:?}", _e.window, win.size_hints);
+        }
+    }
+}
+
+/**
+ * Handle the clipboard contents arriving from a client about to be killed
+ */
+fn on_selection_notify(_wm: &mut WindowManager, _e: xlib::XSelectionEvent) {
+    if let Some(_w) = clipboard::on_selection_notify(_wm, _e) {
+        _wm.timers.cancel(&Timer::ClipboardTimeout(_w));
+        kill_client(_wm, _w);
+    }
+}
+
+/**
+ * Handle a client changing its shape, which the frame follows
+ */
+fn on_shape_notify(_wm: &mut WindowManager, _e: shape::XShapeEvent) {
+    if !shape::is_bounding(&_e) {
+        return;
+    }
+
+    let win = match _wm.windows.get_mut(&_e.window) {
+        Some(win) => win,
+        None => return,
+    };
+
+    debug!("Shape of {} changed, shaped: {}", _e.window, _e.shaped != 0);
+    win.shaped = _e.shaped != 0;
+    shape_frame(_wm, _e.window);
+
+    let win = &_wm.windows[&_e.window];
+    publish_opaque_region(_wm, win, get_geometry(_wm, win.frame).1);
+}
+
+/**
+ * Handle creation notification event
+ */
+fn on_create_notify(_wm: &WindowManager, _e: xlib::XCreateWindowEvent) {
+    // Ignore for now
+}
+
+/**
+ * Builds the menu shown for a window from its titlebar
+ */
+fn window_menu(_wm: &WindowManager, _w: xlib::Window) -> Vec<Item> {
+    let win = _wm.windows.get(&_w).unwrap();
+    let workspace = workspaces::of(_wm, _w);
+    let mut destinations: Vec<Item> = _wm
+        .workspaces
+        .iter()
+        .enumerate()
+        .map(|(i, ws)| Item::action(&ws.name, Action::SendToWorkspace(i)).checked(i == workspace))
+        .collect();
+
+    if _wm.monitors.len() > 1 {
+        let (position, size) = get_geometry(_wm, win.frame);
+        let current = monitor::index_at(&_wm.monitors, position + size.as_i32s() / 2);
+
+        destinations.push(Item::separator());
+        destinations.extend(_wm.monitors.iter().enumerate().map(|(i, monitor)| {
+            Item::action(&monitor.name, Action::SendToMonitor(i)).checked(i == current)
+        }));
+    }
+
+    let maximize = if win.maximized_horz && win.maximized_vert {
+        "Restore"
+    } else {
+        "Maximize"
+    };
+
+    // Closing a window that stopped responding kills it
+    let close = if win.hung { "Force quit" } else { "Close" };
+
+    vec![
+        Item::action("Move", Action::Move),
+        Item::action("Resize", Action::Resize),
+        Item::action("Minimize", Action::Minimize),
+        Item::action(maximize, Action::Maximize),
+        Item::submenu("Send to", destinations),
+        Item::action("Pass shortcuts through", Action::TogglePassthrough).checked(win.passthrough),
+        Item::action("Click through", Action::ToggleClickThrough).checked(win.click_through),
+        Item::separator(),
+        Item::action(close, Action::Close),
+    ]
+}
+
+/**
+ * Opens the window menu below the titlebar for keyboard navigation
+ */
+fn open_keyboard_menu(_wm: &mut WindowManager, _w: xlib::Window) {
+    let win = _wm.windows.get(&_w).unwrap();
+    let ext = extents(win);
+    let (position, _) = get_geometry(_wm, win.frame);
+    let items = window_menu(_wm, _w);
+
+    menu::open(
+        _wm,
+        _w,
+        items,
+        position + Vector2D::new(ext.left, ext.top),
+        true,
+    );
+}
+
+/**
+ * Runs an entry picked from the window menu
+ */
+fn on_menu_action(_wm: &mut WindowManager, _w: xlib::Window, action: Action) {
+    if !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    match action {
+        Action::Minimize => minimize_window(_wm, _w),
+        Action::Maximize => toggle_maximize(_wm, _w),
+        Action::Close => kill_window(_wm, _w),
+        Action::SendToWorkspace(index) => workspaces::send_to(_wm, _w, index),
+        Action::SendToMonitor(index) => send_to_monitor(_wm, _w, index),
+        Action::TogglePassthrough => {
+            let enable = !_wm.windows[&_w].passthrough;
+            set_passthrough(_wm, _w, enable);
+        }
+        Action::ToggleClickThrough => {
+            let enable = !_wm.windows[&_w].click_through;
+            set_click_through(_wm, _w, enable);
+        }
+        Action::Move => begin_key_drag(_wm, _w, Region::Titlebar),
+        Action::Resize => begin_key_drag(_wm, _w, Region::BottomRight),
+    }
+}
+
+/**
+ * Starts moving or resizing a window with the pointer
+ */
+fn begin_drag(_wm: &mut WindowManager, _w: xlib::Window, pointer: Vector2D<i32>, region: Region) {
+    if _wm.windows.get(&_w).unwrap().fullscreen {
+        return;
+    }
+
+    let frame = _wm.windows.get(&_w).unwrap().frame;
+    let (position, size) = get_geometry(_wm, frame);
+    let win = _wm.windows.get_mut(&_w).unwrap();
+
+    _wm.active_window = Some(_w);
+    _wm.drag_region = region;
+    _wm.drag_start = pointer;
+
+    win.drag_start = position;
+    win.drag_start_size = size;
+
+    // Drags that start with a button held end when it goes up, others once
+    // they were left alone for long enough
+    _wm.drag_buttons = query_pointer(_wm).1 & DRAG_BUTTONS != 0;
+    _wm.drag_touched = Instant::now();
+    _wm.timers.cancel(&Timer::GrabWatchdog);
+    _wm.timers
+        .schedule(GRAB_CHECK_INTERVAL, Timer::GrabWatchdog);
+
+    // For Escape to cancel the drag, whatever has the focus
+    unsafe {
+        xlib::XGrabKeyboard(
+            _wm.display,
+            _wm.root,
+            xlib::False,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            xlib::CurrentTime,
+        );
+    }
+
+    raise_window(_wm, _w);
+}
+
+/**
+ * Stops moving or resizing a window
+ */
+fn end_drag(_wm: &mut WindowManager) {
+    if _wm.drag_keys {
+        _wm.drag_keys = false;
+        _wm.held_key = None;
+        _wm.timers.cancel(&Timer::KeyDrag);
+    }
+
+    // Maximized windows dragged to another monitor fill that one instead
+    if let Some(_w) = _wm
+        .active_window
+        .filter(|_| _wm.drag_region == Region::Titlebar)
+    {
+        if let Some(win) = _wm.windows.get(&_w) {
+            let center = win.drag_start + win.drag_start_size.as_i32s() / 2;
+            let source = monitor::index_at(&_wm.monitors, center);
+            follow_monitor(_wm, _w, source);
+        }
+    }
+
+    snap::hide(_wm);
+    flip::cancel(_wm);
+    if _wm.active_window.take().is_some() {
+        _wm.timers.cancel(&Timer::GrabWatchdog);
+
+        unsafe {
+            xlib::XUngrabKeyboard(_wm.display, xlib::CurrentTime);
+        }
+    }
+    bar::update(_wm);
+
+    if _wm.drag_grab {
+        _wm.drag_grab = false;
+
+        unsafe {
+            xlib::XUngrabPointer(_wm.display, xlib::CurrentTime);
+        }
+    }
+}
+
+/**
+ * Ends a drag whose grab outlived it, so that a lost button release or a
+ * forgotten move from the keyboard cannot keep the pointer and keyboard
+ * away from everything else
+ */
+fn check_grabs(_wm: &mut WindowManager) {
+    let _w = match _wm.active_window {
+        Some(_w) => _w,
+        None => return,
+    };
+
+    let stale = if _wm.drag_buttons {
+        query_pointer(_wm).1 & DRAG_BUTTONS == 0
+    } else {
+        _wm.drag_touched.elapsed() > DRAG_IDLE_TIMEOUT
+    };
+
+    if !stale {
+        _wm.timers
+            .schedule(GRAB_CHECK_INTERVAL, Timer::GrabWatchdog);
+        return;
+    }
+
+    warn!("Drag of {} outlived its grab, releasing it", _w);
+    end_drag(_wm);
+
+    unsafe {
+        xlib::XUngrabPointer(_wm.display, xlib::CurrentTime);
+        xlib::XUngrabKeyboard(_wm.display, xlib::CurrentTime);
+    }
+}
+
+/**
+ * Starts moving or resizing a window with the arrow keys, until ENTER or a
+ * click ends it. Resizing moves the bottom right corner.
+ */
+fn begin_key_drag(_wm: &mut WindowManager, _w: xlib::Window, region: Region) {
+    begin_drag(_wm, _w, Vector2D::new(0, 0), region);
+    if _wm.active_window != Some(_w) {
+        return;
+    }
+
+    debug!("Moving {} as {:?} from the keyboard", _w, region);
+    _wm.drag_buttons = false;
+    _wm.drag_keys = true;
+    _wm.drag_offset = Vector2D::new(0, 0);
+    _wm.held_key = None;
+}
+
+/**
+ * Gets the time between steps while an arrow key is held down
+ */
+fn key_drag_interval(_wm: &WindowManager) -> Duration {
+    Duration::from_millis(1000 / _wm.config.key_move_rate.max(1) as u64)
+}
+
+/**
+ * Moves a window or its corner one step from the keyboard
+ */
+fn step_key_drag(_wm: &mut WindowManager, direction: Vector2D<i32>) {
+    let _w = match _wm.active_window {
+        Some(_w) if _wm.drag_keys => _w,
+        _ => return,
+    };
+
+    _wm.drag_touched = Instant::now();
+    _wm.drag_offset += direction * _wm.config.key_move_step as i32;
+    let delta = _wm.drag_offset;
+    let win = _wm.windows.get(&_w).unwrap();
+
+    match _wm.drag_region {
+        Region::Titlebar => move_window(_wm, _w, win, delta),
+        region => resize_window(_wm, _w, win, region, delta),
+    }
+}
+
+/**
+ * Handle a key going down while a window is moved from the keyboard. An
+ * arrow key held down keeps going at the configured rate, whatever the key
+ * repeat of the server does.
+ */
+fn on_key_drag_press(_wm: &mut WindowManager, _e: &xlib::XKeyEvent) {
+    let keycode = |sym: u32| unsafe { xlib::XKeysymToKeycode(_wm.display, sym as u64) as u32 };
+
+    if _e.keycode == keycode(keysym::XK_Return) {
+        end_drag(_wm);
+        return;
+    }
+
+    let direction = [
+        (keysym::XK_Left, Vector2D::new(-1, 0)),
+        (keysym::XK_Right, Vector2D::new(1, 0)),
+        (keysym::XK_Up, Vector2D::new(0, -1)),
+        (keysym::XK_Down, Vector2D::new(0, 1)),
+    ]
+    .iter()
+    .find(|(sym, _)| _e.keycode == keycode(*sym))
+    .map(|(_, direction)| *direction);
+
+    let direction = match direction {
+        Some(direction) => direction,
+        None => return,
+    };
+
+    // Repeats of the key already held are covered by the timer
+    if _wm.held_key.is_some_and(|(held, _)| held == _e.keycode) {
+        return;
+    }
+
+    _wm.held_key = Some((_e.keycode, direction));
+    step_key_drag(_wm, direction);
+    _wm.timers.cancel(&Timer::KeyDrag);
+    _wm.timers.schedule(key_drag_interval(_wm), Timer::KeyDrag);
+}
+
+/**
+ * Handle a client asking to be moved or resized, usually because its own
+ * titlebar or edge was dragged
+ */
+fn on_moveresize_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    const SIZE_KEYBOARD: i64 = 9;
+    const MOVE_KEYBOARD: i64 = 10;
+    const CANCEL: i64 = 11;
+
+    let direction = _e.data.get_long(2);
+    let region = match direction {
+        0 => Region::TopLeft,
+        1 => Region::Top,
+        2 => Region::TopRight,
+        3 => Region::Right,
+        4 | 9 => Region::BottomRight,
+        5 => Region::Bottom,
+        6 => Region::BottomLeft,
+        7 => Region::Left,
+        8 | 10 => Region::Titlebar,
+        CANCEL => return end_drag(_wm),
+        _ => return,
+    };
+
+    if !_wm.windows.contains_key(&_e.window) || _wm.menu.is_some() {
+        return;
+    }
+
+    // Moves asked for from the keyboard are driven by the arrow keys
+    if direction == SIZE_KEYBOARD || direction == MOVE_KEYBOARD {
+        begin_key_drag(_wm, _e.window, region);
+        focus_window(_wm, Some(_e.window));
+        return;
+    }
+
+    // The client has released its own grab, so the pointer is ours until
+    // the button goes up
+    // The grab keeps the cursor alive after it is freed
+    let grabbed = unsafe {
+        let cursor = xlib::XCreateFontCursor(_wm.display, region.cursor());
+        let grabbed = xlib::XGrabPointer(
+            _wm.display,
+            _wm.root,
+            xlib::False,
+            (xlib::ButtonPressMask | xlib::ButtonReleaseMask | xlib::PointerMotionMask) as u32,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            0,
+            cursor,
+            xlib::CurrentTime,
+        );
+        xlib::XFreeCursor(_wm.display, cursor);
+        grabbed
+    };
+
+    if grabbed != xlib::GrabSuccess {
+        warn!("Could not grab the pointer to move {}", _e.window);
+        return;
+    }
+
+    let pointer = Vector2D::new(_e.data.get_long(0) as i32, _e.data.get_long(1) as i32);
+    debug!("Moving {} as {:?} on request", _e.window, region);
+
+    _wm.drag_grab = true;
+    begin_drag(_wm, _e.window, pointer, region);
+    focus_window(_wm, Some(_e.window));
+}
+
+/**
+ * Handle button press event
+ */
+fn on_button_press(_wm: &mut WindowManager, _e: xlib::XButtonEvent) {
+    // A click ends a move started from the keyboard
+    if _wm.drag_grab || _wm.drag_keys {
+        end_drag(_wm);
+
+        // A keyboard move holds no pointer grab, so the click may have come
+        // through the synchronous focus grab and froze the pointer
+        unsafe {
+            xlib::XAllowEvents(_wm.display, xlib::AsyncPointer, xlib::CurrentTime);
+        }
+        return;
+    }
+
+    if _wm.menu.is_some() {
+        if let Some((_w, action)) = menu::on_button_press(_wm, Vector2D::new(_e.x_root, _e.y_root))
+        {
+            on_menu_action(_wm, _w, action);
+        }
+        return;
+    }
+
+    if notify::on_button_press(_wm, _e.window) {
+        return;
+    }
+
+    if bar::is_bar(_wm, _e.window) && _e.button == xlib::Button1 {
+        if let Some(index) = bar::workspace_at(_wm, _e.window, _e.x) {
+            workspaces::switch(_wm, index);
+        }
+        return;
+    }
+
+    if _e.window == _wm.root || bar::is_bar(_wm, _e.window) {
+        if _e.button == xlib::Button4 || _e.button == xlib::Button5 {
+            let down = _e.button == xlib::Button5;
+            workspaces::step(_wm, down != _wm.config.workspace_scroll_reverse);
+        }
+        return;
+    }
+
+    if let Some(_w) = frame_client(_wm, _e.window) {
+        raise_window(_wm, _w);
+        focus_window(_wm, Some(_w));
+
+        let win = _wm.windows.get(&_w).unwrap();
+        let region = decoration::region_at(_wm, win, _e.x, _e.y);
+        let position = Vector2D::new(_e.x_root, _e.y_root);
+
+        if _e.button == xlib::Button3 && matches!(region, Region::Titlebar | Region::Tab(_)) {
+            let items = window_menu(_wm, _w);
+            menu::open(_wm, _w, items, position, false);
+            return;
+        }
+
+        if _e.button != xlib::Button1 {
+            return;
+        }
+
+        match region {
+            Region::Button(Button::Icon) => {
+                let items = window_menu(_wm, _w);
+                menu::open(_wm, _w, items, position, false);
+            }
+            Region::Button(Button::Close) => kill_window(_wm, _w),
+            Region::Button(Button::Minimize) => minimize_window(_wm, _w),
+            Region::Button(Button::Maximize) => toggle_maximize(_wm, _w),
+            Region::Tab(index) => tree::select_tab(_wm, _e.window, index),
+            Region::Client => {}
+            region => begin_drag(_wm, _w, Vector2D::new(_e.x_root, _e.y_root), region),
+        }
+        return;
+    }
+
     if !_wm.windows.contains_key(&_e.window) {
         return;
     }
 
-    let win = _wm.windows.get(&_e.window).unwrap();
-    if _wm.active_window != win {
+    let change = _wm.focus_policy.clicked(_e.window, _wm.focused);
+    focus::apply(_wm, change);
+
+    // A plain click caught to focus the window, pass it on to the client
+    if _e.state & xlib::Mod1Mask == 0 {
+        unsafe {
+            xlib::XAllowEvents(_wm.display, xlib::ReplayPointer, xlib::CurrentTime);
+        }
+        return;
+    }
+
+    let region = if _e.button == xlib::Button3 {
+        Region::BottomRight
+    } else {
+        Region::Titlebar
+    };
+
+    begin_drag(_wm, _e.window, Vector2D::new(_e.x_root, _e.y_root), region);
+}
+
+/**
+ * Handle button release event
+ */
+fn on_button_release(_wm: &mut WindowManager, _e: xlib::XButtonEvent) {
+    let moved = _wm
+        .active_window
+        .filter(|_| _wm.drag_region == Region::Titlebar);
+    end_drag(_wm);
+
+    if let (Some(_w), true) = (moved, _wm.config.edge_maximize) {
+        snap::drop_on_edge(_wm, _w, Vector2D::new(_e.x_root, _e.y_root));
+    }
+}
+
+/**
+ * Makes a window cover the left or right half of its monitor, maximized
+ * vertically so restoring it brings back its old geometry
+ */
+fn tile_window(_wm: &mut WindowManager, _w: xlib::Window, left: bool) {
+    debug!(
+        "Tiling {} to the {} half",
+        _w,
+        if left { "left" } else { "right" }
+    );
+    set_maximized(_wm, _w, false, true);
+
+    let win = match _wm.windows.get(&_w) {
+        Some(win) if !win.fullscreen => win,
+        _ => return,
+    };
+
+    let (position, size) = get_geometry(_wm, win.frame);
+    let center = position + size.as_i32s() / 2;
+    let (origin, area) = work_area(_wm, monitor::index_at(&_wm.monitors, center));
+
+    let width = area.x / 2;
+    let x = if left {
+        origin.x
+    } else {
+        origin.x + (area.x - width) as i32
+    };
+
+    resize_client(
+        _wm,
+        _w,
+        win,
+        client_size(extents(win), Vector2D::new(width, size.y)),
+    );
+
+    unsafe {
+        xlib::XMoveWindow(_wm.display, win.frame, x, position.y);
+    }
+}
+
+/**
+ * Puts the dragged window back where and how large it was when the drag
+ * started
+ */
+fn cancel_drag(_wm: &mut WindowManager) {
+    if let Some((_w, win)) = _wm
+        .active_window
+        .and_then(|_w| _wm.windows.get(&_w).map(|win| (_w, win)))
+    {
+        let client = client_size(extents(win), win.drag_start_size);
+        debug!("Cancelled drag of {}", _w);
+
+        unsafe {
+            xlib::XMoveWindow(_wm.display, win.frame, win.drag_start.x, win.drag_start.y);
+        }
+        resize_client(_wm, _w, win, client);
+    }
+
+    end_drag(_wm);
+}
+
+/**
+ * Handle key press event
+ */
+fn on_key_press(_wm: &mut WindowManager, mut _e: xlib::XKeyEvent) {
+    if _wm.menu.is_some() {
+        let sym = unsafe { xlib::XLookupKeysym(&mut _e, 0) } as u32;
+        if let Some((_w, action)) = menu::on_key_press(_wm, sym) {
+            on_menu_action(_wm, _w, action);
+        }
+        return;
+    }
+
+    if _wm.active_window.is_some() {
+        if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_Escape as u64) as u32 }
+        {
+            cancel_drag(_wm);
+        } else if _wm.drag_keys {
+            on_key_drag_press(_wm, &_e);
+        }
+        return;
+    }
+
+    if _e.window == _wm.root {
+        if workspaces::on_key_press(_wm, &_e) {
+            return;
+        }
+
+        if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_Tab as u64) as u32 }
+        {
+            restack_windows(_wm, _e.window);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_d as u64) as u32 }
+        {
+            let showing = _wm.showing_desktop;
+            set_showing_desktop(_wm, !showing);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_u as u64) as u32 }
+        {
+            jump_to_urgent(_wm);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_Return as u64) as u32 }
+        {
+            if let Some(focused) = _wm.focused {
+                layout::promote(_wm, focused);
+            }
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_j as u64) as u32 }
+        {
+            layout::cycle(_wm, true);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_k as u64) as u32 }
+        {
+            layout::cycle(_wm, false);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_b as u64) as u32 }
+        {
+            tree::split(_wm, false);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_v as u64) as u32 }
+        {
+            tree::split(_wm, true);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_z as u64) as u32 }
+        {
+            zoom::toggle(_wm);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_t as u64) as u32 }
+        {
+            tree::toggle_tabbed(_wm);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_f as u64) as u32 }
+        {
+            if let Some(focused) = _wm.focused {
+                layout::toggle_floating(_wm, focused);
+            }
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_h as u64) as u32 }
+        {
+            layout::resize_master(_wm, false);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_l as u64) as u32 }
+        {
+            layout::resize_master(_wm, true);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_equal as u64) as u32 }
+        {
+            layout::adjust_gaps(_wm, _e.state & xlib::ShiftMask != 0, true);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_minus as u64) as u32 }
+        {
+            layout::adjust_gaps(_wm, _e.state & xlib::ShiftMask != 0, false);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_space as u64) as u32 }
+        {
+            layout::next(_wm);
+        } else if let Some((_, direction)) = DIRECTION_KEYS.iter().find(|(sym, _)| {
+            _e.keycode == unsafe { xlib::XKeysymToKeycode(_wm.display, *sym as u64) as u32 }
+        }) {
+            if _e.state & xlib::ControlMask != 0 {
+                layout::resize_tiled(_wm, *direction);
+            } else if _e.state & xlib::ShiftMask != 0 {
+                move_direction(_wm, *direction);
+            } else {
+                focus_direction(_wm, *direction);
+            }
+        }
+        return;
+    }
+
+    if !_wm.windows.contains_key(&_e.window) {
+        return;
+    }
+
+    if _e.state & xlib::Mod1Mask > 0 {
+        if _e.keycode == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_F4 as u64) as u32 }
+        {
+            kill_window(_wm, _e.window);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_F10 as u64) as u32 }
+        {
+            toggle_maximize(_wm, _e.window);
+        } else if _e.keycode
+            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_space as u64) as u32 }
+        {
+            open_keyboard_menu(_wm, _e.window);
+        }
+    } else if _wm.config.raise_on_focus {
+        raise_window(_wm, _e.window);
+    }
+}
+
+/**
+ * Handle key release event
+ */
+fn on_key_release(_wm: &mut WindowManager, _e: xlib::XKeyEvent) {
+    if _wm.held_key.is_some_and(|(held, _)| held == _e.keycode) {
+        _wm.held_key = None;
+        _wm.timers.cancel(&Timer::KeyDrag);
+    }
+}
+
+/**
+ * Handle expose event
+ */
+fn on_expose(_wm: &WindowManager, _e: xlib::XExposeEvent) {
+    if menu::on_expose(_wm, _e.window)
+        || bar::on_expose(_wm, _e.window)
+        || notify::on_expose(_wm, _e.window)
+    {
         return;
     }
 
-    let position = Vector2D::new(_e.x_root, _e.y_root);
-    let delta = position - _wm.drag_start;
+    // The event is for the frame, not the actual application window
+    if let Some(_w) = frame_client(_wm, _e.window) {
+        decoration::draw(_wm, _wm.windows.get(&_w).unwrap());
+    }
+}
+
+/**
+ * Handle _NET_WM_STATE change requests
+ */
+fn on_wm_state_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    const REMOVE: i64 = 0;
+    const ADD: i64 = 1;
+    const TOGGLE: i64 = 2;
+
+    let skip_taskbar = _wm.atoms.net_wm_state_skip_taskbar;
+    let skip_pager = _wm.atoms.net_wm_state_skip_pager;
+    let fullscreen = _wm.atoms.net_wm_state_fullscreen;
+    let maximized_horz = _wm.atoms.net_wm_state_maximized_horz;
+    let maximized_vert = _wm.atoms.net_wm_state_maximized_vert;
+    let demands_attention = _wm.atoms.net_wm_state_demands_attention;
+    let wm_state = _wm.atoms.net_wm_state;
+    let action = _e.data.get_long(0);
+    let mut states = get_atom_list(_wm, _e.window, wm_state);
+    let properties = [
+        _e.data.get_long(1) as xlib::Atom,
+        _e.data.get_long(2) as xlib::Atom,
+    ];
+
+    let win = match _wm.windows.get_mut(&_e.window) {
+        Some(win) => win,
+        None => return,
+    };
+
+    let mut fullscreen_flag = win.fullscreen;
+    let mut horz_flag = win.maximized_horz;
+    let mut vert_flag = win.maximized_vert;
+    let attention_before = win.demands_attention;
+    for property in &properties {
+        let flag = if *property == skip_taskbar {
+            &mut win.skip_taskbar
+        } else if *property == skip_pager {
+            &mut win.skip_pager
+        } else if *property == fullscreen {
+            &mut fullscreen_flag
+        } else if *property == maximized_horz {
+            &mut horz_flag
+        } else if *property == maximized_vert {
+            &mut vert_flag
+        } else if *property == demands_attention {
+            &mut win.demands_attention
+        } else {
+            continue;
+        };
+
+        *flag = match action {
+            REMOVE => false,
+            ADD => true,
+            TOGGLE => !*flag,
+            _ => *flag,
+        };
+
+        states.retain(|a| a != property);
+        if *flag {
+            states.push(*property);
+        }
+    }
+
+    let attention_changed = win.demands_attention != attention_before;
+    set_atom_list(_wm, _e.window, wm_state, &states);
+
+    if attention_changed {
+        show_urgency(_wm, _e.window);
+    }
+
+    // Fullscreen and maximize also move the window around, which is
+    // handled separately
+    set_fullscreen(_wm, _e.window, fullscreen_flag);
+    set_maximized(_wm, _e.window, horz_flag, vert_flag);
+}
+
+/**
+ * Handle startup notification messages
+ */
+fn on_startup_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent, begin: bool) {
+    let message = match _wm.startup.feed(&_e, begin) {
+        Some(message) => message,
+        None => return,
+    };
+
+    let id = match message.values.get("ID") {
+        Some(id) => id.clone(),
+        None => return,
+    };
 
-    if _e.state & xlib::Mod1Mask != 0 {
-        if _e.state & xlib::Button1Mask != 0 {
-            move_window(_wm, _e.window, win, delta);
-        } else if _e.state & xlib::Button3Mask != 0 {
-            resize_window(_wm, _e.window, win, delta);
+    // Launchers may name the workspace to open on, otherwise it is the one
+    // the launch started from
+    let desktop = message
+        .values
+        .get("DESKTOP")
+        .and_then(|d| d.parse::<usize>().ok())
+        .filter(|d| *d < _wm.workspaces.len());
+
+    match message.kind.as_str() {
+        "new" => {
+            let workspace = desktop.unwrap_or(_wm.workspace);
+            debug!("Launch {} started on workspace {}", id, workspace);
+
+            let timeout = Duration::from_secs(_wm.config.startup_timeout);
+            _wm.timers
+                .schedule(timeout, Timer::StartupTimeout(id.clone()));
+            _wm.launches.insert(id, workspace);
+            update_root_cursor(_wm);
+        }
+        "change" => {
+            if let (Some(desktop), Some(workspace)) = (desktop, _wm.launches.get_mut(&id)) {
+                *workspace = desktop;
+            }
         }
+        "remove" => finish_launch(_wm, &id),
+        _ => {}
     }
 }
 
 /**
- * Handle configuration notification event
+ * Handle _NET_ACTIVE_WINDOW requests from pagers and taskbars
  */
-fn on_configure_notify(_wm: &WindowManager, _e: xlib::XConfigureEvent) {
-    // Ignore for now
+fn on_active_window_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    debug!(
+        "Activating {} on request (source {})",
+        _e.window,
+        _e.data.get_long(0)
+    );
+
+    activate_window(_wm, _e.window);
 }
 
 /**
- * Handle configuration request event
+ * Handle requests from pagers to switch workspaces or move windows between them
  */
-fn on_configure_request(_wm: &WindowManager, _e: xlib::XConfigureRequestEvent) {
-    let mut changes: xlib::XWindowChanges = unsafe { uninitialized() };
-    changes.x = _e.x;
-    changes.y = _e.y;
-    changes.width = _e.width;
-    changes.height = _e.height;
-    changes.border_width = _e.border_width;
-    changes.sibling = _e.above;
-    changes.stack_mode = _e.detail;
-
-    unsafe {
-        if _wm.windows.contains_key(&_e.window) {
-            let win = _wm.windows.get(&_e.window).unwrap();
-            xlib::XConfigureWindow(_wm.display, win.frame, _e.value_mask as u32, &mut changes);
-        }
+fn on_desktop_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    let index = _e.data.get_long(0) as usize;
 
-        xlib::XConfigureWindow(_wm.display, _e.window, _e.value_mask as u32, &mut changes);
+    if _e.message_type == _wm.atoms.net_current_desktop {
+        workspaces::switch(_wm, index);
+    } else if _e.message_type == _wm.atoms.net_wm_desktop {
+        workspaces::send_to(_wm, _e.window, index);
+    } else {
+        // The workspaces come from the configuration and from names used
+        debug!("Ignoring request for {} workspaces", index);
     }
 }
 
 /**
- * Handle destruction notification event
+ * Handle pagers and taskbars asking to close a window
  */
-fn on_destroy_notify(_wm: &WindowManager, _e: xlib::XDestroyWindowEvent) {
-    // Ignore for now
+fn on_close_window_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    if !_wm.windows.contains_key(&_e.window) {
+        return;
+    }
+
+    debug!(
+        "Closing {} on request (source {})",
+        _e.window,
+        _e.data.get_long(1)
+    );
+
+    kill_window(_wm, _e.window);
 }
 
 /**
- * Handle creation notification event
+ * Handle client message event
  */
-fn on_create_notify(_wm: &WindowManager, _e: xlib::XCreateWindowEvent) {
-    // Ignore for now
+fn on_client_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    match _wm.message_handlers.get(&_e.message_type).cloned() {
+        Some(handler) => handler(_wm, _e),
+        None => debug!("Ignoring ClientMessage {}", _e.message_type),
+    }
 }
 
 /**
- * Handle button press event
+ * Has ClientMessages of a type handled by a function. A type has a single
+ * handler, the last one registered.
  */
-fn on_button_press(_wm: &mut WindowManager, _e: xlib::XButtonEvent) {
-    if !_wm.windows.contains_key(&_e.window) {
-        return;
+fn register_message(_wm: &mut WindowManager, message_type: xlib::Atom, handler: MessageHandler) {
+    if _wm.message_handlers.insert(message_type, handler).is_some() {
+        warn!("ClientMessage {} registered twice", message_type);
     }
+}
 
-    let win = _wm.windows.get_mut(&_e.window).unwrap();
-    let mut x: i32 = 0;
-    let mut y: i32 = 0;
-    let mut w: u32 = 0;
-    let mut h: u32 = 0;
-    let mut border: u32 = 0;
-    let mut depth: u32 = 0;
+/**
+ * Registers the handlers for the ClientMessages the window manager takes
+ */
+fn register_messages(_wm: &mut WindowManager) {
+    let atoms = &_wm.atoms;
+    let handlers: [(xlib::Atom, MessageHandler); 14] = [
+        (atoms.wm_protocols, on_protocols_message),
+        (atoms.wm_change_state, on_change_state_message),
+        (atoms.net_wm_state, on_wm_state_message),
+        (atoms.net_active_window, on_active_window_message),
+        (atoms.net_wm_moveresize, on_moveresize_message),
+        (atoms.net_close_window, on_close_window_message),
+        (
+            atoms.net_wm_fullscreen_monitors,
+            on_fullscreen_monitors_message,
+        ),
+        (atoms.net_showing_desktop, on_showing_desktop_message),
+        (
+            atoms.net_request_frame_extents,
+            on_request_frame_extents_message,
+        ),
+        (atoms.net_current_desktop, on_desktop_message),
+        (atoms.net_wm_desktop, on_desktop_message),
+        (atoms.net_number_of_desktops, on_desktop_message),
+        (atoms.net_startup_info_begin, |_wm, _e| {
+            on_startup_message(_wm, _e, true)
+        }),
+        (atoms.net_startup_info, |_wm, _e| {
+            on_startup_message(_wm, _e, false)
+        }),
+    ];
 
-    unsafe {
-        let mut root: xlib::Window = uninitialized();
-        xlib::XGetGeometry(
-            _wm.display,
-            win.frame,
-            &mut root,
-            &mut x,
-            &mut y,
-            &mut w,
-            &mut h,
-            &mut border,
-            &mut depth,
-        );
+    for (message_type, handler) in handlers.iter() {
+        register_message(_wm, *message_type, *handler);
+    }
+}
 
-        xlib::XRaiseWindow(_wm.display, win.frame);
+/**
+ * Handle WM_PROTOCOLS messages sent back to the root, which are replies to
+ * _NET_WM_PING
+ */
+fn on_protocols_message(_wm: &mut WindowManager, _e: xlib::XClientMessageEvent) {
+    if _e.data.get_long(0) as xlib::Atom == _wm.atoms.net_wm_ping {
+        on_ping_reply(_wm, _e);
     }
+}
 
-    _wm.active_window = win;
-    _wm.drag_start = Vector2D {
-        x: _e.x_root,
-        y: _e.y_root,
+/**
+ * Gets the name of an event type for logging
+ */
+fn event_name(kind: i32) -> String {
+    let name = match kind {
+        xlib::KeyPress => "KeyPress",
+        xlib::KeyRelease => "KeyRelease",
+        xlib::ButtonPress => "ButtonPress",
+        xlib::ButtonRelease => "ButtonRelease",
+        xlib::MotionNotify => "MotionNotify",
+        xlib::EnterNotify => "EnterNotify",
+        xlib::LeaveNotify => "LeaveNotify",
+        xlib::FocusIn => "FocusIn",
+        xlib::FocusOut => "FocusOut",
+        xlib::KeymapNotify => "KeymapNotify",
+        xlib::Expose => "Expose",
+        xlib::GraphicsExpose => "GraphicsExpose",
+        xlib::NoExpose => "NoExpose",
+        xlib::VisibilityNotify => "VisibilityNotify",
+        xlib::CreateNotify => "CreateNotify",
+        xlib::DestroyNotify => "DestroyNotify",
+        xlib::UnmapNotify => "UnmapNotify",
+        xlib::MapNotify => "MapNotify",
+        xlib::MapRequest => "MapRequest",
+        xlib::ReparentNotify => "ReparentNotify",
+        xlib::ConfigureNotify => "ConfigureNotify",
+        xlib::ConfigureRequest => "ConfigureRequest",
+        xlib::GravityNotify => "GravityNotify",
+        xlib::ResizeRequest => "ResizeRequest",
+        xlib::CirculateNotify => "CirculateNotify",
+        xlib::CirculateRequest => "CirculateRequest",
+        xlib::PropertyNotify => "PropertyNotify",
+        xlib::SelectionClear => "SelectionClear",
+        xlib::SelectionRequest => "SelectionRequest",
+        xlib::SelectionNotify => "SelectionNotify",
+        xlib::ColormapNotify => "ColormapNotify",
+        xlib::ClientMessage => "ClientMessage",
+        xlib::MappingNotify => "MappingNotify",
+        xlib::GenericEvent => "GenericEvent",
+        _ => return format!("#{}", kind),
     };
 
-    win.drag_start = Vector2D::new(x, y);
-    win.drag_start_size = Vector2D::new(w, h);
+    name.to_string()
 }
 
 /**
- * Handle button release event
+ * Counts an event nobody handles, to be logged in the next summary
  */
-fn on_button_release(_wm: &mut WindowManager, _e: xlib::XButtonEvent) {
-    _wm.active_window = unsafe { uninitialized() };
+fn count_unhandled_event(_wm: &mut WindowManager, kind: i32) {
+    trace!("Did not handle event of type {}", event_name(kind));
+
+    if _wm.unhandled.is_empty() {
+        _wm.timers
+            .schedule(UNHANDLED_SUMMARY_INTERVAL, Timer::UnhandledSummary);
+    }
+
+    *_wm.unhandled.entry(kind).or_insert(0) += 1;
 }
 
 /**
- * Handle key press event
+ * Reads the title of a client, preferring the UTF-8 one
  */
-fn on_key_press(_wm: &mut WindowManager, _e: xlib::XKeyEvent) {
-    if _e.window == _wm.root {
-        if _e.keycode
-            == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_Tab as u64) as u32 }
-        {
-            restack_windows(_wm, _e.window);
+fn get_title(_wm: &WindowManager, _w: xlib::Window) -> String {
+    get_string_property(_wm, _w, _wm.atoms.net_wm_name)
+        .or_else(|| get_string_property(_wm, _w, xlib::XA_WM_NAME))
+        .filter(|title| !title.is_empty())
+        .or_else(|| get_string_property(_wm, _w, _wm.atoms.net_wm_icon_name))
+        .or_else(|| get_string_property(_wm, _w, xlib::XA_WM_ICON_NAME))
+        .unwrap_or_default()
+}
+
+/**
+ * Lists the clients picked by IPC criteria, topmost first
+ */
+fn find_windows(_wm: &WindowManager, criteria: &ipc::Criteria) -> Vec<xlib::Window> {
+    if criteria.is_empty() {
+        return _wm.focused.into_iter().collect();
+    }
+
+    stacking_order(_wm)
+        .into_iter()
+        .rev()
+        .filter(|w| {
+            let win = &_wm.windows[w];
+            criteria.matches(&[*w, win.frame], &win.class, &win.title)
+        })
+        .collect()
+}
+
+/**
+ * Lists the windows that match the criteria and a check on their state, top
+ * first, one per line with their id, workspace, class and title. Unlike
+ * other commands, no criteria means all windows.
+ */
+fn list_windows<F>(_wm: &WindowManager, criteria: &ipc::Criteria, all: bool, check: F) -> String
+where
+    F: Fn(&WindowManager, xlib::Window, &Window) -> bool,
+{
+    stacking_order(_wm)
+        .into_iter()
+        .rev()
+        .filter(|w| {
+            let win = &_wm.windows[w];
+            (all || (!win.skip_taskbar && !win.skip_pager))
+                && criteria.matches(&[*w, win.frame], &win.class, &win.title)
+                && check(_wm, *w, win)
+        })
+        .map(|w| {
+            let win = &_wm.windows[&w];
+            format!(
+                "0x{:x}\t{}\t{}\t{}",
+                w,
+                _wm.workspaces[workspaces::of(_wm, w)].name,
+                win.class
+                    .get(1)
+                    .or_else(|| win.class.first())
+                    .map_or("", |c| c),
+                win.title
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/**
+ * Handle a command sent through gothite-msg
+ */
+fn on_ipc_request(_wm: &mut WindowManager, mut connection: ipc::Connection) {
+    let args = match connection.read() {
+        Some(args) => args,
+        None => return,
+    };
+
+    let request = match ipc::Request::parse(&args) {
+        Ok(request) => request,
+        Err(err) => return connection.reply(&format!("error: {}", err)),
+    };
+
+    debug!("Command {:?}", request);
+
+    match request.command {
+        ipc::Command::Reload => {
+            return match reload_config(_wm) {
+                Ok(warnings) => connection.reply(&warnings.join("\n")),
+                Err(err) => connection.reply(&format!("error: {}", err)),
+            };
         }
-        return;
+        ipc::Command::AddRule(..) | ipc::Command::RemoveRule(_) | ipc::Command::ListRules => {
+            return connection.reply(&rules::on_request(_wm, &request.command));
+        }
+        ipc::Command::Workspace(ref name) => {
+            workspaces::switch_to_name(_wm, name);
+            return connection.reply("");
+        }
+        ipc::Command::Layout(None) => {
+            return connection.reply(layout::name_of(_wm, _wm.workspace));
+        }
+        ipc::Command::Layout(Some(ref name)) => {
+            if !layout::exists(name) {
+                return connection.reply(&format!("error: no layout {}", name));
+            }
+            let workspace = _wm.workspace;
+            layout::set(_wm, workspace, name);
+            return connection.reply("");
+        }
+        ipc::Command::FocusDirection(direction) => {
+            focus_direction(_wm, direction);
+            return connection.reply("");
+        }
+        ipc::Command::Nightlight(None) => {
+            return connection.reply(&format!("{}K", nightlight::current(_wm)));
+        }
+        ipc::Command::Nightlight(Some(ref value)) => {
+            let temperature = match value.as_str() {
+                "auto" => None,
+                "off" => Some(nightlight::NEUTRAL),
+                _ => match nightlight::parse_temperature(value) {
+                    Some(temperature) => Some(temperature),
+                    None => {
+                        return connection.reply(&format!(
+                            "error: temperature must be from {}K to {}K",
+                            nightlight::MIN_TEMPERATURE,
+                            nightlight::MAX_TEMPERATURE
+                        ))
+                    }
+                },
+            };
+            nightlight::set(_wm, temperature);
+            return connection.reply("");
+        }
+        ipc::Command::Profile(None) => {
+            return connection.reply(_wm.profile.as_ref().map_or("", |name| name));
+        }
+        ipc::Command::Profile(Some(ref name)) => {
+            return match profiles::switch(_wm, name) {
+                Ok(warnings) => connection.reply(&warnings.join("\n")),
+                Err(err) => connection.reply(&format!("error: {}", err)),
+            };
+        }
+        ipc::Command::Clients(ref name, all) => {
+            let workspace = match *name {
+                Some(ref name) => match _wm.workspaces.iter().position(|ws| ws.name == *name) {
+                    Some(index) => index,
+                    None => return connection.reply(&format!("error: no workspace {}", name)),
+                },
+                None => _wm.workspace,
+            };
+            let windows = list_windows(_wm, &request.criteria, all, |_wm, w, _| {
+                _wm.workspaces[workspace].tree.contains(w)
+            });
+            return connection.reply(&windows);
+        }
+        ipc::Command::Visible(number, all) => {
+            if number.is_some_and(|n| n > _wm.monitors.len()) {
+                return connection.reply(&format!("error: no monitor {}", number.unwrap()));
+            }
+            let windows = list_windows(_wm, &request.criteria, all, |_wm, w, win| {
+                let (position, size) = get_geometry(_wm, win.frame);
+                let monitor = monitor::index_at(&_wm.monitors, position + size.as_i32s() / 2);
+                _wm.workspaces[_wm.workspace].tree.contains(w)
+                    && !win.minimized
+                    && !win.hidden
+                    && !win.covered
+                    && number.is_none_or(|n| n == monitor + 1)
+            });
+            return connection.reply(&windows);
+        }
+        _ => {}
     }
 
-    if !_wm.windows.contains_key(&_e.window) {
-        return;
+    let windows = find_windows(_wm, &request.criteria);
+    if windows.is_empty() {
+        return connection.reply("error: no matching window");
     }
 
-    if _e.state & xlib::Mod1Mask > 0 {
-        if _e.keycode == unsafe { xlib::XKeysymToKeycode(_wm.display, keysym::XK_F4 as u64) as u32 }
-        {
-            kill_window(_wm, _e.window);
+    match request.command {
+        ipc::Command::Focus => activate_window(_wm, windows[0]),
+        ipc::Command::Raise => {
+            // Bottom first, so the matches keep their order between them
+            for _w in windows.iter().rev() {
+                raise_window(_wm, *_w);
+            }
         }
-    } else {
-        unsafe {
-            xlib::XRaiseWindow(_wm.display, _e.window);
+        ipc::Command::Close => {
+            for _w in &windows {
+                kill_window(_wm, *_w);
+            }
+        }
+        ipc::Command::Reload
+        | ipc::Command::FocusDirection(_)
+        | ipc::Command::Layout(_)
+        | ipc::Command::AddRule(..)
+        | ipc::Command::RemoveRule(_)
+        | ipc::Command::ListRules
+        | ipc::Command::Workspace(_)
+        | ipc::Command::Clients(..)
+        | ipc::Command::Visible(..)
+        | ipc::Command::Nightlight(_)
+        | ipc::Command::Profile(_) => unreachable!(),
+        ipc::Command::Float => {
+            for _w in &windows {
+                layout::toggle_floating(_wm, *_w);
+            }
+        }
+        ipc::Command::Send(ref name) => {
+            // Sending a window may remove a workspace it left empty and
+            // renumber the others, so the target is looked up each time
+            for _w in &windows {
+                let index = workspaces::find_or_create(_wm, name);
+                workspaces::send_to(_wm, *_w, index);
+            }
+        }
+        ipc::Command::Move(x, y) => {
+            for _w in &windows {
+                let win = &_wm.windows[_w];
+                if !win.fullscreen {
+                    unsafe {
+                        xlib::XMoveWindow(_wm.display, win.frame, x, y);
+                    }
+                }
+            }
         }
     }
+
+    connection.reply("");
 }
 
 /**
- * Handle key release event
+ * Handle an expired timer
  */
-fn on_key_release(_wm: &WindowManager, _e: xlib::XKeyEvent) {
-    // Ignore for now
+fn on_timeout(_wm: &mut WindowManager, _t: Timer) {
+    match _t {
+        Timer::PingTimeout(_w) => {
+            if let Some(win) = _wm.windows.get_mut(&_w) {
+                info!("Window {} is not responding, closing it again kills it", _w);
+                win.hung = true;
+                _wm.timers.schedule(HUNG_PULSE_INTERVAL, Timer::HungPulse);
+            }
+        }
+        Timer::HungPulse => {
+            let mut any = false;
+            for win in _wm.windows.values().filter(|win| win.hung) {
+                decoration::draw(_wm, win);
+                any = true;
+            }
+
+            if any {
+                _wm.timers.schedule(HUNG_PULSE_INTERVAL, Timer::HungPulse);
+            }
+        }
+        Timer::NotificationExpire(id) => notify::expire(_wm, id),
+        Timer::ClipboardTimeout(_w) => {
+            if let Some(_w) = clipboard::give_up(_wm, _w) {
+                kill_client(_wm, _w);
+            }
+        }
+        Timer::Profile => profiles::on_timeout(_wm),
+        Timer::GrabWatchdog => check_grabs(_wm),
+        Timer::EdgeFlip => flip::on_timeout(_wm),
+        Timer::Nightlight => nightlight::update(_wm),
+        Timer::KeyDrag => {
+            if let Some((_, direction)) = _wm.held_key {
+                step_key_drag(_wm, direction);
+                _wm.timers.schedule(key_drag_interval(_wm), Timer::KeyDrag);
+            }
+        }
+        Timer::ZoomRefresh => {
+            zoom::refresh(_wm);
+            _wm.timers
+                .schedule(zoom::REFRESH_INTERVAL, Timer::ZoomRefresh);
+        }
+        Timer::OrphanCheck => {
+            collect_orphans(_wm);
+            autostart::reap(_wm);
+            _wm.timers
+                .schedule(ORPHAN_CHECK_INTERVAL, Timer::OrphanCheck);
+        }
+        Timer::BarRefresh => {
+            bar::refresh(_wm);
+            _wm.timers
+                .schedule(bar::REFRESH_INTERVAL, Timer::BarRefresh);
+        }
+        Timer::StartupTimeout(id) => {
+            debug!("Launch {} timed out", id);
+            finish_launch(_wm, &id);
+        }
+        Timer::UnhandledSummary => {
+            let mut counts: Vec<(i32, u32)> = _wm.unhandled.drain().collect();
+            counts.sort();
+
+            let summary: Vec<String> = counts
+                .iter()
+                .map(|(kind, count)| format!("{} x{}", event_name(*kind), count))
+                .collect();
+
+            debug!(
+                "Unhandled events in the last {}s: {}",
+                UNHANDLED_SUMMARY_INTERVAL.as_secs(),
+                summary.join(", ")
+            );
+        }
+    }
 }
 
 /**
- * Handle expose event
+ * Events the root window is watched for
  */
-fn on_expose(_wm: &WindowManager, _e: xlib::XExposeEvent) {
-    // FIXME: There must be a better way to get the belonging window.
-    //        The one from the event is the "frame", not actual application window.
+fn root_event_mask(config: &Config) -> i64 {
+    // Managed clients are watched through their frames, so the root only
+    // needs requests from new windows and its own geometry changes. Watching
+    // its substructure would wake us up for every frame we move ourselves.
+    // Startup notification messages are sent to the root for whoever selects
+    // property changes on it.
+    let mut mask =
+        xlib::SubstructureRedirectMask | xlib::StructureNotifyMask | xlib::PropertyChangeMask;
+    if config.workspace_scroll {
+        // Scrolling on the desktop background switches workspaces
+        mask |= xlib::ButtonPressMask;
+    }
+    if focus::by_name(&config.focus_policy).watches_desktop() {
+        mask |= xlib::EnterWindowMask;
+    }
+
+    mask
+}
+
+/**
+ * Rereads the configuration file and applies it to the running session.
+ * Returns the problems found in it, or why it could not be read at all.
+ */
+fn reload_config(_wm: &mut WindowManager) -> Result<Vec<String>, String> {
+    // A broken file leaves the running configuration alone
+    let mut config = match Config::read() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            return Err(err);
+        }
+    };
+
+    _wm.profile = profiles::pick(&config, _wm.profile.as_ref());
+    if let Some(ref name) = _wm.profile {
+        config.apply_profile(name);
+    }
+    let warnings = config.warnings.clone();
+
+    _wm.font = FontSet::load(&config.font, &config.font_fallback);
+    _wm.config = config;
+    _wm.monitors = monitor::query(_wm.display, _wm.root, &_wm.config.monitor_split);
+    bar::create(_wm);
+    fit_desktops(_wm);
+    restack(_wm);
+    publish_workarea(_wm);
+
+    if !_wm.config.notifications {
+        notify::stop(_wm);
+    }
+    start_notifications(_wm);
+
     unsafe {
-        let mut parent: xlib::Window = uninitialized();
-        let mut root: xlib::Window = uninitialized();
-        let mut windows: *mut xlib::Window = uninitialized();
-        let mut count: u32 = 0;
+        xlib::XSelectInput(_wm.display, _wm.root, root_event_mask(&_wm.config));
+    }
 
-        if xlib::XQueryTree(
-            _wm.display,
-            _e.window,
-            &mut root,
-            &mut parent,
-            &mut windows,
-            &mut count,
-        ) == 0
-        {
+    workspaces::reload(_wm);
+    workspaces::update_names(_wm);
+    workspaces::publish(_wm);
+    publish_workarea(_wm);
+
+    _wm.gaps = layout::Gaps::configured(&_wm.config);
+    _wm.focus_policy = focus::by_name(&_wm.config.focus_policy);
+    layout::schedule_all(_wm);
+    profiles::schedule(_wm);
+    nightlight::update(_wm);
+
+    unsafe {
+        xlib::XSetWindowBackground(_wm.display, _wm.root, _wm.config.background);
+        xlib::XClearWindow(_wm.display, _wm.root);
+    }
+
+    let metrics = decoration::metrics(&_wm.config);
+    for _w in _wm.clients.clone() {
+        set_metrics(_wm, _w, metrics);
+    }
+
+    for win in _wm.windows.values() {
+        decoration::draw(_wm, win);
+    }
+
+    info!("Reloaded configuration");
+    Ok(warnings)
+}
+
+/**
+ * Blocks until there are events to process or a timer is due
+ */
+fn wait_for_event(_wm: &WindowManager) {
+    unsafe {
+        if xlib::XPending(_wm.display) > 0 {
             return;
         }
 
-        if count > 0 {
-            let _w = windows.offset(0);
-            let win = _wm.windows.get(&*_w).unwrap();
-            draw_window_decoration(_wm, win.frame, win.decoration_context);
+        let timeout = match _wm.timers.next_delay() {
+            Some(delay) => delay.as_micros().div_ceil(1000) as i32,
+            None => -1,
+        };
+
+        let mut fds = vec![libc::pollfd {
+            fd: xlib::XConnectionNumber(_wm.display),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+
+        if let Some(ref server) = _wm.ipc {
+            fds.push(libc::pollfd {
+                fd: server.fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+
+        if let Some(ref service) = _wm.notifications {
+            fds.push(libc::pollfd {
+                fd: service.fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            });
         }
 
-        xlib::XFree(windows as *mut c_void);
+        libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout);
+    }
+}
+
+/**
+ * Starts showing notifications when they are enabled and no other daemon
+ * runs
+ */
+fn start_notifications(_wm: &mut WindowManager) {
+    if !_wm.config.notifications || _wm.notifications.is_some() {
+        return;
+    }
+
+    _wm.notifications = notify::Notifications::start();
+    match _wm.notifications {
+        Some(_) => info!("Showing notifications"),
+        None => warn!("Could not take over notifications, is another daemon running?"),
     }
 }
 
@@ -634,8 +5118,18 @@ fn main() {
         xlib::XInitThreads();
     }
 
+    let check_only = env::args().skip(1).any(|arg| arg == "--check-x11");
+
     let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
     if display.is_null() {
+        if check_only {
+            eprintln!(
+                "Cannot open display '{}'",
+                env::var("DISPLAY").unwrap_or_default()
+            );
+            process::exit(1);
+        }
+
         panic!("Failed to open display");
     }
 
@@ -645,88 +5139,215 @@ fn main() {
         xlib::XSetErrorHandler(Some(error_handler));
     }
 
+    if check_only {
+        check::run(display);
+        return;
+    }
+
     let screen = unsafe { xlib::XDefaultScreenOfDisplay(display) };
     let root = unsafe { xlib::XRootWindowOfScreen(screen) };
+    let mut config = Config::load();
+    let profile = profiles::pick(&config, None);
+    if let Some(ref name) = profile {
+        info!("Starting with profile {}", name);
+        config.apply_profile(name);
+    }
 
     unsafe {
-        xlib::XSelectInput(
-            display,
-            root,
-            xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
-        );
+        xlib::XSelectInput(display, root, root_event_mask(&config));
+    }
 
-        xlib::XGrabKey(
-            display,
-            xlib::XKeysymToKeycode(display, keysym::XK_Tab as u64) as i32,
-            xlib::Mod1Mask,
-            root,
-            0,
-            xlib::GrabModeAsync,
-            xlib::GrabModeAsync,
-        );
+    grab_root_shortcuts(display, root, true);
 
+    // Held keys then send no releases in between repeats, so moves from the
+    // keyboard can tell when a key really goes up
+    unsafe {
+        xlib::XkbSetDetectableAutoRepeat(display, xlib::True, ptr::null_mut());
+    }
+
+    unsafe {
         xlib::XSync(display, 0);
-        xlib::XSetWindowBackground(display, root, 0x2E3440);
+        xlib::XSetWindowBackground(display, root, config.background);
         xlib::XClearWindow(display, root);
     }
 
+    let font = FontSet::load(&config.font, &config.font_fallback);
+    let workspace_names = config.workspaces.clone();
+    let workspace_list = workspaces::configured(&config);
+    let gaps = layout::Gaps::configured(&config);
+    let focus_policy = focus::by_name(&config.focus_policy);
+
+    let monitors = monitor::query(display, root, &config.monitor_split);
+
+    let ipc = ipc::Server::bind();
+    match ipc {
+        Some(ref server) => info!("Listening for commands on {}", server.path().display()),
+        None => warn!("Could not open the command socket, gothite-msg will not work"),
+    }
+
     let mut wm = WindowManager {
         display: display,
         root: root,
         windows: HashMap::new(),
+        clients: Vec::new(),
+        stack: Vec::new(),
         drag_start: Vector2D::new(0, 0),
-        active_window: unsafe { uninitialized() },
+        drag_region: Region::Titlebar,
+        drag_grab: false,
+        drag_keys: false,
+        drag_offset: Vector2D::new(0, 0),
+        held_key: None,
+        drag_touched: Instant::now(),
+        drag_buttons: false,
+        active_window: None,
+        focused: None,
+        config,
+        atoms: Atoms::intern(display),
+        timers: Timers::new(),
+        startup: startup::Assembler::new(),
+        launches: HashMap::new(),
+        autostart: Vec::new(),
+        autostart_done: Vec::new(),
+        workspaces: workspace_list,
+        workspace_names,
+        workspace_focus: HashMap::new(),
+        font,
+        monitors,
+        menu: None,
+        workspace: 0,
+        unhandled: HashMap::new(),
+        frame_pool: Vec::new(),
+        shortcuts_inhibited: false,
+        unmanaged: Vec::new(),
+        struts: HashMap::new(),
+        ipc,
+        shape_event: shape::query(display),
+        showing_desktop: false,
+        bar: bar::Bar::new(),
+        event_time: xlib::CurrentTime,
+        user_time: xlib::CurrentTime,
+        notifications: None,
+        clipboard: clipboard::Clipboard::new(display, root),
+        lost: Vec::new(),
+        message_handlers: HashMap::new(),
+        relayout: HashSet::new(),
+        gaps,
+        focus_policy,
+        zoom: None,
+        snap_preview: None,
+        edge_flip: None,
+        nightlight: nightlight::Nightlight::default(),
+        profile,
     };
 
+    // Have frames ready for the first windows that show up
+    for _i in 0..FRAME_POOL_SIZE {
+        let frame = create_frame(&wm);
+        wm.frame_pool.push(frame);
+    }
+
+    register_messages(&mut wm);
+    publish_wm_check(&wm);
+    publish_desktop_geometry(&wm);
+    workspaces::publish(&wm);
+    bar::create(&mut wm);
+    wm.timers.schedule(bar::REFRESH_INTERVAL, Timer::BarRefresh);
+    wm.timers
+        .schedule(ORPHAN_CHECK_INTERVAL, Timer::OrphanCheck);
+    profiles::schedule(&mut wm);
+    nightlight::update(&mut wm);
+    start_notifications(&mut wm);
     reparent_initial_windows(&mut wm);
+    publish_workarea(&wm);
+    autostart::run(&mut wm);
+    publish_client_list(&wm);
+    focus_window(&mut wm, None);
 
     unsafe {
         xlib::XDefineCursor(display, root, xlib::XCreateFontCursor(display, XC_ARROW));
     }
 
+    info!("Focus policy is {}", wm.focus_policy.name());
     info!("Starting event loop");
 
     loop {
-        let mut ev: xlib::XEvent = unsafe { uninitialized() };
+        wait_for_event(&wm);
 
-        unsafe {
-            xlib::XNextEvent(display, &mut ev);
-
-            match ev.get_type() {
-                xlib::ConfigureRequest => on_configure_request(&wm, ev.configure_request),
-                xlib::ConfigureNotify => on_configure_notify(&wm, ev.configure),
-                xlib::CreateNotify => on_create_notify(&wm, ev.create_window),
-                xlib::DestroyNotify => on_destroy_notify(&wm, ev.destroy_window),
-                xlib::ReparentNotify => on_reparent_notify(&wm, ev.reparent),
-                xlib::MapNotify => on_map_notify(&wm, ev.map),
-                xlib::MapRequest => on_map_request(&mut wm, ev.map_request),
-                xlib::UnmapNotify => on_unmap_notify(&mut wm, ev.unmap),
-                xlib::ButtonPress => on_button_press(&mut wm, ev.button),
-                xlib::ButtonRelease => on_button_release(&mut wm, ev.button),
-                xlib::KeyPress => on_key_press(&mut wm, ev.key),
-                xlib::KeyRelease => on_key_release(&wm, ev.key),
-                xlib::Expose => on_expose(&wm, ev.expose),
-
-                xlib::MotionNotify => {
-                    while xlib::XCheckTypedWindowEvent(
-                        display,
-                        ev.motion.window,
-                        xlib::MotionNotify,
-                        &mut ev,
-                    ) > 0
-                    {
-                        // Skip pending motion evets
-                    }
+        for timeout in wm.timers.expired() {
+            on_timeout(&mut wm, timeout);
+        }
+
+        while let Some(connection) = wm.ipc.as_ref().and_then(|server| server.accept()) {
+            on_ipc_request(&mut wm, connection);
+        }
+
+        notify::on_bus(&mut wm);
 
-                    on_motion_notify(&wm, ev.motion);
+        while unsafe { xlib::XPending(display) } > 0 {
+            let mut ev: xlib::XEvent = unsafe { uninitialized() };
+
+            unsafe {
+                xlib::XNextEvent(display, &mut ev);
+                wm.event_time = event_time(&ev).unwrap_or(wm.event_time);
+
+                // Only input counts as the user doing something
+                if let xlib::KeyPress | xlib::ButtonPress = ev.get_type() {
+                    wm.user_time = wm.event_time;
                 }
 
-                _ => {
-                    info!("Did not handle event of type {}", ev.get_type());
-                    // void
+                match ev.get_type() {
+                    xlib::ConfigureRequest => on_configure_request(&mut wm, ev.configure_request),
+                    xlib::ConfigureNotify => on_configure_notify(&mut wm, ev.configure),
+                    xlib::CreateNotify => on_create_notify(&wm, ev.create_window),
+                    xlib::DestroyNotify => on_destroy_notify(&mut wm, ev.destroy_window),
+                    xlib::ReparentNotify => on_reparent_notify(&mut wm, ev.reparent),
+                    xlib::MapNotify => on_map_notify(&wm, ev.map),
+                    xlib::MapRequest => on_map_request(&mut wm, ev.map_request),
+                    xlib::UnmapNotify => on_unmap_notify(&mut wm, ev.unmap),
+                    xlib::ButtonPress => on_button_press(&mut wm, ev.button),
+                    xlib::ButtonRelease => on_button_release(&mut wm, ev.button),
+                    xlib::KeyPress => on_key_press(&mut wm, ev.key),
+                    xlib::KeyRelease => on_key_release(&mut wm, ev.key),
+                    xlib::Expose => on_expose(&wm, ev.expose),
+                    xlib::ClientMessage => on_client_message(&mut wm, ev.client_message),
+                    xlib::EnterNotify => on_enter_notify(&mut wm, ev.crossing),
+                    xlib::LeaveNotify => on_leave_notify(&mut wm, ev.crossing),
+                    xlib::PropertyNotify => on_property_notify(&mut wm, ev.property),
+                    xlib::SelectionNotify => on_selection_notify(&mut wm, ev.selection),
+                    xlib::SelectionRequest => {
+                        clipboard::on_selection_request(&wm, ev.selection_request)
+                    }
+                    xlib::SelectionClear => {
+                        clipboard::on_selection_clear(&mut wm, ev.selection_clear)
+                    }
+
+                    xlib::MotionNotify => {
+                        while xlib::XCheckTypedWindowEvent(
+                            display,
+                            ev.motion.window,
+                            xlib::MotionNotify,
+                            &mut ev,
+                        ) > 0
+                        {
+                            // Skip pending motion evets
+                        }
+
+                        on_motion_notify(&mut wm, ev.motion);
+                    }
+
+                    t if Some(t) == wm.shape_event => on_shape_notify(&mut wm, shape::event(&ev)),
+
+                    _ => count_unhandled_event(&mut wm, ev.get_type()),
                 }
             }
         }
+
+        // Requests pile up in the output buffer while handling everything
+        // that came in, and go out together once the layouts are applied
+        layout::flush(&mut wm);
+        unsafe {
+            xlib::XFlush(display);
+        }
     }
 
     unsafe {
@@ -0,0 +1,542 @@
+use cairo_sys;
+use monitor;
+use std::mem::zeroed;
+use vector2d::Vector2D;
+use x11::{keysym, xlib};
+use {name_window, WindowManager};
+
+const ITEM_PADDING: f64 = 6.0;
+const MARGIN: f64 = 12.0;
+const MARKER_WIDTH: f64 = 14.0;
+const ARROW_WIDTH: f64 = 14.0;
+const SEPARATOR_HEIGHT: f64 = 9.0;
+
+/**
+ * Things a menu entry can do to the window it was opened for
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Minimize,
+    Maximize,
+    Close,
+    SendToWorkspace(usize),
+    SendToMonitor(usize),
+    TogglePassthrough,
+    ToggleClickThrough,
+    Move,
+    Resize,
+}
+
+/**
+ * A menu entry
+ */
+#[derive(Clone)]
+pub struct Item {
+    label: String,
+    action: Option<Action>,
+    submenu: Vec<Item>,
+    checked: bool,
+}
+
+impl Item {
+    pub fn action(label: &str, action: Action) -> Item {
+        Item {
+            label: label.to_string(),
+            action: Some(action),
+            submenu: Vec::new(),
+            checked: false,
+        }
+    }
+
+    pub fn submenu(label: &str, items: Vec<Item>) -> Item {
+        Item {
+            label: label.to_string(),
+            action: None,
+            submenu: items,
+            checked: false,
+        }
+    }
+
+    pub fn separator() -> Item {
+        Item {
+            label: String::new(),
+            action: None,
+            submenu: Vec::new(),
+            checked: false,
+        }
+    }
+
+    /**
+     * Marks the entry as the current choice
+     */
+    pub fn checked(mut self, checked: bool) -> Item {
+        self.checked = checked;
+        self
+    }
+
+    fn is_separator(&self) -> bool {
+        self.label.is_empty()
+    }
+}
+
+/**
+ * A single popup level of a menu
+ */
+struct Pane {
+    window: xlib::Window,
+    surface: *mut cairo_sys::cairo_surface_t,
+    context: *mut cairo_sys::cairo_t,
+    items: Vec<Item>,
+    heights: Vec<f64>,
+    hovered: Option<usize>,
+    position: Vector2D<i32>,
+    size: Vector2D<u32>,
+}
+
+/**
+ * An open menu along with its submenus
+ */
+pub struct Menu {
+    client: xlib::Window,
+    panes: Vec<Pane>,
+}
+
+impl Pane {
+    /**
+     * Checks if a root position is inside the pane
+     */
+    fn contains(&self, point: Vector2D<i32>) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < self.position.x + self.size.x as i32
+            && point.y < self.position.y + self.size.y as i32
+    }
+
+    /**
+     * Finds the entry at a root position
+     */
+    fn item_at(&self, point: Vector2D<i32>) -> Option<usize> {
+        let mut top = self.position.y as f64;
+
+        for (i, height) in self.heights.iter().enumerate() {
+            if (point.y as f64) >= top && (point.y as f64) < top + height {
+                return if self.items[i].is_separator() {
+                    None
+                } else {
+                    Some(i)
+                };
+            }
+            top += height;
+        }
+
+        None
+    }
+
+    /**
+     * Gets the vertical offset of an entry inside the pane
+     */
+    fn item_top(&self, index: usize) -> f64 {
+        self.heights[..index].iter().sum()
+    }
+}
+
+/**
+ * Creates and maps the popup for a list of entries, keeping it on the
+ * monitor it was opened on
+ */
+fn create_pane(_wm: &WindowManager, items: Vec<Item>, anchor: Vector2D<i32>, flip: i32) -> Pane {
+    unsafe {
+        let screen = xlib::XDefaultScreen(_wm.display);
+        let visual = xlib::XDefaultVisual(_wm.display, screen);
+        let depth = xlib::XDefaultDepth(_wm.display, screen);
+
+        let mut attributes: xlib::XSetWindowAttributes = zeroed();
+        attributes.override_redirect = xlib::True;
+        attributes.event_mask = xlib::ExposureMask;
+
+        let window = xlib::XCreateWindow(
+            _wm.display,
+            _wm.root,
+            anchor.x,
+            anchor.y,
+            1,
+            1,
+            0,
+            depth,
+            xlib::InputOutput as u32,
+            visual,
+            xlib::CWOverrideRedirect | xlib::CWEventMask,
+            &mut attributes,
+        );
+        name_window(_wm.display, window, "gothite-menu");
+
+        let surface = cairo_sys::cairo_xlib_surface_create(_wm.display, window, visual, 1, 1);
+        let context = cairo_sys::cairo_create(surface);
+
+        let line = _wm.font.height(context) + ITEM_PADDING * 2.0;
+        let heights: Vec<f64> = items
+            .iter()
+            .map(|item| {
+                if item.is_separator() {
+                    SEPARATOR_HEIGHT
+                } else {
+                    line
+                }
+            })
+            .collect();
+
+        let text = items
+            .iter()
+            .map(|item| _wm.font.text_width(context, &item.label))
+            .fold(0.0, f64::max);
+
+        let size = Vector2D::new(
+            (MARGIN * 2.0 + MARKER_WIDTH + text + ARROW_WIDTH).ceil() as u32,
+            heights.iter().sum::<f64>().ceil() as u32,
+        );
+
+        let monitor = &_wm.monitors[monitor::index_at(&_wm.monitors, anchor)];
+        let right = monitor.position.x + monitor.size.x as i32;
+        let bottom = monitor.position.y + monitor.size.y as i32;
+
+        let mut position = anchor;
+        if position.x + size.x as i32 > right {
+            position.x = flip - size.x as i32;
+        }
+        if position.y + size.y as i32 > bottom {
+            position.y = bottom - size.y as i32;
+        }
+        position.x = position.x.max(monitor.position.x);
+        position.y = position.y.max(monitor.position.y);
+
+        xlib::XMoveResizeWindow(_wm.display, window, position.x, position.y, size.x, size.y);
+        cairo_sys::cairo_xlib_surface_set_size(surface, size.x as i32, size.y as i32);
+        xlib::XMapRaised(_wm.display, window);
+
+        Pane {
+            window,
+            surface,
+            context,
+            items,
+            heights,
+            hovered: None,
+            position,
+            size,
+        }
+    }
+}
+
+/**
+ * Destroys a popup
+ */
+fn destroy_pane(_wm: &WindowManager, pane: Pane) {
+    unsafe {
+        cairo_sys::cairo_destroy(pane.context);
+        cairo_sys::cairo_surface_destroy(pane.surface);
+        xlib::XDestroyWindow(_wm.display, pane.window);
+    }
+}
+
+/**
+ * Renders a popup
+ */
+fn draw_pane(_wm: &WindowManager, pane: &Pane) {
+    let _ctx = pane.context;
+    let width = pane.size.x as f64;
+
+    unsafe {
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.231, 0.25, 0.322);
+        cairo_sys::cairo_paint(_ctx);
+
+        for (i, item) in pane.items.iter().enumerate() {
+            let top = pane.item_top(i);
+            let height = pane.heights[i];
+
+            if item.is_separator() {
+                cairo_sys::cairo_set_source_rgb(_ctx, 0.298, 0.337, 0.416);
+                cairo_sys::cairo_rectangle(
+                    _ctx,
+                    MARGIN,
+                    top + height / 2.0,
+                    width - MARGIN * 2.0,
+                    1.0,
+                );
+                cairo_sys::cairo_fill(_ctx);
+                continue;
+            }
+
+            if pane.hovered == Some(i) {
+                cairo_sys::cairo_set_source_rgb(_ctx, 0.298, 0.337, 0.416);
+                cairo_sys::cairo_rectangle(_ctx, 0.0, top, width, height);
+                cairo_sys::cairo_fill(_ctx);
+            }
+
+            cairo_sys::cairo_set_source_rgb(_ctx, 0.925, 0.937, 0.957);
+
+            let middle = top + height / 2.0;
+            if item.checked {
+                cairo_sys::cairo_new_path(_ctx);
+                cairo_sys::cairo_arc(
+                    _ctx,
+                    MARGIN + 4.0,
+                    middle,
+                    3.0,
+                    0.0,
+                    std::f64::consts::PI * 2.0,
+                );
+                cairo_sys::cairo_fill(_ctx);
+            }
+
+            if !item.submenu.is_empty() {
+                let x = width - MARGIN - 4.0;
+                cairo_sys::cairo_move_to(_ctx, x - 4.0, middle - 4.0);
+                cairo_sys::cairo_line_to(_ctx, x, middle);
+                cairo_sys::cairo_line_to(_ctx, x - 4.0, middle + 4.0);
+                cairo_sys::cairo_close_path(_ctx);
+                cairo_sys::cairo_fill(_ctx);
+            }
+
+            _wm.font
+                .draw_text(_ctx, &item.label, MARGIN + MARKER_WIDTH, top + ITEM_PADDING);
+        }
+
+        cairo_sys::cairo_surface_flush(pane.surface);
+    }
+}
+
+/**
+ * Highlights an entry, closing any submenus opened from the pane
+ */
+fn set_hovered(_wm: &WindowManager, menu: &mut Menu, level: usize, hovered: Option<usize>) {
+    for pane in menu.panes.drain(level + 1..) {
+        destroy_pane(_wm, pane);
+    }
+
+    let pane = &mut menu.panes[level];
+    pane.hovered = hovered;
+    draw_pane(_wm, pane);
+}
+
+/**
+ * Opens the submenu of an entry next to its pane
+ */
+fn open_submenu(_wm: &WindowManager, menu: &mut Menu, level: usize, index: usize) -> bool {
+    let pane = &menu.panes[level];
+    if pane.items[index].submenu.is_empty() {
+        return false;
+    }
+
+    let items = pane.items[index].submenu.clone();
+    let anchor = Vector2D::new(
+        pane.position.x + pane.size.x as i32,
+        pane.position.y + pane.item_top(index) as i32,
+    );
+    let flip = pane.position.x;
+
+    menu.panes.push(create_pane(_wm, items, anchor, flip));
+    true
+}
+
+/**
+ * Finds the next selectable entry of a pane in a direction, wrapping around
+ */
+fn step(pane: &Pane, forward: bool) -> Option<usize> {
+    let count = pane.items.len();
+    let mut index = pane.hovered;
+
+    for _i in 0..count {
+        let next = match (index, forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+        };
+
+        if !pane.items[next].is_separator() {
+            return Some(next);
+        }
+        index = Some(next);
+    }
+
+    None
+}
+
+/**
+ * Opens a menu for a client at a root position. Menus opened from the
+ * keyboard start with the first entry selected.
+ */
+pub fn open(
+    _wm: &mut WindowManager,
+    _w: xlib::Window,
+    items: Vec<Item>,
+    position: Vector2D<i32>,
+    keyboard: bool,
+) {
+    close(_wm);
+
+    let mut pane = create_pane(_wm, items, position, position.x);
+    if keyboard {
+        pane.hovered = step(&pane, true);
+    }
+
+    unsafe {
+        // Everything goes to the menu until it is dismissed
+        xlib::XGrabPointer(
+            _wm.display,
+            _wm.root,
+            xlib::False,
+            (xlib::ButtonPressMask | xlib::ButtonReleaseMask | xlib::PointerMotionMask) as u32,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            0,
+            0,
+            xlib::CurrentTime,
+        );
+
+        xlib::XGrabKeyboard(
+            _wm.display,
+            _wm.root,
+            xlib::False,
+            xlib::GrabModeAsync,
+            xlib::GrabModeAsync,
+            xlib::CurrentTime,
+        );
+    }
+
+    _wm.menu = Some(Menu {
+        client: _w,
+        panes: vec![pane],
+    });
+}
+
+/**
+ * Dismisses the open menu
+ */
+pub fn close(_wm: &mut WindowManager) {
+    if let Some(menu) = _wm.menu.take() {
+        for pane in menu.panes {
+            destroy_pane(_wm, pane);
+        }
+
+        unsafe {
+            xlib::XUngrabPointer(_wm.display, xlib::CurrentTime);
+            xlib::XUngrabKeyboard(_wm.display, xlib::CurrentTime);
+        }
+    }
+}
+
+/**
+ * Highlights the entry under the pointer and opens its submenu
+ */
+pub fn on_motion(_wm: &mut WindowManager, position: Vector2D<i32>) {
+    let mut menu = match _wm.menu.take() {
+        Some(menu) => menu,
+        None => return,
+    };
+
+    if let Some(level) = menu.panes.iter().rposition(|pane| pane.contains(position)) {
+        let hovered = menu.panes[level].item_at(position);
+
+        if menu.panes[level].hovered != hovered {
+            set_hovered(_wm, &mut menu, level, hovered);
+
+            if let Some(index) = hovered {
+                open_submenu(_wm, &mut menu, level, index);
+            }
+        }
+    }
+
+    _wm.menu = Some(menu);
+}
+
+/**
+ * Handles a click while the menu is open, giving back the picked action
+ * along with the client it applies to
+ */
+pub fn on_button_press(
+    _wm: &mut WindowManager,
+    position: Vector2D<i32>,
+) -> Option<(xlib::Window, Action)> {
+    let picked = {
+        let menu = _wm.menu.as_ref()?;
+        match menu.panes.iter().rposition(|pane| pane.contains(position)) {
+            Some(level) => {
+                let pane = &menu.panes[level];
+                match pane.item_at(position) {
+                    Some(index) => match pane.items[index].action {
+                        Some(action) => Some((menu.client, action)),
+                        None => return None,
+                    },
+                    None => return None,
+                }
+            }
+            None => None,
+        }
+    };
+
+    close(_wm);
+    picked
+}
+
+/**
+ * Navigates the menu with the arrow keys, giving back the action picked
+ * with Enter along with the client it applies to
+ */
+pub fn on_key_press(_wm: &mut WindowManager, sym: u32) -> Option<(xlib::Window, Action)> {
+    let mut menu = _wm.menu.take()?;
+    let level = menu.panes.len() - 1;
+    let hovered = menu.panes[level].hovered;
+    let mut picked = None;
+    let mut dismiss = false;
+
+    match sym {
+        keysym::XK_Up | keysym::XK_Down => {
+            let next = step(&menu.panes[level], sym == keysym::XK_Down);
+            set_hovered(_wm, &mut menu, level, next);
+        }
+        keysym::XK_Right | keysym::XK_Return | keysym::XK_KP_Enter | keysym::XK_space => {
+            if let Some(index) = hovered {
+                if open_submenu(_wm, &mut menu, level, index) {
+                    let pane = menu.panes.last_mut().unwrap();
+                    pane.hovered = step(pane, true);
+                } else if sym != keysym::XK_Right {
+                    picked = menu.panes[level].items[index].action;
+                    dismiss = picked.is_some();
+                }
+            }
+        }
+        keysym::XK_Left if level > 0 => {
+            let parent = menu.panes[level - 1].hovered;
+            set_hovered(_wm, &mut menu, level - 1, parent);
+        }
+        keysym::XK_Escape => dismiss = true,
+        _ => {}
+    }
+
+    let client = menu.client;
+    _wm.menu = Some(menu);
+
+    if dismiss {
+        close(_wm);
+    }
+
+    picked.map(|action| (client, action))
+}
+
+/**
+ * Redraws a popup after it was exposed
+ */
+pub fn on_expose(_wm: &WindowManager, window: xlib::Window) -> bool {
+    let menu = match _wm.menu.as_ref() {
+        Some(menu) => menu,
+        None => return false,
+    };
+
+    match menu.panes.iter().find(|pane| pane.window == window) {
+        Some(pane) => {
+            draw_pane(_wm, pane);
+            true
+        }
+        None => false,
+    }
+}
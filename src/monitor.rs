@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::mem::zeroed;
+use vector2d::Vector2D;
+use x11::{xlib, xrandr};
+
+/**
+ * A physical output, as reported by RandR
+ */
+pub struct Monitor {
+    pub name: String,
+    pub position: Vector2D<i32>,
+    pub size: Vector2D<u32>,
+}
+
+impl Monitor {
+    /**
+     * Checks if a point is inside the monitor
+     */
+    pub fn contains(&self, point: Vector2D<i32>) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < self.position.x + self.size.x as i32
+            && point.y < self.position.y + self.size.y as i32
+    }
+}
+
+/**
+ * Finds the monitor containing a point, defaulting to the first one
+ */
+pub fn index_at(monitors: &[Monitor], point: Vector2D<i32>) -> usize {
+    monitors
+        .iter()
+        .position(|monitor| monitor.contains(point))
+        .unwrap_or(0)
+}
+
+/**
+ * Cuts a monitor into side by side parts of equal width, the last one
+ * taking any leftover pixels
+ */
+fn split(monitor: Monitor, parts: u32) -> Vec<Monitor> {
+    let parts = parts.min(monitor.size.x).max(1);
+    if parts == 1 {
+        return vec![monitor];
+    }
+
+    let width = monitor.size.x / parts;
+    (0..parts)
+        .map(|part| Monitor {
+            name: format!("{} ({}/{})", monitor.name, part + 1, parts),
+            position: Vector2D::new(
+                monitor.position.x + (part * width) as i32,
+                monitor.position.y,
+            ),
+            size: Vector2D::new(
+                if part == parts - 1 {
+                    monitor.size.x - part * width
+                } else {
+                    width
+                },
+                monitor.size.y,
+            ),
+        })
+        .collect()
+}
+
+/**
+ * Lists the active monitors, falling back to the whole root window
+ * when RandR has nothing to say. Outputs listed in `splits` are cut into
+ * that many logical monitors.
+ */
+pub fn query(
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    splits: &HashMap<String, u32>,
+) -> Vec<Monitor> {
+    let mut monitors = Vec::new();
+
+    unsafe {
+        let mut count = 0;
+        let list = xrandr::XRRGetMonitors(display, root, xlib::True, &mut count);
+
+        if !list.is_null() {
+            for _i in 0..count as isize {
+                let info = &*list.offset(_i);
+                let name = xlib::XGetAtomName(display, info.name);
+                let label = if name.is_null() {
+                    format!("Monitor {}", _i + 1)
+                } else {
+                    let label = CStr::from_ptr(name).to_string_lossy().into_owned();
+                    xlib::XFree(name as *mut _);
+                    label
+                };
+
+                monitors.push(Monitor {
+                    name: label,
+                    position: Vector2D::new(info.x, info.y),
+                    size: Vector2D::new(info.width as u32, info.height as u32),
+                });
+            }
+
+            xrandr::XRRFreeMonitors(list);
+        }
+
+        if monitors.is_empty() {
+            let mut attrs: xlib::XWindowAttributes = zeroed();
+            xlib::XGetWindowAttributes(display, root, &mut attrs);
+
+            monitors.push(Monitor {
+                name: String::from("Screen"),
+                position: Vector2D::new(0, 0),
+                size: Vector2D::new(attrs.width as u32, attrs.height as u32),
+            });
+        }
+    }
+
+    let monitors: Vec<Monitor> = monitors
+        .into_iter()
+        .flat_map(|monitor| {
+            let parts = splits.get(&monitor.name).cloned().unwrap_or(1);
+            split(monitor, parts)
+        })
+        .collect();
+
+    debug!("Found {} monitor(s)", monitors.len());
+
+    monitors
+}
@@ -0,0 +1,393 @@
+use config::Config;
+use profiles;
+use std::slice;
+use std::time::Duration;
+use x11::xrandr;
+use {Timer, WindowManager};
+
+/**
+ * Color temperature in kelvin that leaves the colors as they are
+ */
+pub const NEUTRAL: u32 = 6500;
+
+/**
+ * Lowest and highest color temperatures that can be set
+ */
+pub const MIN_TEMPERATURE: u32 = 1000;
+pub const MAX_TEMPERATURE: u32 = 10000;
+
+/**
+ * How often the temperature follows the schedule
+ */
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/**
+ * Seconds in a day, after which the schedule starts over
+ */
+const DAY: u64 = 24 * 60 * 60;
+
+/**
+ * When the screen turns warmer, and how warm. Times are in seconds since
+ * midnight, and the temperature fades in and out over the given seconds.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    pub temperature: u32,
+    pub from: u64,
+    pub to: u64,
+    pub fade: u64,
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule {
+            temperature: 3500,
+            from: 20 * 60 * 60,
+            to: 7 * 60 * 60,
+            fade: 30 * 60,
+        }
+    }
+}
+
+impl Schedule {
+    /**
+     * Checks if a time of day is between the start and the end
+     */
+    fn is_night(&self, now: u64) -> bool {
+        if self.from <= self.to {
+            now >= self.from && now < self.to
+        } else {
+            now >= self.from || now < self.to
+        }
+    }
+
+    /**
+     * Gives the temperature reached by the end of the night, which falls
+     * short of the night one when the night is shorter than the fade
+     */
+    fn reached(&self) -> u32 {
+        let night = (self.to + DAY - self.from) % DAY;
+        if night >= self.fade {
+            return self.temperature;
+        }
+
+        blend(NEUTRAL, self.temperature, night as f64 / self.fade as f64)
+    }
+
+    /**
+     * Gives the temperature for a time of day, partway between the neutral
+     * one and the night one while fading
+     */
+    fn temperature_at(&self, now: u64) -> u32 {
+        let (start, from, to) = if self.is_night(now) {
+            (self.from, NEUTRAL, self.temperature)
+        } else {
+            (self.to, self.reached(), NEUTRAL)
+        };
+
+        let elapsed = (now + DAY - start) % DAY;
+        if elapsed >= self.fade {
+            return to;
+        }
+
+        blend(from, to, elapsed as f64 / self.fade as f64)
+    }
+}
+
+/**
+ * Gives the temperature partway from one to another
+ */
+fn blend(from: u32, to: u32, progress: f64) -> u32 {
+    (from as f64 + (to as f64 - from as f64) * progress).round() as u32
+}
+
+/**
+ * Color temperature set with gothite-msg, which lasts until the schedule
+ * turns to day or night
+ */
+struct Manual {
+    temperature: u32,
+    night: bool,
+}
+
+/**
+ * State of the color temperature of the screens
+ */
+#[derive(Default)]
+pub struct Nightlight {
+    manual: Option<Manual>,
+    applied: Option<u32>,
+}
+
+/**
+ * Parses a color temperature like "3500K" or "3500"
+ */
+pub fn parse_temperature(text: &str) -> Option<u32> {
+    let number = text.trim_end_matches(['K', 'k']);
+    number
+        .parse()
+        .ok()
+        .filter(|t| (MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(t))
+}
+
+/**
+ * Approximates the color of a black body at a temperature, each channel
+ * from 0 to 1
+ */
+fn black_body(kelvin: u32) -> [f64; 3] {
+    let t = kelvin as f64 / 100.0;
+
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (t - 60.0).powf(-0.1332047592)
+    };
+    let green = if t <= 66.0 {
+        99.4708025861 * t.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (t - 60.0).powf(-0.0755148492)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+
+    [red, green, blue].map(|c| (c / 255.0).clamp(0.0, 1.0))
+}
+
+/**
+ * Gives how much of each channel is kept at a temperature, relative to the
+ * neutral one so that it leaves the colors alone
+ */
+fn multipliers(kelvin: u32) -> [f64; 3] {
+    let color = black_body(kelvin);
+    let white = black_body(NEUTRAL);
+
+    [0, 1, 2].map(|i| (color[i] / white[i]).min(1.0))
+}
+
+/**
+ * Sets the gamma ramps of every CRTC for a color temperature
+ */
+fn set_gamma(_wm: &WindowManager, kelvin: u32) {
+    let [red, green, blue] = multipliers(kelvin);
+
+    unsafe {
+        let resources = xrandr::XRRGetScreenResourcesCurrent(_wm.display, _wm.root);
+        if resources.is_null() {
+            warn!("Could not get the screen resources for the night light");
+            return;
+        }
+
+        let crtcs = if (*resources).ncrtc > 0 {
+            slice::from_raw_parts((*resources).crtcs, (*resources).ncrtc as usize)
+        } else {
+            &[]
+        };
+
+        for &crtc in crtcs {
+            let size = xrandr::XRRGetCrtcGammaSize(_wm.display, crtc);
+            if size < 2 {
+                continue;
+            }
+
+            let gamma = xrandr::XRRAllocGamma(size);
+            if gamma.is_null() {
+                continue;
+            }
+
+            for i in 0..size as usize {
+                let level = i as f64 / (size - 1) as f64 * 65535.0;
+                *(*gamma).red.add(i) = (level * red) as u16;
+                *(*gamma).green.add(i) = (level * green) as u16;
+                *(*gamma).blue.add(i) = (level * blue) as u16;
+            }
+
+            xrandr::XRRSetCrtcGamma(_wm.display, crtc, gamma);
+            xrandr::XRRFreeGamma(gamma);
+        }
+
+        xrandr::XRRFreeScreenResources(resources);
+    }
+}
+
+/**
+ * Gives the temperature the schedule asks for now, neutral without one
+ */
+fn scheduled(config: &Config, now: u64) -> u32 {
+    config
+        .nightlight
+        .map_or(NEUTRAL, |schedule| schedule.temperature_at(now))
+}
+
+/**
+ * Checks if the schedule is in its night part now
+ */
+fn is_night(config: &Config, now: u64) -> bool {
+    config
+        .nightlight
+        .is_some_and(|schedule| schedule.is_night(now))
+}
+
+/**
+ * Brings the screens to the temperature set by hand or on the schedule.
+ * Gamma ramps are left alone until something other than neutral is asked
+ * for, so other tools can still set them.
+ */
+pub fn update(_wm: &mut WindowManager) {
+    let now = profiles::now();
+    let night = is_night(&_wm.config, now);
+
+    // Setting it by hand lasts until the schedule turns to day or night
+    if _wm
+        .nightlight
+        .manual
+        .as_ref()
+        .is_some_and(|m| m.night != night)
+    {
+        _wm.nightlight.manual = None;
+    }
+
+    let target = match _wm.nightlight.manual {
+        Some(ref manual) => manual.temperature,
+        None => scheduled(&_wm.config, now),
+    };
+
+    if _wm.nightlight.applied != Some(target)
+        && (_wm.nightlight.applied.is_some() || target != NEUTRAL)
+    {
+        debug!("Setting the color temperature to {}K", target);
+        set_gamma(_wm, target);
+        _wm.nightlight.applied = Some(target);
+    }
+
+    _wm.timers.cancel(&Timer::Nightlight);
+    if _wm.config.nightlight.is_some() {
+        _wm.timers.schedule(INTERVAL, Timer::Nightlight);
+    }
+}
+
+/**
+ * Sets the temperature by hand, or goes back to the schedule without one
+ */
+pub fn set(_wm: &mut WindowManager, temperature: Option<u32>) {
+    let night = is_night(&_wm.config, profiles::now());
+
+    info!(
+        "Night light set to {}",
+        temperature.map_or("the schedule".to_string(), |t| format!("{}K", t))
+    );
+    _wm.nightlight.manual = temperature.map(|temperature| Manual { temperature, night });
+    update(_wm);
+}
+
+/**
+ * Gives the temperature the screens are at
+ */
+pub fn current(_wm: &WindowManager) -> u32 {
+    _wm.nightlight.applied.unwrap_or(NEUTRAL)
+}
+
+/**
+ * Sets the gamma ramps again, for monitors that were just connected
+ */
+pub fn refresh(_wm: &WindowManager) {
+    if let Some(kelvin) = _wm.nightlight.applied {
+        set_gamma(_wm, kelvin);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60;
+    const MINUTE: u64 = 60;
+
+    #[test]
+    fn temperatures_are_parsed_within_bounds() {
+        assert_eq!(parse_temperature("3500K"), Some(3500));
+        assert_eq!(parse_temperature("3500k"), Some(3500));
+        assert_eq!(parse_temperature("3500"), Some(3500));
+        assert_eq!(parse_temperature("1000"), Some(MIN_TEMPERATURE));
+        assert_eq!(parse_temperature("10000K"), Some(MAX_TEMPERATURE));
+        assert_eq!(parse_temperature("999"), None);
+        assert_eq!(parse_temperature("10001"), None);
+        assert_eq!(parse_temperature("-3500"), None);
+        assert_eq!(parse_temperature("warm"), None);
+        assert_eq!(parse_temperature(""), None);
+    }
+
+    #[test]
+    fn the_night_fades_in_and_out() {
+        let schedule = Schedule::default();
+        assert_eq!(schedule.temperature_at(12 * HOUR), NEUTRAL);
+        assert_eq!(schedule.temperature_at(20 * HOUR), NEUTRAL);
+        assert_eq!(schedule.temperature_at(20 * HOUR + 15 * MINUTE), 5000);
+        assert_eq!(schedule.temperature_at(20 * HOUR + 30 * MINUTE), 3500);
+        assert_eq!(schedule.temperature_at(3 * HOUR), 3500);
+        assert_eq!(schedule.temperature_at(7 * HOUR), 3500);
+        assert_eq!(schedule.temperature_at(7 * HOUR + 15 * MINUTE), 5000);
+        assert_eq!(schedule.temperature_at(7 * HOUR + 30 * MINUTE), NEUTRAL);
+    }
+
+    #[test]
+    fn without_a_fade_the_temperature_switches_at_once() {
+        let schedule = Schedule {
+            fade: 0,
+            ..Schedule::default()
+        };
+        assert_eq!(schedule.temperature_at(20 * HOUR - 1), NEUTRAL);
+        assert_eq!(schedule.temperature_at(20 * HOUR), 3500);
+        assert_eq!(schedule.temperature_at(7 * HOUR - 1), 3500);
+        assert_eq!(schedule.temperature_at(7 * HOUR), NEUTRAL);
+    }
+
+    #[test]
+    fn a_night_shorter_than_the_fade_fades_back_from_where_it_got() {
+        let schedule = Schedule {
+            from: 20 * HOUR,
+            to: 20 * HOUR + 30 * MINUTE,
+            fade: HOUR,
+            ..Schedule::default()
+        };
+        assert_eq!(schedule.temperature_at(20 * HOUR + 15 * MINUTE), 5750);
+        assert_eq!(schedule.temperature_at(20 * HOUR + 30 * MINUTE - 1), 5001);
+        assert_eq!(schedule.temperature_at(20 * HOUR + 30 * MINUTE), 5000);
+        assert_eq!(schedule.temperature_at(21 * HOUR), 5750);
+        assert_eq!(schedule.temperature_at(21 * HOUR + 30 * MINUTE), NEUTRAL);
+    }
+
+    #[test]
+    fn an_empty_night_leaves_the_colors_alone() {
+        for fade in [0, 30 * MINUTE] {
+            let schedule = Schedule {
+                from: 20 * HOUR,
+                to: 20 * HOUR,
+                fade,
+                ..Schedule::default()
+            };
+            for now in [0, 20 * HOUR, 20 * HOUR + 10 * MINUTE, DAY - 1] {
+                assert!(!schedule.is_night(now));
+                assert_eq!(schedule.temperature_at(now), NEUTRAL);
+            }
+        }
+    }
+
+    #[test]
+    fn a_night_over_midnight_wraps_around() {
+        let schedule = Schedule {
+            from: 23 * HOUR,
+            to: HOUR,
+            fade: 0,
+            ..Schedule::default()
+        };
+        assert!(schedule.is_night(23 * HOUR));
+        assert!(schedule.is_night(0));
+        assert!(!schedule.is_night(HOUR));
+        assert!(!schedule.is_night(12 * HOUR));
+    }
+}
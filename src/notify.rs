@@ -0,0 +1,433 @@
+use cairo_sys;
+use dbus::{Connection, Message, Writer};
+use std::mem::zeroed;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+use vector2d::Vector2D;
+use x11::xlib;
+use {name_window, work_area, Timer, WindowManager};
+
+const NAME: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+
+const WIDTH: u32 = 320;
+const PADDING: f64 = 10.0;
+const GAP: i32 = 8;
+const MAX_LINES: usize = 4;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Reasons given in NotificationClosed
+const REASON_EXPIRED: u32 = 1;
+const REASON_DISMISSED: u32 = 2;
+const REASON_CLOSED: u32 = 3;
+
+/**
+ * A notification on screen
+ */
+struct Popup {
+    id: u32,
+    summary: String,
+    body: Vec<String>,
+    window: xlib::Window,
+    surface: *mut cairo_sys::cairo_surface_t,
+    context: *mut cairo_sys::cairo_t,
+    size: Vector2D<u32>,
+}
+
+/**
+ * The notification service, with the notifications it shows
+ */
+pub struct Notifications {
+    bus: Connection,
+    popups: Vec<Popup>,
+    next_id: u32,
+}
+
+impl Notifications {
+    /**
+     * Takes over the notification service on the session bus, which fails
+     * when another daemon already runs
+     */
+    pub fn start() -> Option<Notifications> {
+        let mut bus = Connection::session()?;
+        if !bus.request_name(NAME) {
+            return None;
+        }
+
+        Some(Notifications {
+            bus,
+            popups: Vec::new(),
+            next_id: 1,
+        })
+    }
+
+    /**
+     * Descriptor to wait on for requests
+     */
+    pub fn fd(&self) -> RawFd {
+        self.bus.fd()
+    }
+}
+
+/**
+ * Handles the requests that arrived on the bus
+ */
+pub fn on_bus(_wm: &mut WindowManager) {
+    let messages = match _wm.notifications.as_mut() {
+        Some(service) => service.bus.receive(),
+        None => return,
+    };
+
+    match messages {
+        Ok(messages) => {
+            for message in messages.into_iter().filter(|m| m.is_call()) {
+                on_call(_wm, message);
+            }
+        }
+        Err(_) => {
+            warn!("Lost the session bus, notifications are no longer shown");
+            stop(_wm);
+        }
+    }
+}
+
+/**
+ * Answers a method call of the notification interface
+ */
+fn on_call(_wm: &mut WindowManager, call: Message) {
+    let mut reply = Writer::new();
+
+    let signature = match call.member.as_str() {
+        "GetCapabilities" => {
+            reply.strings(&["body"]);
+            "as"
+        }
+        "GetServerInformation" => {
+            reply.string("gothite-wm");
+            reply.string("gothite");
+            reply.string(env!("CARGO_PKG_VERSION"));
+            reply.string("1.2");
+            "ssss"
+        }
+        "Notify" => match parse_notify(&call) {
+            Some((replaces, summary, body, timeout)) => {
+                reply.u32(show(_wm, replaces, summary, body, timeout));
+                "u"
+            }
+            None => {
+                let service = _wm.notifications.as_mut().unwrap();
+                service.bus.error(
+                    &call,
+                    "org.freedesktop.DBus.Error.InvalidArgs",
+                    "Malformed notification",
+                );
+                return;
+            }
+        },
+        "CloseNotification" => {
+            if let Some(id) = call.reader().u32() {
+                close(_wm, id, REASON_CLOSED);
+            }
+            ""
+        }
+        _ => {
+            let service = _wm.notifications.as_mut().unwrap();
+            service.bus.error(
+                &call,
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                &format!("No method {} on {}", call.member, PATH),
+            );
+            return;
+        }
+    };
+
+    if let Some(service) = _wm.notifications.as_mut() {
+        service.bus.reply(&call, signature, &reply.data);
+    }
+}
+
+/**
+ * Reads the arguments of Notify that are used: the id to replace, the
+ * summary, the body and the timeout
+ */
+fn parse_notify(call: &Message) -> Option<(u32, String, String, i32)> {
+    let mut reader = call.reader();
+    reader.string()?;
+    let replaces = reader.u32()?;
+    reader.string()?;
+    let summary = reader.string()?;
+    let text = reader.string()?;
+
+    // Actions are strings and hints are dict entries
+    reader.skip_array(4)?;
+    reader.skip_array(8)?;
+    let timeout = reader.i32()?;
+
+    Some((replaces, summary, text, timeout))
+}
+
+/**
+ * Shows a notification, or updates the one it replaces, and gives its id
+ */
+fn show(
+    _wm: &mut WindowManager,
+    replaces: u32,
+    summary: String,
+    text: String,
+    timeout: i32,
+) -> u32 {
+    let body: Vec<String> = text
+        .lines()
+        .take(MAX_LINES)
+        .map(|line| line.to_string())
+        .collect();
+
+    let existing = _wm
+        .notifications
+        .as_ref()
+        .unwrap()
+        .popups
+        .iter()
+        .position(|popup| replaces != 0 && popup.id == replaces);
+
+    let id = match existing {
+        Some(index) => {
+            let popup = &mut _wm.notifications.as_mut().unwrap().popups[index];
+            popup.summary = summary;
+            popup.body = body;
+            popup.id
+        }
+        None => {
+            let mut popup = create_popup(_wm, summary, body);
+            let service = _wm.notifications.as_mut().unwrap();
+            popup.id = service.next_id;
+            service.next_id += 1;
+            service.popups.push(popup);
+            service.next_id - 1
+        }
+    };
+
+    debug!("Showing notification {}", id);
+    layout(_wm);
+
+    match timeout {
+        0 => _wm.timers.cancel(&Timer::NotificationExpire(id)),
+        t if t < 0 => _wm
+            .timers
+            .schedule(DEFAULT_TIMEOUT, Timer::NotificationExpire(id)),
+        t => _wm.timers.schedule(
+            Duration::from_millis(t as u64),
+            Timer::NotificationExpire(id),
+        ),
+    }
+
+    id
+}
+
+/**
+ * Creates the window of a notification, sized later by the layout
+ */
+fn create_popup(_wm: &WindowManager, summary: String, body: Vec<String>) -> Popup {
+    unsafe {
+        let screen = xlib::XDefaultScreen(_wm.display);
+        let visual = xlib::XDefaultVisual(_wm.display, screen);
+        let depth = xlib::XDefaultDepth(_wm.display, screen);
+
+        let mut attributes: xlib::XSetWindowAttributes = zeroed();
+        attributes.override_redirect = xlib::True;
+        attributes.event_mask = xlib::ExposureMask | xlib::ButtonPressMask;
+
+        let window = xlib::XCreateWindow(
+            _wm.display,
+            _wm.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            depth,
+            xlib::InputOutput as u32,
+            visual,
+            xlib::CWOverrideRedirect | xlib::CWEventMask,
+            &mut attributes,
+        );
+        name_window(_wm.display, window, "gothite-notification");
+
+        let surface = cairo_sys::cairo_xlib_surface_create(_wm.display, window, visual, 1, 1);
+        let context = cairo_sys::cairo_create(surface);
+
+        Popup {
+            id: 0,
+            summary,
+            body,
+            window,
+            surface,
+            context,
+            size: Vector2D::new(WIDTH, 1),
+        }
+    }
+}
+
+/**
+ * Stacks the notifications down the top right corner of the first monitor,
+ * oldest first
+ */
+fn layout(_wm: &mut WindowManager) {
+    if _wm.monitors.is_empty() {
+        return;
+    }
+
+    let (origin, area) = work_area(_wm, 0);
+    let right = origin.x + area.x as i32 - GAP;
+    let mut top = origin.y + GAP;
+
+    let service = _wm.notifications.as_mut().unwrap();
+    for popup in service.popups.iter_mut() {
+        let line = _wm.font.height(popup.context) + PADDING / 2.0;
+        let height = PADDING * 2.0 + line * (1 + popup.body.len()) as f64;
+        popup.size = Vector2D::new(WIDTH, height.ceil() as u32);
+
+        unsafe {
+            xlib::XMoveResizeWindow(
+                _wm.display,
+                popup.window,
+                right - WIDTH as i32,
+                top,
+                popup.size.x,
+                popup.size.y,
+            );
+            cairo_sys::cairo_xlib_surface_set_size(
+                popup.surface,
+                popup.size.x as i32,
+                popup.size.y as i32,
+            );
+            xlib::XMapRaised(_wm.display, popup.window);
+        }
+
+        top += popup.size.y as i32 + GAP;
+    }
+
+    let service = _wm.notifications.as_ref().unwrap();
+    for popup in service.popups.iter() {
+        draw(_wm, popup);
+    }
+}
+
+/**
+ * Renders a notification
+ */
+fn draw(_wm: &WindowManager, popup: &Popup) {
+    let _ctx = popup.context;
+
+    unsafe {
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.231, 0.25, 0.322);
+        cairo_sys::cairo_paint(_ctx);
+
+        let line = _wm.font.height(_ctx) + PADDING / 2.0;
+
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.925, 0.937, 0.957);
+        _wm.font.draw_text(_ctx, &popup.summary, PADDING, PADDING);
+
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.847, 0.871, 0.914);
+        for (i, text) in popup.body.iter().enumerate() {
+            _wm.font
+                .draw_text(_ctx, text, PADDING, PADDING + line * (i + 1) as f64);
+        }
+
+        cairo_sys::cairo_surface_flush(popup.surface);
+    }
+}
+
+/**
+ * Takes a notification off the screen and tells its sender why
+ */
+pub fn close(_wm: &mut WindowManager, id: u32, reason: u32) {
+    _wm.timers.cancel(&Timer::NotificationExpire(id));
+
+    let service = match _wm.notifications.as_mut() {
+        Some(service) => service,
+        None => return,
+    };
+
+    let index = match service.popups.iter().position(|popup| popup.id == id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    let popup = service.popups.remove(index);
+    unsafe {
+        cairo_sys::cairo_destroy(popup.context);
+        cairo_sys::cairo_surface_destroy(popup.surface);
+        xlib::XDestroyWindow(_wm.display, popup.window);
+    }
+
+    let mut body = Writer::new();
+    body.u32(id);
+    body.u32(reason);
+    service
+        .bus
+        .signal(PATH, NAME, "NotificationClosed", "uu", &body.data);
+
+    layout(_wm);
+}
+
+/**
+ * Closes a notification once its time is up
+ */
+pub fn expire(_wm: &mut WindowManager, id: u32) {
+    close(_wm, id, REASON_EXPIRED);
+}
+
+/**
+ * Shuts the service down, taking every notification off the screen
+ */
+pub fn stop(_wm: &mut WindowManager) {
+    let ids: Vec<u32> = match _wm.notifications.as_ref() {
+        Some(service) => service.popups.iter().map(|popup| popup.id).collect(),
+        None => return,
+    };
+
+    for id in ids {
+        close(_wm, id, REASON_CLOSED);
+    }
+    _wm.notifications = None;
+}
+
+/**
+ * Redraws a notification after it was exposed
+ */
+pub fn on_expose(_wm: &WindowManager, window: xlib::Window) -> bool {
+    let service = match _wm.notifications.as_ref() {
+        Some(service) => service,
+        None => return false,
+    };
+
+    match service.popups.iter().find(|popup| popup.window == window) {
+        Some(popup) => {
+            draw(_wm, popup);
+            true
+        }
+        None => false,
+    }
+}
+
+/**
+ * Dismisses a notification that was clicked
+ */
+pub fn on_button_press(_wm: &mut WindowManager, window: xlib::Window) -> bool {
+    let id = _wm.notifications.as_ref().and_then(|service| {
+        service
+            .popups
+            .iter()
+            .find(|popup| popup.window == window)
+            .map(|popup| popup.id)
+    });
+
+    match id {
+        Some(id) => {
+            close(_wm, id, REASON_DISMISSED);
+            true
+        }
+        None => false,
+    }
+}
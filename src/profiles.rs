@@ -0,0 +1,154 @@
+use config::Config;
+use libc;
+use std::mem::zeroed;
+use std::ptr;
+use std::time::Duration;
+use toml::value::{Table, Value};
+use {reload_config, Timer, WindowManager};
+
+/**
+ * Seconds in a day, after which the schedule starts over
+ */
+const DAY: u64 = 24 * 60 * 60;
+
+/**
+ * A named set of options applied over the configuration, either at a time
+ * of day or when asked for with gothite-msg
+ */
+pub struct Profile {
+    pub name: String,
+    pub at: Option<u64>,
+    pub options: Value,
+}
+
+/**
+ * Parses a profile table, giving what went wrong with it otherwise
+ */
+pub fn parse(name: &str, table: &Table) -> Result<Profile, String> {
+    let mut options = table.clone();
+
+    let at = match options.remove("at") {
+        Some(value) => Some(
+            value
+                .as_str()
+                .and_then(parse_time)
+                .ok_or("'at' must be a time of day like \"07:30\"")?,
+        ),
+        None => None,
+    };
+
+    for key in ["version", "profiles"].iter() {
+        if options.contains_key(*key) {
+            return Err(format!("'{}' cannot be set by a profile", key));
+        }
+    }
+
+    Ok(Profile {
+        name: name.to_string(),
+        at,
+        options: Value::Table(options),
+    })
+}
+
+/**
+ * Parses a time of day as HH:MM into seconds since midnight
+ */
+pub fn parse_time(text: &str) -> Option<u64> {
+    let (hours, minutes) = text.split_once(':')?;
+    let hours: u64 = hours.parse().ok()?;
+    let minutes: u64 = minutes.parse().ok()?;
+
+    if hours < 24 && minutes < 60 {
+        Some(hours * 3600 + minutes * 60)
+    } else {
+        None
+    }
+}
+
+/**
+ * Gives the local time of day in seconds since midnight. A leap second
+ * counts as the last second of the day, so it is always less than a day.
+ */
+pub fn now() -> u64 {
+    unsafe {
+        let now = libc::time(ptr::null_mut());
+        let mut local: libc::tm = zeroed();
+        libc::localtime_r(&now, &mut local);
+
+        ((local.tm_hour * 3600 + local.tm_min * 60 + local.tm_sec) as u64).min(DAY - 1)
+    }
+}
+
+/**
+ * Finds the profile whose time came last, which may have been yesterday
+ */
+pub fn scheduled(config: &Config) -> Option<String> {
+    let now = now();
+    let timed = config.profiles.iter().filter(|p| p.at.is_some());
+
+    timed
+        .clone()
+        .filter(|p| p.at <= Some(now))
+        .max_by_key(|p| p.at)
+        .or_else(|| timed.max_by_key(|p| p.at))
+        .map(|p| p.name.clone())
+}
+
+/**
+ * Sets up the timer for the next profile on the schedule
+ */
+pub fn schedule(_wm: &mut WindowManager) {
+    _wm.timers.cancel(&Timer::Profile);
+
+    let now = now();
+    let next = _wm
+        .config
+        .profiles
+        .iter()
+        .filter_map(|p| p.at)
+        .map(|at| (at + DAY - now - 1) % DAY + 1)
+        .min();
+
+    if let Some(delay) = next {
+        debug!("Next profile in {}s", delay);
+        _wm.timers
+            .schedule(Duration::from_secs(delay), Timer::Profile);
+    }
+}
+
+/**
+ * Switches to the profile that is due
+ */
+pub fn on_timeout(_wm: &mut WindowManager) {
+    if let Some(name) = scheduled(&_wm.config) {
+        if _wm.profile.as_ref() != Some(&name) {
+            let _ = switch(_wm, &name);
+        }
+    }
+    schedule(_wm);
+}
+
+/**
+ * Applies a profile over the configuration file, giving the problems found
+ * in either, or why it could not be applied
+ */
+pub fn switch(_wm: &mut WindowManager, name: &str) -> Result<Vec<String>, String> {
+    if !_wm.config.profiles.iter().any(|p| p.name == name) {
+        return Err(format!("no profile {}", name));
+    }
+
+    info!("Switching to profile {}", name);
+    _wm.profile = Some(name.to_string());
+    reload_config(_wm)
+}
+
+/**
+ * Picks the profile to apply to a freshly read configuration: the one in
+ * use if it is still there, or else the one on the schedule
+ */
+pub fn pick(config: &Config, current: Option<&String>) -> Option<String> {
+    current
+        .filter(|name| config.profiles.iter().any(|p| p.name == **name))
+        .cloned()
+        .or_else(|| scheduled(config))
+}
@@ -0,0 +1,281 @@
+use config;
+use ipc::Command;
+use regex::Regex;
+use toml::value::{Table, Value};
+use vector2d::Vector2D;
+use workspaces;
+use x11::xlib;
+use {get_title, set_atom_list, Candidate, WindowManager};
+
+/**
+ * What a rule does to the windows it matches
+ */
+#[derive(Clone, Default)]
+pub struct Actions {
+    pub workspace: Option<String>,
+    pub decorated: Option<bool>,
+    pub geometry: Option<(Vector2D<i32>, Vector2D<u32>)>,
+    pub above: Option<bool>,
+    pub click_through: Option<bool>,
+    pub follow: Option<bool>,
+    pub floating: Option<bool>,
+}
+
+/**
+ * A rule from the configuration, matching new windows by patterns on their
+ * WM_CLASS and title
+ */
+pub struct Rule {
+    pub class: Option<Regex>,
+    pub instance: Option<Regex>,
+    pub title: Option<Regex>,
+    pub actions: Actions,
+    pub source: Table,
+}
+
+impl Rule {
+    /**
+     * Checks if every pattern of the rule matches a window
+     */
+    pub fn matches(&self, instance: &str, class: &str, title: &str) -> bool {
+        let check = |pattern: &Option<Regex>, text: &str| {
+            pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(text))
+        };
+
+        check(&self.class, class) && check(&self.instance, instance) && check(&self.title, title)
+    }
+
+    /**
+     * Writes the rule on one line the way it is written in the configuration
+     */
+    pub fn describe(&self) -> String {
+        self.source
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, value))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/**
+ * Parses a rule table, giving what went wrong with it otherwise
+ */
+pub fn parse(table: &Table) -> Result<Rule, String> {
+    let mut rule = Rule {
+        class: None,
+        instance: None,
+        title: None,
+        actions: Actions::default(),
+        source: table.clone(),
+    };
+
+    for (key, value) in table {
+        match key.as_str() {
+            "class" | "instance" | "title" => {
+                let pattern = value
+                    .as_str()
+                    .ok_or_else(|| format!("'{}' must be a string", key))?;
+                let regex = Regex::new(pattern)
+                    .map_err(|err| format!("Invalid pattern for '{}': {}", key, err))?;
+
+                match key.as_str() {
+                    "class" => rule.class = Some(regex),
+                    "instance" => rule.instance = Some(regex),
+                    _ => rule.title = Some(regex),
+                }
+            }
+            "workspace" => {
+                rule.actions.workspace = Some(
+                    value
+                        .as_str()
+                        .ok_or("'workspace' must be a workspace name")?
+                        .to_string(),
+                );
+            }
+            "decorated" => {
+                rule.actions.decorated =
+                    Some(value.as_bool().ok_or("'decorated' must be a boolean")?);
+            }
+            "above" => {
+                rule.actions.above = Some(value.as_bool().ok_or("'above' must be a boolean")?);
+            }
+            "follow" => {
+                rule.actions.follow = Some(value.as_bool().ok_or("'follow' must be a boolean")?);
+            }
+            "floating" => {
+                rule.actions.floating =
+                    Some(value.as_bool().ok_or("'floating' must be a boolean")?);
+            }
+            "click_through" => {
+                rule.actions.click_through =
+                    Some(value.as_bool().ok_or("'click_through' must be a boolean")?);
+            }
+            "geometry" => {
+                let numbers: Option<Vec<i64>> = value
+                    .as_array()
+                    .map(|array| array.iter().map(|n| n.as_integer()).collect())
+                    .unwrap_or(None);
+
+                match numbers.as_deref() {
+                    Some(&[x, y, w, h]) if w > 0 && h > 0 => {
+                        rule.actions.geometry = Some((
+                            Vector2D::new(x as i32, y as i32),
+                            Vector2D::new(w as u32, h as u32),
+                        ));
+                    }
+                    _ => return Err("'geometry' must be [x, y, width, height]".to_string()),
+                }
+            }
+            _ => return Err(format!("Unknown option '{}'", key)),
+        }
+    }
+
+    Ok(rule)
+}
+
+/**
+ * Builds a rule table from KEY=VALUE options given on the command line.
+ * Booleans and arrays are written like in the configuration, anything else
+ * is taken as a string so patterns need no quoting.
+ */
+fn from_fields(fields: &[(String, String)]) -> Table {
+    let mut table = Table::new();
+
+    for (key, text) in fields {
+        let value = match text.as_str() {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ if text.starts_with('[') => format!("value = {}", text)
+                .parse::<Value>()
+                .ok()
+                .and_then(|parsed| parsed.get("value").cloned())
+                .unwrap_or_else(|| Value::String(text.clone())),
+            _ => Value::String(text.clone()),
+        };
+
+        table.insert(key.clone(), value);
+    }
+
+    table
+}
+
+/**
+ * Handle a rule command sent through gothite-msg, giving the reply
+ */
+pub fn on_request(_wm: &mut WindowManager, command: &Command) -> String {
+    match *command {
+        Command::AddRule(ref fields, save) => {
+            let table = from_fields(fields);
+            let rule = match parse(&table) {
+                Ok(rule) => rule,
+                Err(err) => return format!("error: {}", err),
+            };
+
+            info!("Added rule {}", rule.describe());
+            _wm.config.rules.push(rule);
+
+            if save {
+                if let Err(err) = config::append_rule(&table) {
+                    return format!("error: rule added but not saved: {}", err);
+                }
+            }
+            String::new()
+        }
+        Command::RemoveRule(index) => {
+            if index > _wm.config.rules.len() {
+                return format!("error: there is no rule {}", index);
+            }
+
+            let rule = _wm.config.rules.remove(index - 1);
+            info!("Removed rule {}", rule.describe());
+            String::new()
+        }
+        Command::ListRules => _wm
+            .config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| format!("{}: {}", i + 1, rule.describe()))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/**
+ * Applies the rules matching a new window to what is known about it, before
+ * it gets a frame. Later rules override earlier ones. Workspaces that do not
+ * exist yet are created.
+ */
+pub fn apply(_wm: &mut WindowManager, candidate: &mut Candidate) {
+    if _wm.config.rules.is_empty() {
+        return;
+    }
+
+    let instance = candidate.class.first().cloned().unwrap_or_default();
+    let class = candidate.class.get(1).cloned().unwrap_or_default();
+    let title = get_title(_wm, candidate.window);
+
+    for i in 0.._wm.config.rules.len() {
+        if !_wm.config.rules[i].matches(&instance, &class, &title) {
+            continue;
+        }
+
+        debug!("Rule {} matches {}", i + 1, candidate.window);
+
+        let actions = _wm.config.rules[i].actions.clone();
+        apply_actions(_wm, candidate, &actions);
+    }
+}
+
+/**
+ * Applies what a rule or an autostart entry does to a new window
+ */
+pub fn apply_actions(_wm: &mut WindowManager, candidate: &mut Candidate, actions: &Actions) {
+    if let Some(ref name) = actions.workspace {
+        candidate.desktop = Some(workspaces::find_or_create(_wm, name));
+    }
+
+    if let Some(decorated) = actions.decorated {
+        candidate.decorated = decorated;
+    }
+
+    if let Some((position, size)) = actions.geometry {
+        candidate.attrs.x = position.x;
+        candidate.attrs.y = position.y;
+        candidate.attrs.width = size.x as i32;
+        candidate.attrs.height = size.y as i32;
+
+        unsafe {
+            xlib::XResizeWindow(_wm.display, candidate.window, size.x, size.y);
+        }
+    }
+
+    if let Some(follow) = actions.follow {
+        candidate.follow = follow;
+    }
+
+    if let Some(floating) = actions.floating {
+        candidate.floating = floating;
+    }
+
+    if let Some(click_through) = actions.click_through {
+        candidate.click_through = click_through;
+    }
+
+    if let Some(above) = actions.above {
+        let atom = _wm.atoms.net_wm_state_above;
+        candidate.states.retain(|a| *a != atom);
+        if above {
+            candidate.states.push(atom);
+        }
+        set_atom_list(
+            _wm,
+            candidate.window,
+            _wm.atoms.net_wm_state,
+            &candidate.states,
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::mem::zeroed;
+use std::os::raw::{c_int, c_uint, c_ulong};
+use vector2d::Vector2D;
+use x11::xlib;
+
+// From X11/extensions/shape.h, which the x11 crate does not cover
+const SHAPE_SET: c_int = 0;
+const SHAPE_UNION: c_int = 1;
+const SHAPE_BOUNDING: c_int = 0;
+const SHAPE_INPUT: c_int = 2;
+const SHAPE_NOTIFY_MASK: c_ulong = 1;
+const SHAPE_NOTIFY: c_int = 0;
+const UNSORTED: c_int = 0;
+
+/**
+ * Sent when the shape of a selected window changes
+ */
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct XShapeEvent {
+    pub type_: c_int,
+    pub serial: c_ulong,
+    pub send_event: xlib::Bool,
+    pub display: *mut xlib::Display,
+    pub window: xlib::Window,
+    pub kind: c_int,
+    pub x: c_int,
+    pub y: c_int,
+    pub width: c_uint,
+    pub height: c_uint,
+    pub time: xlib::Time,
+    pub shaped: xlib::Bool,
+}
+
+#[link(name = "Xext")]
+extern "C" {
+    fn XShapeQueryExtension(
+        display: *mut xlib::Display,
+        event_base: *mut c_int,
+        error_base: *mut c_int,
+    ) -> xlib::Bool;
+    fn XShapeSelectInput(display: *mut xlib::Display, window: xlib::Window, mask: c_ulong);
+    fn XShapeQueryExtents(
+        display: *mut xlib::Display,
+        window: xlib::Window,
+        bounding_shaped: *mut xlib::Bool,
+        x_bounding: *mut c_int,
+        y_bounding: *mut c_int,
+        w_bounding: *mut c_uint,
+        h_bounding: *mut c_uint,
+        clip_shaped: *mut xlib::Bool,
+        x_clip: *mut c_int,
+        y_clip: *mut c_int,
+        w_clip: *mut c_uint,
+        h_clip: *mut c_uint,
+    ) -> xlib::Status;
+    fn XShapeCombineShape(
+        display: *mut xlib::Display,
+        dest: xlib::Window,
+        dest_kind: c_int,
+        x_off: c_int,
+        y_off: c_int,
+        src: xlib::Window,
+        src_kind: c_int,
+        op: c_int,
+    );
+    fn XShapeCombineRectangles(
+        display: *mut xlib::Display,
+        dest: xlib::Window,
+        dest_kind: c_int,
+        x_off: c_int,
+        y_off: c_int,
+        rectangles: *mut xlib::XRectangle,
+        count: c_int,
+        op: c_int,
+        ordering: c_int,
+    );
+    fn XShapeCombineMask(
+        display: *mut xlib::Display,
+        dest: xlib::Window,
+        dest_kind: c_int,
+        x_off: c_int,
+        y_off: c_int,
+        src: xlib::Pixmap,
+        op: c_int,
+    );
+}
+
+/**
+ * Checks for the Shape extension, giving the type of its notify event
+ */
+pub fn query(display: *mut xlib::Display) -> Option<c_int> {
+    let (mut event_base, mut error_base) = (0, 0);
+
+    match unsafe { XShapeQueryExtension(display, &mut event_base, &mut error_base) } {
+        0 => None,
+        _ => Some(event_base + SHAPE_NOTIFY),
+    }
+}
+
+/**
+ * Asks to be told when the shape of a window changes
+ */
+pub fn select(display: *mut xlib::Display, window: xlib::Window) {
+    unsafe {
+        XShapeSelectInput(display, window, SHAPE_NOTIFY_MASK);
+    }
+}
+
+/**
+ * Checks if a window has a bounding shape other than its rectangle
+ */
+pub fn is_shaped(display: *mut xlib::Display, window: xlib::Window) -> bool {
+    unsafe {
+        let mut shaped = 0;
+        let mut clip_shaped = 0;
+        let (mut x, mut y, mut w, mut h) = (0, 0, 0, 0);
+        let (mut cx, mut cy, mut cw, mut ch) = (0, 0, 0, 0);
+
+        let status = XShapeQueryExtents(
+            display,
+            window,
+            &mut shaped,
+            &mut x,
+            &mut y,
+            &mut w,
+            &mut h,
+            &mut clip_shaped,
+            &mut cx,
+            &mut cy,
+            &mut cw,
+            &mut ch,
+        );
+
+        status != 0 && shaped != 0
+    }
+}
+
+/**
+ * Checks if a shape event is about the bounding shape, the one that decides
+ * what is visible
+ */
+pub fn is_bounding(_e: &XShapeEvent) -> bool {
+    _e.kind == SHAPE_BOUNDING
+}
+
+/**
+ * Reads a shape event out of a generic event
+ */
+pub fn event(ev: &xlib::XEvent) -> XShapeEvent {
+    unsafe { *(ev as *const xlib::XEvent as *const XShapeEvent) }
+}
+
+/**
+ * Shapes a frame like its client, keeping a band of the given height along
+ * the top for the titlebar. The client sits at the given offset.
+ */
+pub fn copy(
+    display: *mut xlib::Display,
+    frame: xlib::Window,
+    client: xlib::Window,
+    offset: (i32, i32),
+    titlebar: (u32, u32),
+) {
+    unsafe {
+        let mut band: xlib::XRectangle = zeroed();
+        band.width = titlebar.0 as u16;
+        band.height = titlebar.1 as u16;
+
+        XShapeCombineRectangles(
+            display,
+            frame,
+            SHAPE_BOUNDING,
+            0,
+            0,
+            &mut band,
+            1,
+            SHAPE_SET,
+            UNSORTED,
+        );
+        XShapeCombineShape(
+            display,
+            frame,
+            SHAPE_BOUNDING,
+            offset.0,
+            offset.1,
+            client,
+            SHAPE_BOUNDING,
+            SHAPE_UNION,
+        );
+    }
+}
+
+/**
+ * Makes a frame and its client let every click through to the windows below,
+ * by giving it an empty input shape, or takes the input shape back off
+ */
+pub fn set_click_through(display: *mut xlib::Display, frame: xlib::Window, enable: bool) {
+    unsafe {
+        if enable {
+            XShapeCombineRectangles(
+                display,
+                frame,
+                SHAPE_INPUT,
+                0,
+                0,
+                std::ptr::null_mut(),
+                0,
+                SHAPE_SET,
+                UNSORTED,
+            );
+        } else {
+            XShapeCombineMask(display, frame, SHAPE_INPUT, 0, 0, 0, SHAPE_SET);
+        }
+    }
+}
+
+/**
+ * Cuts a window down to an outline of the given width along its edges
+ */
+pub fn set_outline(
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    size: Vector2D<u32>,
+    width: u32,
+) {
+    let rectangle = |x: u32, y: u32, w: u32, h: u32| xlib::XRectangle {
+        x: x as i16,
+        y: y as i16,
+        width: w as u16,
+        height: h as u16,
+    };
+    let width = width.min(size.x / 2).min(size.y / 2);
+    let mut rectangles = [
+        rectangle(0, 0, size.x, width),
+        rectangle(0, size.y - width, size.x, width),
+        rectangle(0, 0, width, size.y),
+        rectangle(size.x - width, 0, width, size.y),
+    ];
+
+    unsafe {
+        XShapeCombineRectangles(
+            display,
+            window,
+            SHAPE_BOUNDING,
+            0,
+            0,
+            rectangles.as_mut_ptr(),
+            rectangles.len() as c_int,
+            SHAPE_SET,
+            UNSORTED,
+        );
+    }
+}
+
+/**
+ * Makes a frame rectangular again
+ */
+pub fn clear(display: *mut xlib::Display, frame: xlib::Window) {
+    unsafe {
+        XShapeCombineMask(display, frame, SHAPE_BOUNDING, 0, 0, 0, SHAPE_SET);
+    }
+}
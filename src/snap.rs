@@ -0,0 +1,244 @@
+use decoration;
+use layout::Area;
+use monitor;
+use shape;
+use std::mem::zeroed;
+use vector2d::Vector2D;
+use x11::xlib;
+use {
+    client_size, extents, name_window, resize_client, set_maximized, tile_window, work_area,
+    WindowManager,
+};
+
+/**
+ * Color of the preview of where a dropped window goes
+ */
+const PREVIEW_COLOR: u64 = 0x88C0D0;
+
+/**
+ * Opacity of the preview for compositors, out of 0xFFFFFFFF
+ */
+const PREVIEW_OPACITY: u64 = 0x60000000;
+
+/**
+ * Width of the outline drawn as the preview when nothing composites it
+ */
+const OUTLINE_WIDTH: u32 = 4;
+
+/**
+ * Part of the work area a window dropped on an edge or corner of a monitor
+ * takes
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Zone {
+    Maximize,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/**
+ * The rectangle showing where a dragged window would go if dropped now
+ */
+pub struct Preview {
+    window: xlib::Window,
+    area: Area,
+}
+
+/**
+ * Finds the zone under the pointer: the top edge maximizes, the left and
+ * right edges take a half and the corners a quarter
+ */
+fn zone_at(_wm: &WindowManager, pointer: Vector2D<i32>) -> Option<(Zone, usize)> {
+    let index = monitor::index_at(&_wm.monitors, pointer);
+    let monitor = &_wm.monitors[index];
+    let corner = decoration::metrics(&_wm.config).edge_corner;
+    let offset = pointer - monitor.position;
+    let size = monitor.size.as_i32s();
+
+    let (left, right) = (offset.x <= 0, offset.x >= size.x - 1);
+    let (top, bottom) = (offset.y <= 0, offset.y >= size.y - 1);
+    let (near_left, near_right) = (offset.x < corner, offset.x >= size.x - corner);
+    let (near_top, near_bottom) = (offset.y < corner, offset.y >= size.y - corner);
+
+    let zone = if (top && near_left) || (left && near_top) {
+        Zone::TopLeft
+    } else if (top && near_right) || (right && near_top) {
+        Zone::TopRight
+    } else if (bottom && near_left) || (left && near_bottom) {
+        Zone::BottomLeft
+    } else if (bottom && near_right) || (right && near_bottom) {
+        Zone::BottomRight
+    } else if top {
+        Zone::Maximize
+    } else if left {
+        Zone::Left
+    } else if right {
+        Zone::Right
+    } else {
+        return None;
+    };
+
+    Some((zone, index))
+}
+
+/**
+ * Gives the part of the work area of a monitor a zone covers
+ */
+fn zone_area(_wm: &WindowManager, zone: Zone, monitor: usize) -> Area {
+    let (origin, size) = work_area(_wm, monitor);
+    let half = Vector2D::new(size.x / 2, size.y / 2);
+    let middle = origin + half.as_i32s();
+    let rest = size - half;
+
+    match zone {
+        Zone::Maximize => (origin, size),
+        Zone::Left => (origin, Vector2D::new(half.x, size.y)),
+        Zone::Right => (
+            Vector2D::new(middle.x, origin.y),
+            Vector2D::new(rest.x, size.y),
+        ),
+        Zone::TopLeft => (origin, half),
+        Zone::TopRight => (
+            Vector2D::new(middle.x, origin.y),
+            Vector2D::new(rest.x, half.y),
+        ),
+        Zone::BottomLeft => (
+            Vector2D::new(origin.x, middle.y),
+            Vector2D::new(half.x, rest.y),
+        ),
+        Zone::BottomRight => (middle, rest),
+    }
+}
+
+/**
+ * Checks if a compositor is running, which can show the preview translucent
+ */
+fn composited(_wm: &WindowManager) -> bool {
+    unsafe { xlib::XGetSelectionOwner(_wm.display, _wm.atoms.net_wm_cm_s0) != 0 }
+}
+
+/**
+ * Creates the preview window. It lets clicks through, and without a
+ * compositor only its outline is shown so it hides nothing.
+ */
+fn create_preview(_wm: &WindowManager) -> xlib::Window {
+    unsafe {
+        let mut attributes: xlib::XSetWindowAttributes = zeroed();
+        attributes.override_redirect = xlib::True;
+        attributes.background_pixel = PREVIEW_COLOR;
+
+        let window = xlib::XCreateWindow(
+            _wm.display,
+            _wm.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            xlib::CopyFromParent,
+            xlib::InputOutput as u32,
+            std::ptr::null_mut(),
+            xlib::CWOverrideRedirect | xlib::CWBackPixel,
+            &mut attributes,
+        );
+        name_window(_wm.display, window, "gothite-snap");
+        shape::set_click_through(_wm.display, window, true);
+
+        let opacity = PREVIEW_OPACITY;
+        xlib::XChangeProperty(
+            _wm.display,
+            window,
+            _wm.atoms.net_wm_window_opacity,
+            xlib::XA_CARDINAL,
+            32,
+            xlib::PropModeReplace,
+            &opacity as *const u64 as *const u8,
+            1,
+        );
+
+        window
+    }
+}
+
+/**
+ * Shows where a window dragged by its titlebar would go if dropped with
+ * the pointer where it is, or hides the preview away from the edges
+ */
+pub fn update(_wm: &mut WindowManager, pointer: Vector2D<i32>) {
+    let area = match zone_at(_wm, pointer) {
+        Some((zone, monitor)) => zone_area(_wm, zone, monitor),
+        None => {
+            hide(_wm);
+            return;
+        }
+    };
+
+    if _wm.snap_preview.as_ref().is_some_and(|p| p.area == area) {
+        return;
+    }
+
+    let window = match _wm.snap_preview {
+        Some(ref preview) => preview.window,
+        None => create_preview(_wm),
+    };
+    _wm.snap_preview = Some(Preview { window, area });
+
+    let (position, size) = area;
+    unsafe {
+        xlib::XMoveResizeWindow(_wm.display, window, position.x, position.y, size.x, size.y);
+        if !composited(_wm) {
+            shape::set_outline(_wm.display, window, size, OUTLINE_WIDTH);
+        } else {
+            shape::clear(_wm.display, window);
+        }
+        xlib::XMapRaised(_wm.display, window);
+    }
+}
+
+/**
+ * Takes the preview down
+ */
+pub fn hide(_wm: &mut WindowManager) {
+    if let Some(preview) = _wm.snap_preview.take() {
+        unsafe {
+            xlib::XDestroyWindow(_wm.display, preview.window);
+        }
+    }
+}
+
+/**
+ * Fits a window dropped on an edge or corner of a monitor into its zone.
+ * Maximized and half tiled windows get their old geometry back when
+ * restored, quarters are placed like a move.
+ */
+pub fn drop_on_edge(_wm: &mut WindowManager, _w: xlib::Window, pointer: Vector2D<i32>) {
+    let (zone, monitor) = match zone_at(_wm, pointer) {
+        Some(found) => found,
+        None => return,
+    };
+
+    debug!("Dropped {} in {:?}", _w, zone);
+    match zone {
+        Zone::Maximize => set_maximized(_wm, _w, true, true),
+        Zone::Left => tile_window(_wm, _w, true),
+        Zone::Right => tile_window(_wm, _w, false),
+        _ => {
+            set_maximized(_wm, _w, false, false);
+
+            let win = match _wm.windows.get(&_w) {
+                Some(win) if !win.fullscreen => win,
+                _ => return,
+            };
+            let (position, size) = zone_area(_wm, zone, monitor);
+            resize_client(_wm, _w, win, client_size(extents(win), size));
+
+            unsafe {
+                xlib::XMoveWindow(_wm.display, win.frame, position.x, position.y);
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use x11::xlib;
+
+/**
+ * A startup notification message
+ */
+pub struct Message {
+    pub kind: String,
+    pub values: HashMap<String, String>,
+}
+
+/**
+ * Reassembles startup notification messages, which arrive split
+ * over several 20 byte ClientMessages
+ */
+pub struct Assembler {
+    buffers: HashMap<xlib::Window, Vec<u8>>,
+}
+
+impl Assembler {
+    pub fn new() -> Assembler {
+        Assembler {
+            buffers: HashMap::new(),
+        }
+    }
+
+    /**
+     * Adds a message chunk, returning the full message once it is complete
+     */
+    pub fn feed(&mut self, _e: &xlib::XClientMessageEvent, begin: bool) -> Option<Message> {
+        if begin {
+            self.buffers.insert(_e.window, Vec::new());
+        }
+
+        let buffer = self.buffers.get_mut(&_e.window)?;
+        for _i in 0..20 {
+            let byte = _e.data.get_byte(_i) as u8;
+            if byte == 0 {
+                let message = String::from_utf8_lossy(buffer).into_owned();
+                self.buffers.remove(&_e.window);
+                return parse(&message);
+            }
+
+            buffer.push(byte);
+        }
+
+        None
+    }
+}
+
+/**
+ * Parses a "kind: KEY=VALUE KEY="QUOTED VALUE"" message
+ */
+pub fn parse(message: &str) -> Option<Message> {
+    let colon = message.find(':')?;
+    let kind = message[..colon].trim().to_string();
+    let mut values = HashMap::new();
+
+    let mut chars = message[colon + 1..].chars().peekable();
+    loop {
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        if key.is_empty() {
+            break;
+        }
+
+        let mut value = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' => value.extend(chars.next()),
+                ' ' if !quoted => break,
+                _ => value.push(c),
+            }
+        }
+
+        values.insert(key, value);
+    }
+
+    Some(Message { kind, values })
+}
@@ -0,0 +1,100 @@
+use vector2d::Vector2D;
+
+/**
+ * Space a panel reserves along the screen edges, with the span of each edge
+ * it covers
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Strut {
+    pub left: i64,
+    pub right: i64,
+    pub top: i64,
+    pub bottom: i64,
+    pub left_span: (i64, i64),
+    pub right_span: (i64, i64),
+    pub top_span: (i64, i64),
+    pub bottom_span: (i64, i64),
+}
+
+impl Strut {
+    /**
+     * Reads the values of _NET_WM_STRUT_PARTIAL, or of the older
+     * _NET_WM_STRUT which always covers whole edges
+     */
+    pub fn parse(values: &[i64]) -> Option<Strut> {
+        let whole = (0, i64::MAX);
+        let span = |start: usize| {
+            if values.len() >= 12 {
+                (values[start], values[start + 1])
+            } else {
+                whole
+            }
+        };
+
+        if values.len() < 4 || values[..4].iter().all(|v| *v <= 0) {
+            return None;
+        }
+
+        Some(Strut {
+            left: values[0],
+            right: values[1],
+            top: values[2],
+            bottom: values[3],
+            left_span: span(4),
+            right_span: span(6),
+            top_span: span(8),
+            bottom_span: span(10),
+        })
+    }
+}
+
+/**
+ * Checks if an inclusive span overlaps the range [start, end)
+ */
+fn overlaps(span: (i64, i64), start: i64, end: i64) -> bool {
+    span.0 < end && span.1 >= start
+}
+
+/**
+ * Shrinks an area so it stays clear of the struts. Struts are measured from
+ * the edges of the whole screen, and only those touching the area count.
+ */
+pub fn apply<'a, I>(
+    struts: I,
+    position: Vector2D<i32>,
+    size: Vector2D<u32>,
+    screen: Vector2D<u32>,
+) -> (Vector2D<i32>, Vector2D<u32>)
+where
+    I: IntoIterator<Item = &'a Strut>,
+{
+    let (width, height) = (screen.x as i64, screen.y as i64);
+    let (mut x0, mut y0) = (position.x as i64, position.y as i64);
+    let (mut x1, mut y1) = (x0 + size.x as i64, y0 + size.y as i64);
+    let (left, top, right, bottom) = (x0, y0, x1, y1);
+
+    for strut in struts {
+        if strut.left > left && overlaps(strut.left_span, top, bottom) {
+            x0 = x0.max(strut.left);
+        }
+        if width - strut.right < right && overlaps(strut.right_span, top, bottom) {
+            x1 = x1.min(width - strut.right);
+        }
+        if strut.top > top && overlaps(strut.top_span, left, right) {
+            y0 = y0.max(strut.top);
+        }
+        if height - strut.bottom < bottom && overlaps(strut.bottom_span, left, right) {
+            y1 = y1.min(height - strut.bottom);
+        }
+    }
+
+    // Panels that would swallow the whole area are ignored
+    if x1 <= x0 || y1 <= y0 {
+        return (position, size);
+    }
+
+    (
+        Vector2D::new(x0 as i32, y0 as i32),
+        Vector2D::new((x1 - x0) as u32, (y1 - y0) as u32),
+    )
+}
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/**
+ * A list of pending timeouts, fired from the event loop
+ */
+pub struct Timers<T> {
+    pending: Vec<(Instant, T)>,
+}
+
+impl<T: PartialEq> Timers<T> {
+    pub fn new() -> Timers<T> {
+        Timers {
+            pending: Vec::new(),
+        }
+    }
+
+    /**
+     * Fires a timeout after the given duration, replacing an identical pending one
+     */
+    pub fn schedule(&mut self, after: Duration, timeout: T) {
+        self.cancel(&timeout);
+        self.pending.push((Instant::now() + after, timeout));
+    }
+
+    /**
+     * Removes a pending timeout
+     */
+    pub fn cancel(&mut self, timeout: &T) {
+        self.pending.retain(|(_, t)| t != timeout);
+    }
+
+    /**
+     * Gets the time until the next timeout fires
+     */
+    pub fn next_delay(&self) -> Option<Duration> {
+        let now = Instant::now();
+
+        self.pending
+            .iter()
+            .map(|(deadline, _)| deadline.saturating_duration_since(now))
+            .min()
+    }
+
+    /**
+     * Takes all the timeouts that are due
+     */
+    pub fn expired(&mut self) -> Vec<T> {
+        let now = Instant::now();
+        let (due, pending) = self
+            .pending
+            .drain(..)
+            .partition(|(deadline, _)| *deadline <= now);
+        self.pending = pending;
+
+        due.into_iter().map(|(_, t)| t).collect::<Vec<T>>()
+    }
+}
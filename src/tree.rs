@@ -0,0 +1,603 @@
+use layout::{self, Area, Layout};
+use std::collections::HashMap;
+use vector2d::Vector2D;
+use workspaces;
+use x11::xlib;
+use {focus_window, frame_client, raise_window, WindowManager};
+
+/**
+ * Share of the space of its container a new child gets
+ */
+const SHARE: u32 = 100;
+
+/**
+ * How much the share of a child changes for each resize from the keyboard
+ */
+const SHARE_STEP: u32 = 10;
+
+/**
+ * A part of the tree of a workspace, either a window or a container that
+ * splits its space between its children
+ */
+pub enum Node {
+    Client(xlib::Window),
+    Split(Split),
+}
+
+/**
+ * A container splitting its space between its children side by side or
+ * above each other, in proportion to their shares. A tabbed container gives
+ * each child all of it instead and only shows one, with tabs on its
+ * titlebar.
+ */
+pub struct Split {
+    pub vertical: bool,
+    pub tabbed: bool,
+    pub children: Vec<Node>,
+    pub shares: Vec<u32>,
+}
+
+/**
+ * The windows of a workspace in their containers, and the window new
+ * windows open next to: the last one focused. Every window on the workspace
+ * is in it, and only the tiled ones take space in the tree layout.
+ */
+pub struct Tree {
+    root: Node,
+    anchor: Option<xlib::Window>,
+}
+
+impl Node {
+    /**
+     * Checks if a window is somewhere in the node
+     */
+    fn contains(&self, _w: xlib::Window) -> bool {
+        match *self {
+            Node::Client(w) => w == _w,
+            Node::Split(ref split) => split.children.iter().any(|c| c.contains(_w)),
+        }
+    }
+
+    /**
+     * Checks if any of the given windows is somewhere in the node
+     */
+    fn shows_any(&self, windows: &[xlib::Window]) -> bool {
+        windows.iter().any(|w| self.contains(*w))
+    }
+
+    /**
+     * Gives the window shown for the node in a tab: the first of its tiled
+     * windows not kept behind another
+     */
+    fn front(&self, _wm: &WindowManager) -> Option<xlib::Window> {
+        match *self {
+            Node::Client(w) => Some(w).filter(|w| layout::tiles(_wm, *w)),
+            Node::Split(ref split) => {
+                let mut windows = Vec::new();
+                for child in split.children.iter() {
+                    windows.extend(child.front(_wm));
+                }
+
+                windows
+                    .iter()
+                    .find(|w| _wm.windows.get(w).is_some_and(|win| !win.covered))
+                    .or_else(|| windows.first())
+                    .cloned()
+            }
+        }
+    }
+
+    /**
+     * Finds the innermost tabbed container around a window, along with the
+     * tab the window is in
+     */
+    fn tabbed_of(&self, _w: xlib::Window) -> Option<(&Split, usize)> {
+        let split = match *self {
+            Node::Split(ref split) => split,
+            Node::Client(_) => return None,
+        };
+
+        let index = split.children.iter().position(|c| c.contains(_w))?;
+        split.children[index].tabbed_of(_w).or(if split.tabbed {
+            Some((split, index))
+        } else {
+            None
+        })
+    }
+
+    /**
+     * Collects the given windows that a tabbed container keeps out of sight:
+     * all but those in the tab of the focused window, or else in the tab
+     * shown before
+     */
+    fn hidden(&self, _wm: &WindowManager, windows: &[xlib::Window], out: &mut Vec<xlib::Window>) {
+        let split = match *self {
+            Node::Split(ref split) => split,
+            Node::Client(_) => return,
+        };
+
+        let shown: Vec<&Node> = split
+            .children
+            .iter()
+            .filter(|c| c.shows_any(windows))
+            .collect();
+        if !split.tabbed || shown.is_empty() {
+            for child in shown {
+                child.hidden(_wm, windows, out);
+            }
+            return;
+        }
+
+        let active = shown
+            .iter()
+            .position(|c| _wm.focused.is_some_and(|f| c.contains(f)))
+            .or_else(|| {
+                shown.iter().position(|c| {
+                    windows
+                        .iter()
+                        .any(|w| c.contains(*w) && !_wm.windows[w].covered)
+                })
+            })
+            .unwrap_or(0);
+
+        for (i, child) in shown.into_iter().enumerate() {
+            if i == active {
+                child.hidden(_wm, windows, out);
+            } else {
+                out.extend(windows.iter().filter(|w| child.contains(**w)));
+            }
+        }
+    }
+
+    /**
+     * Collects the windows in the node, in order
+     */
+    fn clients(&self, out: &mut Vec<xlib::Window>) {
+        match *self {
+            Node::Client(w) => out.push(w),
+            Node::Split(ref split) => {
+                for child in split.children.iter() {
+                    child.clients(out);
+                }
+            }
+        }
+    }
+
+    /**
+     * Drops a window from the node, along with the containers it leaves
+     * empty, telling if it was there
+     */
+    fn remove(&mut self, _w: xlib::Window) -> bool {
+        let split = match *self {
+            Node::Split(ref mut split) => split,
+            Node::Client(_) => return false,
+        };
+
+        for i in 0..split.children.len() {
+            let (found, drop) = match split.children[i] {
+                Node::Client(w) => (w == _w, true),
+                ref mut child => {
+                    let found = child.remove(_w);
+                    (
+                        found,
+                        matches!(*child, Node::Split(ref s) if s.children.is_empty()),
+                    )
+                }
+            };
+            if !found {
+                continue;
+            }
+
+            if drop {
+                split.children.remove(i);
+                split.shares.remove(i);
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /**
+     * Grows or shrinks the share of the part around a window in the
+     * innermost container splitting it the given way, telling if there was
+     * one
+     */
+    fn resize(&mut self, _w: xlib::Window, vertical: bool, grow: bool) -> bool {
+        let split = match *self {
+            Node::Split(ref mut split) => split,
+            Node::Client(_) => return false,
+        };
+
+        let index = match split.children.iter().position(|c| c.contains(_w)) {
+            Some(index) => index,
+            None => return false,
+        };
+        if split.children[index].resize(_w, vertical, grow) {
+            return true;
+        }
+        if split.tabbed || split.vertical != vertical || split.children.len() < 2 {
+            return false;
+        }
+
+        let share = &mut split.shares[index];
+        *share = if grow {
+            (*share + SHARE_STEP).min(SHARE * 10)
+        } else {
+            share.saturating_sub(SHARE_STEP).max(SHARE_STEP)
+        };
+        true
+    }
+
+    /**
+     * Has two windows trade places in the node
+     */
+    fn swap(&mut self, a: xlib::Window, b: xlib::Window) {
+        match *self {
+            Node::Client(ref mut w) if *w == a => *w = b,
+            Node::Client(ref mut w) if *w == b => *w = a,
+            Node::Client(_) => {}
+            Node::Split(ref mut split) => {
+                for child in split.children.iter_mut() {
+                    child.swap(a, b);
+                }
+            }
+        }
+    }
+
+    /**
+     * Finds the container holding a window directly
+     */
+    fn parent_of(&mut self, _w: xlib::Window) -> Option<&mut Split> {
+        let split = match *self {
+            Node::Split(ref mut split) => split,
+            Node::Client(_) => return None,
+        };
+
+        if split
+            .children
+            .iter()
+            .any(|c| matches!(*c, Node::Client(w) if w == _w))
+        {
+            return Some(split);
+        }
+
+        split
+            .children
+            .iter_mut()
+            .find_map(|child| child.parent_of(_w))
+    }
+
+    /**
+     * Works out the place of every given window in the node. Children
+     * without any of them take no space, and the others split it by their
+     * shares.
+     */
+    fn places(&self, area: Area, windows: &[xlib::Window], out: &mut HashMap<xlib::Window, Area>) {
+        let split = match *self {
+            Node::Client(w) => {
+                out.insert(w, area);
+                return;
+            }
+            Node::Split(ref split) => split,
+        };
+
+        let shown: Vec<(&Node, u32)> = split
+            .children
+            .iter()
+            .zip(split.shares.iter().cloned())
+            .filter(|(c, _)| c.shows_any(windows))
+            .collect();
+        let (origin, size) = area;
+        let total: u32 = shown.iter().map(|(_, share)| share).sum();
+        let mut before = 0;
+
+        for (child, share) in shown {
+            let (start, end) = (before, before + share);
+            before = end;
+
+            let part = if split.tabbed {
+                area
+            } else if split.vertical {
+                let (top, bottom) = (
+                    (size.y as u64 * start as u64 / total as u64) as u32,
+                    (size.y as u64 * end as u64 / total as u64) as u32,
+                );
+                (
+                    Vector2D::new(origin.x, origin.y + top as i32),
+                    Vector2D::new(size.x, bottom - top),
+                )
+            } else {
+                let (left, right) = (
+                    (size.x as u64 * start as u64 / total as u64) as u32,
+                    (size.x as u64 * end as u64 / total as u64) as u32,
+                );
+                (
+                    Vector2D::new(origin.x + left as i32, origin.y),
+                    Vector2D::new(right - left, size.y),
+                )
+            };
+
+            child.places(part, windows, out);
+        }
+    }
+}
+
+impl Tree {
+    /**
+     * Creates the tree of a workspace without any windows
+     */
+    pub fn new() -> Tree {
+        Tree {
+            root: Node::Split(Split {
+                vertical: false,
+                tabbed: false,
+                children: Vec::new(),
+                shares: Vec::new(),
+            }),
+            anchor: None,
+        }
+    }
+
+    /**
+     * Checks if a window is on the workspace of the tree
+     */
+    pub fn contains(&self, _w: xlib::Window) -> bool {
+        self.root.contains(_w)
+    }
+
+    /**
+     * Checks if the workspace of the tree holds no windows
+     */
+    pub fn is_empty(&self) -> bool {
+        matches!(self.root, Node::Split(ref split) if split.children.is_empty())
+    }
+
+    /**
+     * Gives the windows in the tree, in order
+     */
+    pub fn clients(&self) -> Vec<xlib::Window> {
+        let mut clients = Vec::new();
+        self.root.clients(&mut clients);
+        clients
+    }
+
+    /**
+     * Adds a window next to the last focused one in its container, or at
+     * the end of the tree
+     */
+    pub fn insert(&mut self, _w: xlib::Window) {
+        if self.root.contains(_w) {
+            return;
+        }
+
+        let sibling = self.anchor;
+        let parent = match sibling {
+            Some(a) => self.root.parent_of(a),
+            None => None,
+        };
+
+        match parent {
+            Some(split) => {
+                let index = split
+                    .children
+                    .iter()
+                    .position(|c| matches!(*c, Node::Client(w) if Some(w) == sibling))
+                    .unwrap();
+                split.children.insert(index + 1, Node::Client(_w));
+                split.shares.insert(index + 1, SHARE);
+            }
+            None => {
+                if let Node::Split(ref mut split) = self.root {
+                    split.children.push(Node::Client(_w));
+                    split.shares.push(SHARE);
+                }
+            }
+        }
+    }
+
+    /**
+     * Takes a window out of the tree, telling if it was in it
+     */
+    pub fn remove(&mut self, _w: xlib::Window) -> bool {
+        if self.anchor == Some(_w) {
+            self.anchor = None;
+        }
+        self.root.remove(_w)
+    }
+
+    /**
+     * Adds the windows of another tree at the end of this one
+     */
+    pub fn absorb(&mut self, other: Tree) {
+        for _w in other.clients() {
+            self.insert(_w);
+        }
+    }
+
+    /**
+     * Has two windows trade places in the tree
+     */
+    pub fn swap(&mut self, a: xlib::Window, b: xlib::Window) {
+        self.root.swap(a, b);
+    }
+
+    /**
+     * Grows or shrinks the part of the tree around a window side by side or
+     * above each other, telling if anything changed
+     */
+    pub fn resize(&mut self, _w: xlib::Window, vertical: bool, grow: bool) -> bool {
+        self.root.resize(_w, vertical, grow)
+    }
+}
+
+/**
+ * Nested containers of windows, split side by side or above each other as
+ * asked for from the keyboard, like i3
+ */
+pub struct Containers;
+
+impl Layout for Containers {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn arrange(
+        &self,
+        _wm: &WindowManager,
+        workspace: usize,
+        area: Area,
+        windows: &[xlib::Window],
+    ) -> Vec<Area> {
+        let mut places = HashMap::new();
+        if let Some(ws) = _wm.workspaces.get(workspace) {
+            ws.tree.root.places(area, windows, &mut places);
+        }
+
+        windows
+            .iter()
+            .map(|w| places.get(w).cloned().unwrap_or(area))
+            .collect()
+    }
+
+    fn hidden(
+        &self,
+        _wm: &WindowManager,
+        workspace: usize,
+        windows: &[xlib::Window],
+    ) -> Vec<xlib::Window> {
+        let mut hidden = Vec::new();
+        if let Some(ws) = _wm.workspaces.get(workspace) {
+            ws.tree.root.hidden(_wm, windows, &mut hidden);
+        }
+        hidden
+    }
+
+    fn stacked(&self) -> bool {
+        true
+    }
+}
+
+/**
+ * Has the windows opened next to the focused one split its space with it
+ * side by side or above each other. A container holding only the focused
+ * window changes its direction instead.
+ */
+pub fn split(_wm: &mut WindowManager, vertical: bool) {
+    let focused = match _wm.focused {
+        Some(_w) => _w,
+        None => return,
+    };
+    let workspace = workspaces::of(_wm, focused);
+    if layout::name_of(_wm, workspace) != "tree" {
+        return;
+    }
+
+    let parent = match _wm.workspaces[workspace].tree.root.parent_of(focused) {
+        Some(parent) => parent,
+        None => return,
+    };
+
+    debug!(
+        "Splitting {} {}",
+        focused,
+        if vertical {
+            "vertically"
+        } else {
+            "horizontally"
+        }
+    );
+
+    if parent.children.len() == 1 {
+        parent.vertical = vertical;
+        return;
+    }
+
+    let index = parent
+        .children
+        .iter()
+        .position(|c| matches!(*c, Node::Client(w) if w == focused))
+        .unwrap();
+    parent.children[index] = Node::Split(Split {
+        vertical,
+        tabbed: false,
+        children: vec![Node::Client(focused)],
+        shares: vec![SHARE],
+    });
+}
+
+/**
+ * Turns the container of the focused window into a tabbed one, or back into
+ * a split
+ */
+pub fn toggle_tabbed(_wm: &mut WindowManager) {
+    let focused = match _wm.focused {
+        Some(_w) => _w,
+        None => return,
+    };
+    let workspace = workspaces::of(_wm, focused);
+    if layout::name_of(_wm, workspace) != "tree" {
+        return;
+    }
+
+    if let Some(parent) = _wm.workspaces[workspace].tree.root.parent_of(focused) {
+        parent.tabbed = !parent.tabbed;
+        debug!("Container of {} tabbed: {}", focused, parent.tabbed);
+        layout::schedule(_wm, workspace);
+    }
+}
+
+/**
+ * Gives the windows shown in the tabs around a frame, and which one of them
+ * is its own, when the frame is in a tabbed container of a workspace using
+ * the tree layout
+ */
+pub fn tabs(_wm: &WindowManager, frame: xlib::Window) -> Option<(Vec<xlib::Window>, usize)> {
+    let _w = frame_client(_wm, frame)?;
+    let workspace = workspaces::of(_wm, _w);
+    if layout::name_of(_wm, workspace) != "tree" || !layout::tiles(_wm, _w) {
+        return None;
+    }
+    let (split, _) = _wm.workspaces[workspace].tree.root.tabbed_of(_w)?;
+
+    // Tabs holding only floating windows are left out
+    let mut tabs = Vec::new();
+    let mut index = 0;
+    for child in split.children.iter() {
+        if let Some(front) = child.front(_wm) {
+            if child.contains(_w) {
+                index = tabs.len();
+            }
+            tabs.push(front);
+        }
+    }
+
+    Some((tabs, index))
+}
+
+/**
+ * Shows the window of a tab picked on the titlebar of a frame
+ */
+pub fn select_tab(_wm: &mut WindowManager, frame: xlib::Window, index: usize) {
+    let target = tabs(_wm, frame).and_then(|(tabs, _)| tabs.get(index).cloned());
+
+    if let Some(_w) = target {
+        layout::reveal(_wm, _w);
+        raise_window(_wm, _w);
+        focus_window(_wm, Some(_w));
+    }
+}
+
+/**
+ * Keeps track of the window new ones open next to in the tree of its
+ * workspace
+ */
+pub fn focus_changed(_wm: &mut WindowManager, workspace: usize) {
+    let focused = _wm.focused;
+
+    if let Some(ws) = _wm.workspaces.get_mut(workspace) {
+        if let Some(_w) = focused.filter(|w| ws.tree.contains(*w)) {
+            ws.tree.anchor = Some(_w);
+        }
+    }
+}
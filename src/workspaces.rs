@@ -0,0 +1,541 @@
+use bar;
+use config::Config;
+use layout;
+use std::cmp::{min, Ordering};
+use std::collections::HashMap;
+use std::mem;
+use tree::Tree;
+use x11::{keysym, xlib};
+use {
+    focus_window, get_geometry, publish_window_desktop, publish_workarea, set_cardinal_list,
+    set_showing_desktop, set_string_property, unfocus_window, WindowManager,
+};
+
+/**
+ * A workspace, either listed in the configuration or created on demand by
+ * name and removed again once it is left empty
+ */
+pub struct Workspace {
+    pub name: String,
+    pub master_width: Option<u32>,
+    pub layout: Option<String>,
+    pub tree: Tree,
+    dynamic: bool,
+    largest: Option<(Vec<xlib::Window>, Option<xlib::Window>)>,
+}
+
+/**
+ * Number keys that pick the workspace of the same number in the workspace
+ * shortcuts
+ */
+const KEYS: [u32; 9] = [
+    keysym::XK_1,
+    keysym::XK_2,
+    keysym::XK_3,
+    keysym::XK_4,
+    keysym::XK_5,
+    keysym::XK_6,
+    keysym::XK_7,
+    keysym::XK_8,
+    keysym::XK_9,
+];
+
+/**
+ * Modifiers that tell the workspace shortcuts apart
+ */
+const MODIFIERS: u32 = xlib::Mod1Mask | xlib::Mod4Mask | xlib::ShiftMask | xlib::ControlMask;
+
+/**
+ * What a workspace shortcut does with the workspace of its number
+ */
+#[derive(Clone, Copy)]
+enum Shortcut {
+    Switch,
+    Send,
+    SendAndFollow,
+}
+
+/**
+ * Modifiers held with a number key for each workspace shortcut
+ */
+const SHORTCUTS: [(u32, Shortcut); 3] = [
+    (xlib::Mod4Mask, Shortcut::Switch),
+    (xlib::Mod4Mask | xlib::ShiftMask, Shortcut::Send),
+    (
+        xlib::Mod4Mask | xlib::ControlMask | xlib::ShiftMask,
+        Shortcut::SendAndFollow,
+    ),
+];
+
+/**
+ * Builds the workspaces listed in the configuration
+ */
+pub fn configured(config: &Config) -> Vec<Workspace> {
+    config
+        .workspaces
+        .iter()
+        .map(|name| Workspace {
+            name: name.clone(),
+            master_width: None,
+            layout: None,
+            tree: Tree::new(),
+            dynamic: false,
+            largest: None,
+        })
+        .collect()
+}
+
+/**
+ * Gives the workspace whose tree holds a window. Every managed window is on
+ * one, others are taken to be on the current workspace.
+ */
+pub fn of(_wm: &WindowManager, _w: xlib::Window) -> usize {
+    _wm.workspaces
+        .iter()
+        .position(|ws| ws.tree.contains(_w))
+        .unwrap_or(_wm.workspace)
+}
+
+/**
+ * Finds a workspace by name, creating it when there is none
+ */
+pub fn find_or_create(_wm: &mut WindowManager, name: &str) -> usize {
+    if let Some(index) = _wm.workspaces.iter().position(|ws| ws.name == name) {
+        return index;
+    }
+
+    info!("Creating workspace {}", name);
+    _wm.workspaces.push(Workspace {
+        name: name.to_string(),
+        master_width: None,
+        layout: None,
+        tree: Tree::new(),
+        dynamic: true,
+        largest: None,
+    });
+
+    update_names(_wm);
+    publish_workarea(_wm);
+    _wm.workspaces.len() - 1
+}
+
+/**
+ * Switches to a workspace by name, creating it when there is none
+ */
+pub fn switch_to_name(_wm: &mut WindowManager, name: &str) {
+    let index = find_or_create(_wm, name);
+    switch(_wm, index);
+}
+
+/**
+ * Maps the frames of the windows on the current workspace and unmaps all
+ * others
+ */
+fn show(_wm: &WindowManager) {
+    let tree = &_wm.workspaces[_wm.workspace].tree;
+
+    for (w, win) in _wm.windows.iter() {
+        unsafe {
+            if tree.contains(*w) && !win.minimized && !win.hidden && !win.covered {
+                xlib::XMapWindow(_wm.display, win.frame);
+            } else {
+                xlib::XUnmapWindow(_wm.display, win.frame);
+            }
+        }
+    }
+}
+
+/**
+ * Shows the windows of a workspace and hides all others
+ */
+pub fn switch(_wm: &mut WindowManager, index: usize) {
+    if index == _wm.workspace || index >= _wm.workspaces.len() {
+        return;
+    }
+
+    debug!("Switching to workspace {}", _wm.workspaces[index].name);
+    set_showing_desktop(_wm, false);
+    _wm.workspace = index;
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_current_desktop,
+        &[index as i64],
+    );
+
+    show(_wm);
+
+    // The focus goes back to the window that had it when this workspace
+    // was left, if it is still here and shown
+    let remembered = _wm.workspace_focus.get(&index).cloned().filter(|w| {
+        _wm.workspaces[index].tree.contains(*w)
+            && _wm.windows.get(w).is_some_and(|win| !win.minimized)
+    });
+
+    let focused_here = _wm.focused.is_some_and(|w| of(_wm, w) == index);
+
+    if remembered.is_some() || !focused_here {
+        focus_window(_wm, remembered);
+    }
+
+    remove_empty(_wm);
+    bar::update(_wm);
+    bar::draw(_wm);
+}
+
+/**
+ * Removes the workspaces created on demand that hold no windows anymore,
+ * apart from the one shown. Later workspaces move down to fill the gap.
+ */
+pub fn remove_empty(_wm: &mut WindowManager) {
+    let mut removed = false;
+
+    while let Some(index) = (0.._wm.workspaces.len()).rev().find(|i| {
+        _wm.workspaces[*i].dynamic && *i != _wm.workspace && _wm.workspaces[*i].tree.is_empty()
+    }) {
+        debug!("Removing empty workspace {}", _wm.workspaces[index].name);
+        _wm.workspaces.remove(index);
+        removed = true;
+
+        let map: Vec<Option<usize>> = (0.._wm.workspaces.len() + 1)
+            .map(|i| match i.cmp(&index) {
+                Ordering::Less => Some(i),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(i - 1),
+            })
+            .collect();
+        renumber(_wm, &map);
+    }
+
+    if removed {
+        update_names(_wm);
+        publish_workarea(_wm);
+    }
+}
+
+/**
+ * Moves everything that refers to a workspace by number to its new number,
+ * looked up by the old one, and forgets what referred to a removed one. The
+ * windows came along with the trees of their workspaces already, and only
+ * need to tell clients where they are now.
+ */
+fn renumber(_wm: &mut WindowManager, map: &[Option<usize>]) {
+    let new = |old: usize| map.get(old).cloned().flatten();
+
+    let mut moved = Vec::new();
+    for (old, to) in map.iter().enumerate() {
+        if let Some(to) = to.filter(|to| *to != old) {
+            moved.extend(_wm.workspaces[to].tree.clients());
+        }
+    }
+
+    for _w in moved {
+        publish_window_desktop(_wm, _w);
+    }
+
+    _wm.workspace = new(_wm.workspace).unwrap_or(0);
+    _wm.relayout = _wm.relayout.drain().filter_map(new).collect();
+
+    // Workspaces merged on reload keep the focus of the one that was there,
+    // or else of the first one moved onto it
+    let mut remembered: Vec<(usize, xlib::Window)> = _wm.workspace_focus.drain().collect();
+    remembered.sort();
+    let mut focus = HashMap::new();
+    for (old, _w) in remembered {
+        let to = match new(old) {
+            Some(to) => to,
+            None => continue,
+        };
+        if old == to {
+            focus.insert(to, _w);
+        } else {
+            focus.entry(to).or_insert(_w);
+        }
+    }
+    _wm.workspace_focus = focus;
+
+    for workspace in _wm.launches.values_mut() {
+        *workspace = new(*workspace).unwrap_or(0);
+    }
+
+    // The places of crashed windows on a removed workspace are forgotten
+    _wm.lost.retain_mut(|lost| match new(lost.workspace) {
+        Some(workspace) => {
+            lost.workspace = workspace;
+            true
+        }
+        None => false,
+    });
+}
+
+/**
+ * Replaces the workspaces with the ones of a new configuration. Windows on
+ * workspaces that are no longer listed end up on the last one, and those
+ * created on demand are kept.
+ */
+pub fn reload(_wm: &mut WindowManager) {
+    let old = mem::replace(&mut _wm.workspaces, configured(&_wm.config));
+    let last = _wm.workspaces.len() - 1;
+
+    let mut map = Vec::new();
+    for (i, mut workspace) in old.into_iter().enumerate() {
+        let found = _wm
+            .workspaces
+            .iter()
+            .position(|ws| ws.name == workspace.name);
+
+        // Workspaces still listed keep the master width and layout they
+        // were left with
+        if let Some(index) = found {
+            _wm.workspaces[index].master_width = workspace.master_width;
+            _wm.workspaces[index].layout = workspace.layout.clone();
+        }
+
+        let index = match found {
+            Some(index) if workspace.dynamic => index,
+            None if workspace.dynamic => {
+                _wm.workspaces.push(workspace);
+                map.push(Some(_wm.workspaces.len() - 1));
+                continue;
+            }
+            _ => min(i, last),
+        };
+
+        // The windows go where their workspace went, in their containers
+        // unless they end up with others
+        let tree = mem::replace(&mut workspace.tree, Tree::new());
+        let target = &mut _wm.workspaces[index].tree;
+        if target.is_empty() {
+            *target = tree;
+        } else {
+            target.absorb(tree);
+        }
+        map.push(Some(index));
+    }
+
+    renumber(_wm, &map);
+    show(_wm);
+
+    let focused_here = _wm.focused.is_some_and(|w| of(_wm, w) == _wm.workspace);
+    if !focused_here {
+        focus_window(_wm, None);
+    }
+
+    remove_empty(_wm);
+}
+
+/**
+ * Moves to the previous or next workspace, stopping at the first and last
+ */
+pub fn step(_wm: &mut WindowManager, forward: bool) {
+    let index = if forward {
+        min(_wm.workspace + 1, _wm.workspaces.len() - 1)
+    } else {
+        _wm.workspace.saturating_sub(1)
+    };
+
+    switch(_wm, index);
+}
+
+/**
+ * Moves a window to another workspace
+ */
+pub fn send_to(_wm: &mut WindowManager, _w: xlib::Window, index: usize) {
+    if index >= _wm.workspaces.len() || !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    let previous = of(_wm, _w);
+    if previous != index {
+        _wm.workspaces[previous].tree.remove(_w);
+        _wm.workspaces[index].tree.insert(_w);
+    }
+
+    if let Some(win) = _wm.windows.get(&_w) {
+        unsafe {
+            if index == _wm.workspace && !win.minimized && !win.hidden && !win.covered {
+                xlib::XMapWindow(_wm.display, win.frame);
+            } else {
+                xlib::XUnmapWindow(_wm.display, win.frame);
+            }
+        }
+    }
+
+    publish_window_desktop(_wm, _w);
+    layout::schedule(_wm, previous);
+    layout::schedule(_wm, index);
+
+    if index != _wm.workspace {
+        unfocus_window(_wm, _w);
+    }
+    remove_empty(_wm);
+    update_names(_wm);
+}
+
+/**
+ * Moves a window to another workspace and switches there with it
+ */
+pub fn send_and_follow(_wm: &mut WindowManager, _w: xlib::Window, index: usize) {
+    if index >= _wm.workspaces.len() || !_wm.windows.contains_key(&_w) {
+        return;
+    }
+
+    send_to(_wm, _w, index);
+    switch(_wm, index);
+    focus_window(_wm, Some(_w));
+}
+
+/**
+ * Finds the largest visible window of a workspace. The answer is kept until
+ * windows are mapped, unmapped or moved on or off the workspace, so focus
+ * changes don't ask the server for every frame again
+ */
+fn largest(_wm: &mut WindowManager, index: usize) -> Option<xlib::Window> {
+    let visible: Vec<xlib::Window> = _wm.workspaces[index]
+        .tree
+        .clients()
+        .into_iter()
+        .filter(|w| !_wm.windows[w].minimized)
+        .collect();
+
+    if let Some((ref windows, largest)) = _wm.workspaces[index].largest {
+        if *windows == visible {
+            return largest;
+        }
+    }
+
+    let largest = visible.iter().cloned().max_by_key(|w| {
+        let (_, size) = get_geometry(_wm, _wm.windows[w].frame);
+        size.x as u64 * size.y as u64
+    });
+    _wm.workspaces[index].largest = Some((visible, largest));
+    largest
+}
+
+/**
+ * Names a workspace after the class of its focused window, or of its largest
+ * one when the focus is elsewhere
+ */
+fn autoname(_wm: &mut WindowManager, index: usize) -> String {
+    if !_wm.config.workspace_autoname {
+        return _wm.workspaces[index].name.clone();
+    }
+
+    let focused = _wm
+        .focused
+        .filter(|w| _wm.workspaces[index].tree.contains(*w) && !_wm.windows[w].minimized);
+    let dominant = focused
+        .or_else(|| largest(_wm, index))
+        .map(|w| &_wm.windows[&w]);
+    let name = &_wm.workspaces[index].name;
+
+    // WM_CLASS holds the instance first and the class second
+    match dominant.and_then(|win| win.class.get(1).or_else(|| win.class.first())) {
+        Some(class) if !class.is_empty() => format!("{}: {}", name, class),
+        _ => name.clone(),
+    }
+}
+
+/**
+ * Renames the workspaces after the windows on them, telling pagers and
+ * redrawing the bar when a name changed
+ */
+pub fn update_names(_wm: &mut WindowManager) {
+    let names: Vec<String> = (0.._wm.workspaces.len())
+        .map(|i| autoname(_wm, i))
+        .collect();
+
+    if names != _wm.workspace_names {
+        _wm.workspace_names = names;
+        publish(_wm);
+        bar::draw(_wm);
+    }
+}
+
+/**
+ * Publishes the workspaces on the root window for pagers
+ */
+pub fn publish(_wm: &WindowManager) {
+    let names: String = _wm
+        .workspace_names
+        .iter()
+        .map(|name| format!("{}\0", name))
+        .collect();
+
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_number_of_desktops,
+        &[_wm.workspaces.len() as i64],
+    );
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_current_desktop,
+        &[_wm.workspace as i64],
+    );
+    set_string_property(_wm, _wm.root, _wm.atoms.net_desktop_names, &names);
+    set_cardinal_list(
+        _wm,
+        _wm.root,
+        _wm.atoms.net_showing_desktop,
+        &[_wm.showing_desktop as i64],
+    );
+}
+
+/**
+ * Grabs or releases the workspace shortcuts on the root window
+ */
+pub fn grab_keys(display: *mut xlib::Display, root: xlib::Window, grab: bool) {
+    for sym in KEYS.iter() {
+        for (modifiers, _) in SHORTCUTS.iter() {
+            unsafe {
+                let keycode = xlib::XKeysymToKeycode(display, *sym as u64) as i32;
+                if grab {
+                    xlib::XGrabKey(
+                        display,
+                        keycode,
+                        *modifiers,
+                        root,
+                        0,
+                        xlib::GrabModeAsync,
+                        xlib::GrabModeAsync,
+                    );
+                } else {
+                    xlib::XUngrabKey(display, keycode, *modifiers, root);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Runs a workspace shortcut when one was pressed, telling if it was one
+ */
+pub fn on_key_press(_wm: &mut WindowManager, _e: &xlib::XKeyEvent) -> bool {
+    let shortcut = SHORTCUTS
+        .iter()
+        .find(|(modifiers, _)| _e.state & MODIFIERS == *modifiers)
+        .map(|(_, shortcut)| *shortcut);
+
+    let index = KEYS.iter().position(|sym| {
+        _e.keycode == unsafe { xlib::XKeysymToKeycode(_wm.display, *sym as u64) as u32 }
+    });
+
+    match (shortcut, index) {
+        (Some(Shortcut::Switch), Some(index)) => switch(_wm, index),
+        (Some(Shortcut::Send), Some(index)) => {
+            if let Some(_w) = _wm.focused {
+                send_to(_wm, _w, index);
+            }
+        }
+        (Some(Shortcut::SendAndFollow), Some(index)) => {
+            if let Some(_w) = _wm.focused {
+                send_and_follow(_wm, _w, index);
+            }
+        }
+        _ => return false,
+    }
+
+    true
+}
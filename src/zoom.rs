@@ -0,0 +1,216 @@
+use cairo_sys;
+use monitor;
+use shape;
+use std::mem::zeroed;
+use std::time::Duration;
+use vector2d::Vector2D;
+use x11::xlib;
+use {name_window, pointer_position, Timer, WindowManager};
+
+/**
+ * How often the magnified view is painted again, to follow what the window
+ * shows and where the pointer goes
+ */
+pub const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ * Size of the magnified view
+ */
+const SIZE: (u32, u32) = (480, 320);
+
+/**
+ * Distance of the magnified view from the edges of the monitor
+ */
+const MARGIN: i32 = 16;
+
+/**
+ * A floating view showing the part of a window around the pointer larger
+ */
+pub struct Zoom {
+    target: xlib::Window,
+    window: xlib::Window,
+    surface: *mut cairo_sys::cairo_surface_t,
+    context: *mut cairo_sys::cairo_t,
+    source: *mut cairo_sys::cairo_surface_t,
+}
+
+/**
+ * Gives the configured magnification as a factor, kept between 1.5x and 2x
+ */
+fn factor(_wm: &WindowManager) -> f64 {
+    _wm.config.zoom.clamp(150, 200) as f64 / 100.0
+}
+
+/**
+ * Opens the magnified view for the focused window, or closes it again
+ */
+pub fn toggle(_wm: &mut WindowManager) {
+    if _wm.zoom.is_some() {
+        close(_wm);
+    } else if let Some(_w) = _wm.focused {
+        open(_wm, _w);
+    }
+}
+
+/**
+ * Creates the magnified view for a window. The view lets clicks through,
+ * so the window can be used as it is magnified.
+ */
+fn open(_wm: &mut WindowManager, _w: xlib::Window) {
+    debug!("Zooming in on {}", _w);
+
+    unsafe {
+        let mut attrs: xlib::XWindowAttributes = zeroed();
+        if xlib::XGetWindowAttributes(_wm.display, _w, &mut attrs) == 0 {
+            return;
+        }
+
+        let screen = xlib::XDefaultScreen(_wm.display);
+        let visual = xlib::XDefaultVisual(_wm.display, screen);
+        let depth = xlib::XDefaultDepth(_wm.display, screen);
+
+        let mut attributes: xlib::XSetWindowAttributes = zeroed();
+        attributes.override_redirect = xlib::True;
+
+        let window = xlib::XCreateWindow(
+            _wm.display,
+            _wm.root,
+            0,
+            0,
+            SIZE.0,
+            SIZE.1,
+            0,
+            depth,
+            xlib::InputOutput as u32,
+            visual,
+            xlib::CWOverrideRedirect,
+            &mut attributes,
+        );
+        name_window(_wm.display, window, "gothite-zoom");
+        shape::set_click_through(_wm.display, window, true);
+
+        let surface = cairo_sys::cairo_xlib_surface_create(
+            _wm.display,
+            window,
+            visual,
+            SIZE.0 as i32,
+            SIZE.1 as i32,
+        );
+        let source = cairo_sys::cairo_xlib_surface_create(
+            _wm.display,
+            _w,
+            attrs.visual,
+            attrs.width,
+            attrs.height,
+        );
+
+        xlib::XMapRaised(_wm.display, window);
+
+        _wm.zoom = Some(Zoom {
+            target: _w,
+            window,
+            surface,
+            context: cairo_sys::cairo_create(surface),
+            source,
+        });
+    }
+
+    refresh(_wm);
+    _wm.timers.schedule(REFRESH_INTERVAL, Timer::ZoomRefresh);
+}
+
+/**
+ * Destroys the magnified view
+ */
+pub fn close(_wm: &mut WindowManager) {
+    let zoom = match _wm.zoom.take() {
+        Some(zoom) => zoom,
+        None => return,
+    };
+
+    debug!("Zooming out of {}", zoom.target);
+    _wm.timers.cancel(&Timer::ZoomRefresh);
+
+    unsafe {
+        cairo_sys::cairo_destroy(zoom.context);
+        cairo_sys::cairo_surface_destroy(zoom.surface);
+        cairo_sys::cairo_surface_destroy(zoom.source);
+        xlib::XDestroyWindow(_wm.display, zoom.window);
+    }
+}
+
+/**
+ * Closes the magnified view when the window it shows goes away
+ */
+pub fn forget(_wm: &mut WindowManager, _w: xlib::Window) {
+    if _wm.zoom.as_ref().is_some_and(|zoom| zoom.target == _w) {
+        close(_wm);
+    }
+}
+
+/**
+ * Paints the part of the window around the pointer into the view, which
+ * sits in the corner of the monitor farthest from the pointer
+ */
+pub fn refresh(_wm: &WindowManager) {
+    let zoom = match _wm.zoom {
+        Some(ref zoom) => zoom,
+        None => return,
+    };
+    let factor = factor(_wm);
+
+    unsafe {
+        let mut attrs: xlib::XWindowAttributes = zeroed();
+        if xlib::XGetWindowAttributes(_wm.display, zoom.target, &mut attrs) == 0 {
+            return;
+        }
+        cairo_sys::cairo_xlib_surface_set_size(zoom.source, attrs.width, attrs.height);
+
+        let mut origin = Vector2D::new(0, 0);
+        let mut child: xlib::Window = 0;
+        xlib::XTranslateCoordinates(
+            _wm.display,
+            zoom.target,
+            _wm.root,
+            0,
+            0,
+            &mut origin.x,
+            &mut origin.y,
+            &mut child,
+        );
+
+        let pointer = pointer_position(_wm);
+        let monitor = &_wm.monitors[monitor::index_at(&_wm.monitors, pointer)];
+
+        // Keep the view on the window, even with the pointer near its edges
+        let visible = (SIZE.0 as f64 / factor, SIZE.1 as f64 / factor);
+        let left = (pointer.x - origin.x) as f64 - visible.0 / 2.0;
+        let top = (pointer.y - origin.y) as f64 - visible.1 / 2.0;
+        let left = left.min(attrs.width as f64 - visible.0).max(0.0);
+        let top = top.min(attrs.height as f64 - visible.1).max(0.0);
+
+        let center = monitor.position + monitor.size.as_i32s() / 2;
+        let x = if pointer.x < center.x {
+            monitor.position.x + monitor.size.x as i32 - SIZE.0 as i32 - MARGIN
+        } else {
+            monitor.position.x + MARGIN
+        };
+        let y = if pointer.y < center.y {
+            monitor.position.y + monitor.size.y as i32 - SIZE.1 as i32 - MARGIN
+        } else {
+            monitor.position.y + MARGIN
+        };
+        xlib::XMoveWindow(_wm.display, zoom.window, x, y);
+        xlib::XRaiseWindow(_wm.display, zoom.window);
+
+        let _ctx = zoom.context;
+        cairo_sys::cairo_identity_matrix(_ctx);
+        cairo_sys::cairo_set_source_rgb(_ctx, 0.18, 0.204, 0.251);
+        cairo_sys::cairo_paint(_ctx);
+
+        cairo_sys::cairo_scale(_ctx, factor, factor);
+        cairo_sys::cairo_set_source_surface(_ctx, zoom.source, -left, -top);
+        cairo_sys::cairo_paint(_ctx);
+        cairo_sys::cairo_surface_flush(zoom.surface);
+    }
+}